@@ -45,6 +45,78 @@ fn main() -> Result<()> {
         assert_eq!(file_config.max_capacity, config.max_capacity)
     }
 
+    if command_line_args.blob_quota.is_some() {
+        assert_eq!(command_line_args.blob_quota, config.blob_quota)
+    } else {
+        assert_eq!(file_config.blob_quota, config.blob_quota)
+    }
+
+    if command_line_args.map_quota.is_some() {
+        assert_eq!(command_line_args.map_quota, config.map_quota)
+    } else {
+        assert_eq!(file_config.map_quota, config.map_quota)
+    }
+
+    if command_line_args.sequence_quota.is_some() {
+        assert_eq!(command_line_args.sequence_quota, config.sequence_quota)
+    } else {
+        assert_eq!(file_config.sequence_quota, config.sequence_quota)
+    }
+
+    if command_line_args.chunk_store_backend.is_some() {
+        assert_eq!(
+            command_line_args.chunk_store_backend,
+            config.chunk_store_backend
+        )
+    } else {
+        assert_eq!(file_config.chunk_store_backend, config.chunk_store_backend)
+    }
+
+    if command_line_args.chunk_shard_depth.is_some() {
+        assert_eq!(
+            command_line_args.chunk_shard_depth,
+            config.chunk_shard_depth
+        )
+    } else {
+        assert_eq!(file_config.chunk_shard_depth, config.chunk_shard_depth)
+    }
+
+    if command_line_args.chunk_cache_size.is_some() {
+        assert_eq!(command_line_args.chunk_cache_size, config.chunk_cache_size)
+    } else {
+        assert_eq!(file_config.chunk_cache_size, config.chunk_cache_size)
+    }
+
+    if command_line_args.chunk_scrub_rate.is_some() {
+        assert_eq!(command_line_args.chunk_scrub_rate, config.chunk_scrub_rate)
+    } else {
+        assert_eq!(file_config.chunk_scrub_rate, config.chunk_scrub_rate)
+    }
+
+    if command_line_args.blob_write_quorum.is_some() {
+        assert_eq!(
+            command_line_args.blob_write_quorum,
+            config.blob_write_quorum
+        )
+    } else {
+        assert_eq!(file_config.blob_write_quorum, config.blob_write_quorum)
+    }
+
+    // The passphrase never comes from the file, only from the command line or its env var.
+    assert_eq!(
+        command_line_args.encryption_passphrase,
+        config.encryption_passphrase
+    );
+
+    if command_line_args.encryption_key_file.is_some() {
+        assert_eq!(
+            command_line_args.encryption_key_file,
+            config.encryption_key_file
+        )
+    } else {
+        assert_eq!(file_config.encryption_key_file, config.encryption_key_file)
+    }
+
     if command_line_args.root_dir.is_some() {
         assert_eq!(command_line_args.root_dir, config.root_dir)
     } else {
@@ -81,6 +153,10 @@ fn main() -> Result<()> {
         config.clear_data,
         file_config.clear_data || command_line_args.clear_data
     );
+    assert_eq!(
+        config.compress_chunks,
+        file_config.compress_chunks || command_line_args.compress_chunks
+    );
 
     if command_line_args.local_addr.is_some() || command_line_args.first.is_some() {
         assert_eq!(
@@ -152,6 +152,11 @@ async fn run_node() {
         });
     }
 
+    #[cfg(unix)]
+    forward_signals(node.command_sender());
+    #[cfg(unix)]
+    forward_shutdown_signals(node.command_sender());
+
     match node.run().await {
         Ok(()) => process::exit(0),
         Err(e) => {
@@ -162,6 +167,77 @@ async fn run_node() {
     }
 }
 
+/// Has the node shut down on SIGINT or SIGTERM, within the deadline of `Node::run`.
+/// A second signal exits right away, e.g. when stuck processing duties.
+#[cfg(unix)]
+fn forward_shutdown_signals(commands: tokio::sync::mpsc::UnboundedSender<utils::Command>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let (mut interrupt, mut terminate) = match (
+        signal(SignalKind::interrupt()),
+        signal(SignalKind::terminate()),
+    ) {
+        (Ok(interrupt), Ok(terminate)) => (interrupt, terminate),
+        (Err(e), _) | (_, Err(e)) => {
+            error!("Unable to listen for signals: {:?}", e);
+            return;
+        }
+    };
+    let _handle = tokio::spawn(async move {
+        tokio::select! {
+            _ = interrupt.recv() => info!("Received SIGINT"),
+            _ = terminate.recv() => info!("Received SIGTERM"),
+        }
+        if commands.send(utils::Command::Shutdown).is_err() {
+            return;
+        }
+        tokio::select! {
+            _ = interrupt.recv() => (),
+            _ = terminate.recv() => (),
+        }
+        error!("Received another signal while shutting down, exiting");
+        process::exit(1);
+    });
+}
+
+/// Lets operators control the running node through signals:
+/// SIGUSR1 reconciles the recorded used space with the stored chunks,
+/// SIGHUP reloads the config, applying a changed max capacity,
+/// SIGUSR2 logs the scores of the adults, when an elder,
+/// SIGQUIT decommissions the node, when an adult: it leaves once
+/// the section has replicated its chunks elsewhere.
+#[cfg(unix)]
+fn forward_signals(commands: tokio::sync::mpsc::UnboundedSender<utils::Command>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let signals = [
+        (
+            SignalKind::user_defined1(),
+            utils::Command::ReconcileUsedSpace,
+        ),
+        (SignalKind::hangup(), utils::Command::ReloadConfig),
+        (SignalKind::user_defined2(), utils::Command::LogAdultScores),
+        (SignalKind::quit(), utils::Command::Decommission),
+    ];
+    for (kind, command) in signals.iter().copied() {
+        let mut signal = match signal(kind) {
+            Ok(signal) => signal,
+            Err(e) => {
+                error!("Unable to listen for signals: {:?}", e);
+                return;
+            }
+        };
+        let commands = commands.clone();
+        let _handle = tokio::spawn(async move {
+            while signal.recv().await.is_some() {
+                if commands.send(command).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
 fn update() -> Result<Status, Box<dyn (::std::error::Error)>> {
     info!("Checking for updates...");
     let target = self_update::get_target();
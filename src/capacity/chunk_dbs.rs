@@ -6,34 +6,314 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{utils, Result};
-use futures::lock::Mutex;
-use pickledb::PickleDb;
-use std::sync::Arc;
-use std::{cell::RefCell, path::Path, rc::Rc};
+use crate::{encryption::EncryptionKey, utils, Error, Result};
+use log::{error, info};
+use serde::{de::DeserializeOwned, Serialize};
+use sled::{
+    transaction::{TransactionError, TransactionResult},
+    Db, Transactional, Tree,
+};
+use std::{fs, ops::Bound, path::Path};
 
+const DB_DIR: &str = "chunk_holders";
+const BLOB_META_TREE: &str = "immutable_data";
+const HOLDER_META_TREE: &str = "holder_data";
+const FULL_ADULTS_TREE: &str = "full_adults";
+
+// The files the tables used to be kept in, imported on opening the dbs.
 const BLOB_META_DB_NAME: &str = "immutable_data.db";
 const HOLDER_META_DB_NAME: &str = "holder_data.db";
 const FULL_ADULTS_DB_NAME: &str = "full_adults.db";
-// The number of separate copies of a blob chunk which should be maintained.
+const MIGRATED_EXTENSION: &str = "migrated";
+
+/// A table of the chunk holder dbs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Table {
+    /// The holders of each chunk, by chunk address.
+    Metadata,
+    /// The chunks of each holder, by holder name.
+    Holders,
+    /// The adults reported full, by name.
+    FullAdults,
+}
+
+/// Changes to the chunk holder dbs, committed all together or not at all.
+#[derive(Default)]
+pub struct DbBatch {
+    changes: Vec<(Table, String, Option<Vec<u8>>)>,
+}
+
+impl DbBatch {
+    /// Returns an empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the value of `key` in `table`.
+    pub fn set<V: Serialize>(&mut self, table: Table, key: &str, value: &V) -> Result<()> {
+        let value = utils::serialise(value)?.to_vec();
+        self.changes.push((table, key.to_string(), Some(value)));
+        Ok(())
+    }
+
+    /// Removes `key` from `table`.
+    pub fn remove(&mut self, table: Table, key: &str) {
+        self.changes.push((table, key.to_string(), None));
+    }
+}
 
+/// The records of which adults hold which chunks, and of full adults, kept in one store with
+/// atomic multi-table commits, values encrypted if the node has an encryption key.
 #[derive(Clone)]
 pub struct ChunkHolderDbs {
-    pub metadata: Arc<Mutex<PickleDb>>,
-    pub holders: Arc<Mutex<PickleDb>>,
-    pub full_adults: Arc<Mutex<PickleDb>>,
+    db: Db,
+    metadata: Tree,
+    holders: Tree,
+    full_adults: Tree,
+    encryption: Option<EncryptionKey>,
 }
 
 impl ChunkHolderDbs {
-    ///
-    pub fn new(path: &Path) -> Result<Self> {
-        let metadata = utils::new_auto_dump_db(path, BLOB_META_DB_NAME)?;
-        let holders = utils::new_auto_dump_db(path, HOLDER_META_DB_NAME)?;
-        let full_adults = utils::new_auto_dump_db(path, FULL_ADULTS_DB_NAME)?;
-        Ok(Self {
-            metadata: Arc::new(Mutex::new(metadata)),
-            holders: Arc::new(Mutex::new(holders)),
-            full_adults: Arc::new(Mutex::new(full_adults)),
+    /// Opens the dbs under `path`, importing the tables from the files they used to be kept in.
+    pub fn new(path: &Path, encryption: Option<EncryptionKey>) -> Result<Self> {
+        let db = sled::open(path.join(DB_DIR))?;
+        let dbs = Self {
+            metadata: db.open_tree(BLOB_META_TREE)?,
+            holders: db.open_tree(HOLDER_META_TREE)?,
+            full_adults: db.open_tree(FULL_ADULTS_TREE)?,
+            db,
+            encryption,
+        };
+        dbs.migrate(path, BLOB_META_DB_NAME, Table::Metadata)?;
+        dbs.migrate(path, HOLDER_META_DB_NAME, Table::Holders)?;
+        dbs.migrate(path, FULL_ADULTS_DB_NAME, Table::FullAdults)?;
+        Ok(dbs)
+    }
+
+    // Imports a table from a `PickleDb` file, which is kept renamed, but not imported again.
+    fn migrate(&self, path: &Path, db_name: &str, table: Table) -> Result<()> {
+        let db_path = path.join(db_name);
+        if !db_path.exists() {
+            return Ok(());
+        }
+        let values = utils::read_db_values(&db_path, self.encryption.as_ref())?;
+        info!(
+            "Importing {} entries of {} into the chunk holder dbs",
+            values.len(),
+            db_name
+        );
+        let changes = values
+            .into_iter()
+            .map(|(key, value)| (table, key, Some(value)))
+            .collect();
+        self.apply(self.seal(DbBatch { changes })?)?;
+        let _ = self.db.flush()?;
+        fs::rename(&db_path, db_path.with_extension(MIGRATED_EXTENSION))?;
+        Ok(())
+    }
+
+    /// Returns the value of `key` in `table`, if present and readable.
+    pub fn get<V: DeserializeOwned>(&self, table: Table, key: &str) -> Option<V> {
+        let value = match self.tree(table).get(key) {
+            Ok(value) => value?,
+            Err(error) => {
+                error!("Could not read {} from {:?}: {}", key, table, error);
+                return None;
+            }
+        };
+        let value = match &self.encryption {
+            Some(encryption) => match encryption.open(value.to_vec(), key.as_bytes()) {
+                Ok(value) => value,
+                Err(error) => {
+                    error!("Could not decrypt the value of {}: {}", key, error);
+                    return None;
+                }
+            },
+            None => value.to_vec(),
+        };
+        utils::deserialise(&value).ok()
+    }
+
+    /// Whether `key` is present in `table`.
+    pub fn exists(&self, table: Table, key: &str) -> bool {
+        self.tree(table).contains_key(key).unwrap_or(false)
+    }
+
+    /// Up to `limit` keys of `table` in order, starting after `after`, or from the first one.
+    pub fn keys(&self, table: Table, after: Option<&str>, limit: usize) -> Vec<String> {
+        let start = match after {
+            Some(after) => Bound::Excluded(after.as_bytes()),
+            None => Bound::Unbounded,
+        };
+        self.tree(table)
+            .range::<&[u8], _>((start, Bound::Unbounded))
+            .keys()
+            .filter_map(|key| match key {
+                Ok(key) => String::from_utf8(key.to_vec()).ok(),
+                Err(error) => {
+                    error!("Could not read a key of {:?}: {}", table, error);
+                    None
+                }
+            })
+            .take(limit)
+            .collect()
+    }
+
+    /// Number of keys present in `table`.
+    pub fn count(&self, table: Table) -> usize {
+        self.tree(table).len()
+    }
+
+    /// Sets the value of `key` in `table`.
+    pub async fn set<V: Serialize>(&self, table: Table, key: &str, value: &V) -> Result<()> {
+        let mut batch = DbBatch::new();
+        batch.set(table, key, value)?;
+        self.commit(batch).await
+    }
+
+    /// Removes `key` from `table`, returning whether it was present.
+    pub async fn remove(&self, table: Table, key: &str) -> Result<bool> {
+        let present = self.exists(table, key);
+        let mut batch = DbBatch::new();
+        batch.remove(table, key);
+        self.commit(batch).await?;
+        Ok(present)
+    }
+
+    /// Applies all the changes of `batch`, or none of them, and persists them.
+    pub async fn commit(&self, batch: DbBatch) -> Result<()> {
+        self.apply(self.seal(batch)?)?;
+        let _ = self.db.flush_async().await?;
+        Ok(())
+    }
+
+    // Encrypts the values of a batch, if the node has an encryption key.
+    fn seal(&self, batch: DbBatch) -> Result<DbBatch> {
+        let changes = batch
+            .changes
+            .into_iter()
+            .map(|(table, key, value)| {
+                let value = match (value, &self.encryption) {
+                    (Some(value), Some(encryption)) => {
+                        Some(encryption.seal(&value, key.as_bytes())?)
+                    }
+                    (value, _) => value,
+                };
+                Ok((table, key, value))
+            })
+            .collect::<Result<_>>()?;
+        Ok(DbBatch { changes })
+    }
+
+    // Writes sealed changes in one transaction.
+    fn apply(&self, batch: DbBatch) -> Result<()> {
+        let result: TransactionResult<()> = (&self.metadata, &self.holders, &self.full_adults)
+            .transaction(|(metadata, holders, full_adults)| {
+                for (table, key, value) in &batch.changes {
+                    let tree = match table {
+                        Table::Metadata => metadata,
+                        Table::Holders => holders,
+                        Table::FullAdults => full_adults,
+                    };
+                    match value {
+                        Some(value) => {
+                            let _ = tree.insert(key.as_bytes(), value.as_slice())?;
+                        }
+                        None => {
+                            let _ = tree.remove(key.as_bytes())?;
+                        }
+                    }
+                }
+                Ok(())
+            });
+        result.map_err(|error| match error {
+            TransactionError::Storage(error) => Error::Sled(error),
+            TransactionError::Abort(()) => Error::Logic("Transaction aborted".to_string()),
         })
     }
+
+    /// Writes the dbs to disk.
+    pub async fn flush(&self) -> Result<()> {
+        let _ = self.db.flush_async().await?;
+        Ok(())
+    }
+
+    fn tree(&self, table: Table) -> &Tree {
+        match table {
+            Table::Metadata => &self.metadata,
+            Table::Holders => &self.holders,
+            Table::FullAdults => &self.full_adults,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::new_auto_dump_db;
+    use pickledb::{PickleDb, PickleDbDumpPolicy};
+    use tempdir::TempDir;
+
+    #[tokio::test]
+    async fn batches_are_committed_together() -> Result<()> {
+        let dir = TempDir::new("chunk_dbs")?;
+        let dbs = ChunkHolderDbs::new(dir.path(), Some(EncryptionKey::generate()?))?;
+        dbs.set(Table::Metadata, "chunk", &vec![1u8, 2]).await?;
+
+        let mut batch = DbBatch::new();
+        batch.remove(Table::Metadata, "chunk");
+        batch.set(Table::Holders, "holder", &"chunks".to_string())?;
+        dbs.commit(batch).await?;
+        assert!(!dbs.exists(Table::Metadata, "chunk"));
+        assert_eq!(
+            dbs.get::<String>(Table::Holders, "holder"),
+            Some("chunks".to_string())
+        );
+        assert!(dbs.remove(Table::Holders, "holder").await?);
+        assert!(!dbs.remove(Table::Holders, "holder").await?);
+        assert_eq!(dbs.count(Table::Holders), 0);
+
+        for key in &["a", "b", "c"] {
+            dbs.set(Table::Metadata, key, &1u8).await?;
+        }
+        assert_eq!(dbs.keys(Table::Metadata, None, 2), vec!["a", "b"]);
+        assert_eq!(dbs.keys(Table::Metadata, Some("b"), 2), vec!["c"]);
+        assert!(dbs.keys(Table::Metadata, Some("c"), 2).is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn pickledb_files_are_imported() -> Result<()> {
+        let dir = TempDir::new("chunk_dbs")?;
+        let key = EncryptionKey::generate()?;
+        new_auto_dump_db(dir.path(), BLOB_META_DB_NAME, None)?.set("chunk", &vec![1u8, 2])?;
+        // full adults as they used to be recorded
+        let mut full_adults = PickleDb::new_bin(
+            dir.path().join(FULL_ADULTS_DB_NAME),
+            PickleDbDumpPolicy::AutoDump,
+        );
+        let _ = full_adults.lcreate("adult")?.ladd(&"Node Full");
+        drop(full_adults);
+
+        let dbs = ChunkHolderDbs::new(dir.path(), Some(key.clone()))?;
+        assert_eq!(dbs.get(Table::Metadata, "chunk"), Some(vec![1u8, 2]));
+        assert!(dbs.exists(Table::FullAdults, "adult"));
+        assert_eq!(
+            dbs.get::<Vec<String>>(Table::FullAdults, "adult"),
+            Some(vec!["Node Full".to_string()])
+        );
+        assert_eq!(dbs.count(Table::Holders), 0);
+        assert!(!dir.path().join(BLOB_META_DB_NAME).exists());
+        drop(dbs);
+
+        // not imported again, over newer changes
+        let dbs = ChunkHolderDbs::new(dir.path(), Some(key.clone()))?;
+        assert!(dbs.remove(Table::Metadata, "chunk").await?);
+        drop(dbs);
+        let dbs = ChunkHolderDbs::new(dir.path(), Some(key))?;
+        assert!(!dbs.exists(Table::Metadata, "chunk"));
+
+        Ok(())
+    }
 }
@@ -9,11 +9,16 @@
 mod chunk_dbs;
 mod rate_limit;
 
-use crate::{Error, Result};
-pub use chunk_dbs::ChunkHolderDbs;
+use crate::{
+    chunks::{MAX_STORAGE_USAGE_RATIO, STORAGE_AVAILABLE_RATIO},
+    Error, Result,
+};
+pub use chunk_dbs::{ChunkHolderDbs, DbBatch, Table};
+use futures::lock::Mutex;
 use log::{error, info};
 pub use rate_limit::RateLimit;
 use sn_data_types::PublicKey;
+use std::{collections::BTreeMap, sync::Arc};
 use xor_name::XorName;
 
 pub const MAX_SUPPLY: u64 = u32::MAX as u64 * 1_000_000_000_u64;
@@ -25,30 +30,120 @@ const MAX_CHUNK_SIZE: u64 = 1_000_000;
 #[derive(Clone)]
 pub struct Capacity {
     dbs: ChunkHolderDbs,
+    /// The used space ratio last reported by each adult, or implied by it being recorded as full
+    /// or available again since.
+    used_space_ratios: Arc<Mutex<BTreeMap<XorName, f64>>>,
 }
 
 impl Capacity {
     /// Pass in dbs with info on chunk holders.
     pub(super) fn new(dbs: ChunkHolderDbs) -> Self {
-        Self { dbs }
+        Self {
+            dbs,
+            used_space_ratios: Arc::new(Mutex::new(BTreeMap::new())),
+        }
     }
 
     /// Number of full chunk storing nodes in the section.
     pub async fn full_nodes(&self) -> u8 {
-        self.dbs.full_adults.lock().await.total_keys() as u8
+        self.dbs.count(Table::FullAdults) as u8
+    }
+
+    /// The used space ratio of an adult.  Adults recorded as full before we started count as
+    /// full, and ones that haven't reported anything as empty.
+    pub async fn used_space_ratio(&self, node_name: &XorName) -> f64 {
+        if let Some(ratio) = self.used_space_ratios.lock().await.get(node_name) {
+            return *ratio;
+        }
+        if self.dbs.exists(Table::FullAdults, &node_name.to_string()) {
+            MAX_STORAGE_USAGE_RATIO
+        } else {
+            0.0
+        }
+    }
+
+    /// Whether an adult is recorded as full, i.e. not to be chosen to store new chunks.
+    pub async fn is_full(&self, node_name: &XorName) -> bool {
+        self.dbs.exists(Table::FullAdults, &node_name.to_string())
+    }
+
+    /// How many full adults the usage of the given ones amounts to, counting each as full from
+    /// `MAX_STORAGE_USAGE_RATIO` on, and as partly full before that.
+    pub async fn full_node_equivalents(&self, adults: &[XorName]) -> f64 {
+        let mut equivalents = 0.0;
+        for adult in adults {
+            equivalents += (self.used_space_ratio(adult).await / MAX_STORAGE_USAGE_RATIO).min(1.0);
+        }
+        equivalents
+    }
+
+    /// The mean unused space ratio of the given adults.
+    pub async fn free_capacity(&self, adults: &[XorName]) -> f64 {
+        if adults.is_empty() {
+            return 0.0;
+        }
+        let mut free = 0.0;
+        for adult in adults {
+            free += 1.0 - self.used_space_ratio(adult).await;
+        }
+        free / adults.len() as f64
     }
 
     ///
     pub async fn increase_full_node_count(&mut self, node_id: PublicKey) -> Result<()> {
+        self.mark_full(XorName::from(node_id)).await
+    }
+
+    /// Records an adult as full, whether it reported so itself or rejected a chunk for lack of
+    /// space.
+    pub async fn mark_full(&mut self, node_name: XorName) -> Result<()> {
         info!("Increasing full_node count");
+        let mut ratios = self.used_space_ratios.lock().await;
+        let ratio = ratios.entry(node_name).or_insert(MAX_STORAGE_USAGE_RATIO);
+        *ratio = ratio.max(MAX_STORAGE_USAGE_RATIO);
+        drop(ratios);
+        self.dbs
+            .set(Table::FullAdults, &node_name.to_string(), &"Node Full")
+            .await
+    }
+
+    /// Records that an adult has storage available again.
+    pub async fn storage_available(&mut self, node_name: XorName) -> Result<()> {
+        let mut ratios = self.used_space_ratios.lock().await;
+        let ratio = ratios.entry(node_name).or_insert(STORAGE_AVAILABLE_RATIO);
+        *ratio = ratio.min(STORAGE_AVAILABLE_RATIO);
+        drop(ratios);
+        self.decrease_full_node_count_if_present(node_name).await
+    }
+
+    /// Records the used space ratio an adult reported, returning whether it is newly full.  As
+    /// with its own full and available reports, it is recorded as full from
+    /// `MAX_STORAGE_USAGE_RATIO` on, and only available again under `STORAGE_AVAILABLE_RATIO`.
+    pub async fn record_used_space_ratio(
+        &mut self,
+        node_name: XorName,
+        used_space_ratio: f64,
+    ) -> Result<bool> {
         let _ = self
-            .dbs
-            .full_adults
+            .used_space_ratios
             .lock()
             .await
-            .lcreate(&XorName::from(node_id).to_string())?
-            .ladd(&"Node Full");
-        Ok(())
+            .insert(node_name, used_space_ratio.max(0.0));
+        let full = self.is_full(&node_name).await;
+        if !full && used_space_ratio >= MAX_STORAGE_USAGE_RATIO {
+            self.mark_full(node_name).await?;
+            return Ok(true);
+        }
+        if full && used_space_ratio < STORAGE_AVAILABLE_RATIO {
+            self.decrease_full_node_count_if_present(node_name).await?;
+        }
+        Ok(false)
+    }
+
+    /// Forgets about an adult that left the section.
+    pub async fn remove_node(&mut self, node_name: XorName) -> Result<()> {
+        let _ = self.used_space_ratios.lock().await.remove(&node_name);
+        self.decrease_full_node_count_if_present(node_name).await
     }
 
     ///
@@ -56,10 +151,8 @@ impl Capacity {
         info!("Checking to decrease full_node count for: {:?}", node_name);
         match self
             .dbs
-            .full_adults
-            .lock()
+            .remove(Table::FullAdults, &node_name.to_string())
             .await
-            .rem(&node_name.to_string())
         {
             Ok(true) => {
                 info!("Node present in DB, remove successful");
@@ -71,8 +164,60 @@ impl Capacity {
             }
             Err(e) => {
                 error!("Error removing from full_nodes db");
-                Err(Error::PickleDb(e))
+                Err(e)
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bls::SecretKey;
+    use tempdir::TempDir;
+
+    #[tokio::test]
+    async fn capacity_grades_adults_by_reports() -> Result<()> {
+        let dir = TempDir::new("capacity").map_err(|e| Error::Logic(e.to_string()))?;
+        let mut capacity = Capacity::new(ChunkHolderDbs::new(dir.path(), None)?);
+        let full = PublicKey::from(SecretKey::random().public_key());
+        let available = PublicKey::from(SecretKey::random().public_key());
+        let unknown = XorName::random();
+        let adults = [XorName::from(full), XorName::from(available), unknown];
+
+        capacity.increase_full_node_count(full).await?;
+        capacity.increase_full_node_count(available).await?;
+        capacity.storage_available(XorName::from(available)).await?;
+        assert_eq!(capacity.full_nodes().await, 1);
+        assert!(capacity.is_full(&XorName::from(full)).await);
+        assert!(!capacity.is_full(&XorName::from(available)).await);
+        assert!(!capacity.is_full(&unknown).await);
+        let equivalents = capacity.full_node_equivalents(&adults).await;
+        assert!((equivalents - 1.8).abs() < 1e-9);
+        let free = capacity.free_capacity(&adults).await;
+        assert!((free - (0.5 + 0.6 + 1.0) / 3.0).abs() < 1e-9);
+
+        // full adults recorded before a restart still count as full
+        drop(capacity);
+        let capacity = Capacity::new(ChunkHolderDbs::new(dir.path(), None)?);
+        let ratio = capacity.used_space_ratio(&XorName::from(full)).await;
+        assert!((ratio - MAX_STORAGE_USAGE_RATIO).abs() < 1e-9);
+
+        // reported ratios are recorded as they are, full status changing with hysteresis
+        let mut capacity = capacity;
+        let reporting = XorName::random();
+        assert!(!capacity.record_used_space_ratio(reporting, 0.3).await?);
+        assert!((capacity.used_space_ratio(&reporting).await - 0.3).abs() < 1e-9);
+        assert!(capacity.record_used_space_ratio(reporting, 0.6).await?);
+        assert!(capacity.is_full(&reporting).await);
+        assert!(!capacity.record_used_space_ratio(reporting, 0.45).await?);
+        assert!(capacity.is_full(&reporting).await);
+        assert!(!capacity.record_used_space_ratio(reporting, 0.35).await?);
+        assert!(!capacity.is_full(&reporting).await);
+
+        capacity.clone().remove_node(XorName::from(full)).await?;
+        assert_eq!(capacity.full_nodes().await, 0);
+
+        Ok(())
+    }
+}
@@ -32,29 +32,51 @@ impl RateLimit {
         let prefix = self.network.our_prefix().await;
         let prefix_len = prefix.bit_count();
 
-        let full_nodes = self.capacity.full_nodes().await;
-        let all_nodes = self.network.our_adults().await.len() as u8;
+        let adults = self.network.our_adults().await;
+        let full_nodes = self.capacity.full_node_equivalents(&adults).await;
+        let all_nodes = adults.len() as u8;
 
         RateLimit::rate_limit(bytes, full_nodes, all_nodes, prefix_len)
     }
 
+    /// The mean unused space ratio of our adults.
+    pub async fn free_capacity(&self) -> f64 {
+        let adults = self.network.our_adults().await;
+        self.capacity.free_capacity(&adults).await
+    }
+
     /// Adds this node to the list of full nodes.
     pub async fn increase_full_node_count(&mut self, node_id: PublicKey) -> Result<()> {
         self.capacity.increase_full_node_count(node_id).await
     }
 
-    /// Adds this node to the list of full nodes.
-    pub async fn decrease_full_node_count_if_present(&mut self, node_name: XorName) -> Result<()> {
+    /// Records that this node has storage available again.
+    pub async fn storage_available(&mut self, node_name: XorName) -> Result<()> {
+        self.capacity.storage_available(node_name).await
+    }
+
+    /// Records the used space ratio this node reported, returning whether it is now full.
+    pub async fn record_used_space_ratio(
+        &mut self,
+        node_name: XorName,
+        used_space_ratio: f64,
+    ) -> Result<bool> {
         self.capacity
-            .decrease_full_node_count_if_present(node_name)
+            .record_used_space_ratio(node_name, used_space_ratio)
             .await
     }
 
-    fn rate_limit(bytes: u64, full_nodes: u8, all_nodes: u8, prefix_len: usize) -> Token {
-        let available_nodes = (all_nodes - full_nodes) as f64;
+    /// Forgets about the capacity of this node, which left the section.
+    pub async fn remove_node(&mut self, node_name: XorName) -> Result<()> {
+        self.capacity.remove_node(node_name).await
+    }
+
+    /// `full_nodes` counts partly full nodes as fractions.
+    fn rate_limit(bytes: u64, full_nodes: f64, all_nodes: u8, prefix_len: usize) -> Token {
+        let available_nodes = all_nodes as f64 - full_nodes;
         let supply_demand_factor = 0.001
             + (1_f64 / available_nodes).powf(8_f64)
-            + (full_nodes as f64 / all_nodes as f64).powf(88_f64);
+            + (full_nodes / all_nodes as f64).powf(88_f64);
         let data_size_factor = (bytes as f64 / MAX_CHUNK_SIZE as f64).powf(2_f64)
             + (bytes as f64 / MAX_CHUNK_SIZE as f64);
         let steepness_reductor = prefix_len as f64 + 1_f64;
@@ -80,7 +102,7 @@ mod test {
         let bytes = 1_000;
         let prefix_len = 0;
         let all_nodes = 8;
-        let full_nodes = 7;
+        let full_nodes = 7.0;
         let rate_limit = RateLimit::rate_limit(bytes, full_nodes, all_nodes, prefix_len).as_nano();
         assert_eq!(rate_limit, 2076594);
     }
@@ -110,7 +132,7 @@ mod test {
         let one_mb_bytes = 1024 * 1024;
         let prefix_len = 0;
         let all_nodes = 8;
-        let full_nodes = 7;
+        let full_nodes = 7.0;
         let standard_rl =
             RateLimit::rate_limit(one_mb_bytes, full_nodes, all_nodes, prefix_len).as_nano();
 
@@ -132,7 +154,7 @@ mod test {
         let one_mb_bytes = 1024 * 1024;
         let prefix_len = 2; // first couple of sections see an increase in cost, whereafter it is strictly decreasing
         let all_nodes = 8;
-        let full_nodes = 7;
+        let full_nodes = 7.0;
         let standard_rl =
             RateLimit::rate_limit(one_mb_bytes, full_nodes, all_nodes, prefix_len).as_nano();
         // large network is cheaper to store than smaller network
@@ -153,11 +175,11 @@ mod test {
         let one_mb_bytes = 1024 * 1024;
         let prefix_len = 0;
         let all_nodes = 8;
-        let full_nodes = 7;
+        let full_nodes = 7.0;
         let standard_rl =
             RateLimit::rate_limit(one_mb_bytes, full_nodes, all_nodes, prefix_len).as_nano();
         // less full section is cheaper than more full section
-        let less_full_nodes = full_nodes - 1;
+        let less_full_nodes = full_nodes - 1.0;
         let empty =
             RateLimit::rate_limit(one_mb_bytes, less_full_nodes, all_nodes, prefix_len).as_nano();
         assert!(
@@ -175,7 +197,7 @@ mod test {
         let one_mb_bytes = 1024 * 1024;
         let prefix_len = 2;
         let all_nodes = 8;
-        let full_nodes = 7;
+        let full_nodes = 7.0;
         let standard_rl =
             RateLimit::rate_limit(one_mb_bytes, full_nodes, all_nodes, prefix_len).as_nano();
         // many tiny chunks is cheaper than the same bytes in one big chunk
@@ -200,7 +222,7 @@ mod test {
         // In general, the size of a type is not stable across compilations,
         // but it is close enough for our purposes here.
         let minimum_storage_bytes = mem::size_of::<DataCmd>() as u64;
-        let half_full_nodes = 10;
+        let half_full_nodes = 10.0;
         let big_section_node_count = 20;
         let big_prefix_len = 33;
         // storage rate limit is applied up to 170 billion nodes
@@ -227,7 +249,7 @@ mod test {
         // In general, the size of a type is not stable across compilations,
         // but it is close enough for our purposes here.
         let minimum_storage_bytes = mem::size_of::<DataCmd>() as u64;
-        let half_full_nodes = 10;
+        let half_full_nodes = 10.0;
         let big_section_node_count = 20;
         let big_prefix_len = 256;
         // storage rate limit is applied up to 2.3 * 10^78 nodes.
@@ -250,7 +272,7 @@ mod test {
         // setup
         let one_mb_bytes = 1024 * 1024;
         let max_initial_cost = 1_000_000_000; // 1 token
-        let zero_full_nodes = 0;
+        let zero_full_nodes = 0.0;
         let minimum_section_nodes = 5;
         let first_section_prefix = 0;
         // the first chunk is a reasonable cost
@@ -0,0 +1,160 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{blocking, Backend};
+use crate::{Error, Result};
+use async_trait::async_trait;
+use sled::{
+    transaction::{TransactionError, TransactionResult},
+    Db, Transactional, Tree,
+};
+use std::{convert::TryInto, path::Path};
+
+const DB_DIR: &str = "db";
+const SIZES_TREE: &str = "sizes";
+const QUARANTINE_TREE: &str = "quarantine";
+
+/// Keeps chunks in an embedded key-value database, in the `ChunkStore`'s directory.
+///
+/// Changes are made in the database's in-memory cache and then flushed to disk asynchronously.
+/// The size of each value is kept alongside it, so it's known without loading the value.
+pub(crate) struct DbBackend {
+    db: Db,
+    chunks: Tree,
+    sizes: Tree,
+    quarantine: Tree,
+}
+
+impl DbBackend {
+    pub(crate) fn new(dir: &Path) -> Result<Self> {
+        let db = sled::open(dir.join(DB_DIR))?;
+        let chunks = Tree::clone(&db);
+        let sizes = db.open_tree(SIZES_TREE)?;
+        let quarantine = db.open_tree(QUARANTINE_TREE)?;
+        Ok(Self {
+            db,
+            chunks,
+            sizes,
+            quarantine,
+        })
+    }
+
+    // Runs `f`, which does blocking I/O, on the blocking threads with clones of the trees.
+    async fn with_trees<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce(Tree, Tree, Tree) -> Result<R> + Send + 'static,
+        R: Send + 'static,
+    {
+        let (chunks, sizes, quarantine) = (
+            self.chunks.clone(),
+            self.sizes.clone(),
+            self.quarantine.clone(),
+        );
+        blocking(move || f(chunks, sizes, quarantine)).await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let _ = self.db.flush_async().await?;
+        Ok(())
+    }
+}
+
+fn transaction_error(error: TransactionError<()>) -> Error {
+    match error {
+        TransactionError::Storage(error) => Error::Sled(error),
+        TransactionError::Abort(()) => Error::Logic("Transaction aborted".to_string()),
+    }
+}
+
+#[async_trait]
+impl Backend for DbBackend {
+    async fn put(&mut self, key: &str, value: Vec<u8>) -> Result<()> {
+        let key = key.to_string();
+        self.with_trees(move |chunks, sizes, _| {
+            let size = (value.len() as u64).to_be_bytes();
+            let result: TransactionResult<()> = (&chunks, &sizes).transaction(|(chunks, sizes)| {
+                let _ = chunks.insert(key.as_bytes(), value.as_slice())?;
+                let _ = sizes.insert(key.as_bytes(), &size)?;
+                Ok(())
+            });
+            result.map_err(transaction_error)
+        })
+        .await?;
+        self.flush().await
+    }
+
+    /// Values not in the cache are read from disk, so this is done on a blocking thread.
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let key = key.to_string();
+        self.with_trees(move |chunks, _, _| Ok(chunks.get(key)?.map(|value| value.to_vec())))
+            .await
+    }
+
+    async fn has(&self, key: &str) -> bool {
+        let key = key.to_string();
+        self.with_trees(move |_, sizes, _| Ok(sizes.contains_key(key)?))
+            .await
+            .unwrap_or(false)
+    }
+
+    async fn delete(&mut self, key: &str) -> Result<()> {
+        let key = key.to_string();
+        self.with_trees(move |chunks, sizes, _| {
+            let result: TransactionResult<()> = (&chunks, &sizes).transaction(|(chunks, sizes)| {
+                let _ = chunks.remove(key.as_bytes())?;
+                let _ = sizes.remove(key.as_bytes())?;
+                Ok(())
+            });
+            result.map_err(transaction_error)
+        })
+        .await?;
+        self.flush().await
+    }
+
+    /// Lists the keys from the sizes, which are much smaller than the values, on a blocking
+    /// thread.
+    async fn keys(&self) -> Vec<String> {
+        self.with_trees(|_, sizes, _| {
+            Ok(sizes
+                .iter()
+                .keys()
+                .filter_map(|key| key.ok())
+                .filter_map(|key| String::from_utf8(key.to_vec()).ok())
+                .collect())
+        })
+        .await
+        .unwrap_or_default()
+    }
+
+    async fn size(&self, key: &str) -> Option<u64> {
+        let key = key.to_string();
+        self.with_trees(move |_, sizes, _| Ok(sizes.get(key)?))
+            .await
+            .ok()
+            .flatten()
+            .and_then(|size| size.as_ref().try_into().ok())
+            .map(u64::from_be_bytes)
+    }
+
+    async fn quarantine(&mut self, key: &str) -> Result<()> {
+        let key = key.to_string();
+        self.with_trees(move |chunks, sizes, quarantine| {
+            let result: TransactionResult<()> =
+                (&chunks, &sizes, &quarantine).transaction(|(chunks, sizes, quarantine)| {
+                    if let Some(value) = chunks.remove(key.as_bytes())? {
+                        let _ = quarantine.insert(key.as_bytes(), value)?;
+                    }
+                    let _ = sizes.remove(key.as_bytes())?;
+                    Ok(())
+                });
+            result.map_err(transaction_error)
+        })
+        .await?;
+        self.flush().await
+    }
+}
@@ -0,0 +1,305 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{blocking, Backend};
+use crate::Result;
+use async_trait::async_trait;
+use log::{info, warn};
+use std::{
+    fs::{self, DirEntry, File, Metadata},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use tokio::fs as tokio_fs;
+use xor_name::XorName;
+
+/// Where chunks failing verification are moved to, next to the `ChunkStore` directories.
+const QUARANTINE_DIR: &str = "quarantine";
+
+/// Suffix of the files chunks are written to before being renamed into place.  The space reserved
+/// for a write left unfinished is released when the `ChunkStore` reconciles its used space.
+const TEMP_FILE_SUFFIX: &str = ".tmp";
+
+/// Records the shard depth a `ChunkStore` directory was last laid out with.
+const LAYOUT_FILENAME: &str = "layout";
+
+/// The max name length for a chunk file.
+const MAX_CHUNK_FILE_NAME_LENGTH: usize = 104;
+
+/// Keeps each chunk in a file of its own, named by its key, spread over `shard_depth` levels of
+/// hex-prefix subdirectories of the `ChunkStore`'s directory.
+pub(crate) struct FsBackend {
+    dir: PathBuf,
+    shard_depth: u8,
+}
+
+impl FsBackend {
+    /// Opens the chunk files in `dir`.  Chunks found laid out with a different shard depth than
+    /// `shard_depth` (e.g. the flat layout of older nodes) are moved into place first.
+    pub(crate) fn new(dir: &Path, shard_depth: u8) -> Result<Self> {
+        Self::verify_file_names(dir)?;
+        Self::migrate_layout(dir, shard_depth)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            shard_depth,
+        })
+    }
+
+    /// Removes the temp files left behind by interrupted writes.
+    pub(crate) fn remove_interrupted_writes(&self) -> Result<()> {
+        for path in temp_files(&self.dir) {
+            warn!("Removing interrupted chunk write at {}", path.display());
+            fs::remove_file(&path)?;
+        }
+        Ok(())
+    }
+
+    fn verify_file_names(dir: &Path) -> Result<()> {
+        // Verify that chunk files can be created.
+        let temp_file_path = dir.join("0".repeat(MAX_CHUNK_FILE_NAME_LENGTH));
+        let _ = File::create(&temp_file_path)?;
+        fs::remove_file(temp_file_path)?;
+
+        Ok(())
+    }
+
+    /// Moves all chunk files from the layout recorded in `dir` to one with `shard_depth` levels.
+    ///
+    /// A missing record means the store predates sharding, i.e. is flat.  The record is only
+    /// updated once every file has been moved, so an interrupted migration is resumed on the next
+    /// start.
+    fn migrate_layout(dir: &Path, shard_depth: u8) -> Result<()> {
+        let layout_path = dir.join(LAYOUT_FILENAME);
+        let current_depth = match fs::read(&layout_path) {
+            Ok(contents) => contents.first().copied().unwrap_or(0),
+            Err(_) => 0,
+        };
+        if current_depth == shard_depth {
+            return Ok(());
+        }
+
+        info!(
+            "Migrating chunks at {} from shard depth {} to {}",
+            dir.display(),
+            current_depth,
+            shard_depth
+        );
+        let mut count = 0;
+        for entry in chunk_entries(dir, current_depth) {
+            let file_name = entry.file_name();
+            let shard_dir = shard_dir(dir, &file_name.to_string_lossy(), shard_depth);
+            fs::create_dir_all(&shard_dir)?;
+            fs::rename(entry.path(), shard_dir.join(file_name))?;
+            count += 1;
+        }
+        remove_empty_shard_dirs(dir, current_depth);
+
+        fs::write(&layout_path, [shard_depth])?;
+        info!("Migrated {} chunks at {}", count, dir.display());
+        Ok(())
+    }
+
+    fn quarantine_dir(&self) -> PathBuf {
+        let subdir = self.dir.file_name().unwrap_or_default();
+        self.dir.with_file_name(QUARANTINE_DIR).join(subdir)
+    }
+}
+
+#[async_trait]
+impl Backend for FsBackend {
+    /// The value is written to a temp file which is renamed into place once synced to disk, so a
+    /// crash never leaves a partially written chunk under its final name.
+    async fn put(&mut self, key: &str, value: Vec<u8>) -> Result<()> {
+        let file_path = file_path(&self.dir, key, self.shard_depth);
+        let shard_dir = file_path.parent().unwrap_or(&self.dir).to_path_buf();
+        blocking(move || {
+            fs::create_dir_all(&shard_dir)?;
+
+            let temp_path = temp_file_path(&file_path);
+            let res = File::create(&temp_path)
+                .and_then(|mut file| {
+                    file.write_all(&value)?;
+                    file.sync_all()
+                })
+                .and_then(|()| fs::rename(&temp_path, &file_path))
+                .and_then(|()| sync_dir(&shard_dir));
+            if res.is_err() {
+                let _ = fs::remove_file(&temp_path);
+            }
+            Ok(res?)
+        })
+        .await
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match tokio_fs::read(file_path(&self.dir, key, self.shard_depth)).await {
+            Ok(contents) => Ok(Some(contents)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    async fn has(&self, key: &str) -> bool {
+        tokio_fs::metadata(file_path(&self.dir, key, self.shard_depth))
+            .await
+            .as_ref()
+            .map(Metadata::is_file)
+            .unwrap_or(false)
+    }
+
+    async fn delete(&mut self, key: &str) -> Result<()> {
+        match tokio_fs::remove_file(file_path(&self.dir, key, self.shard_depth)).await {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn keys(&self) -> Vec<String> {
+        let dir = self.dir.clone();
+        let shard_depth = self.shard_depth;
+        blocking(move || {
+            Ok(chunk_entries(&dir, shard_depth)
+                .iter()
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect())
+        })
+        .await
+        .unwrap_or_default()
+    }
+
+    async fn size(&self, key: &str) -> Option<u64> {
+        tokio_fs::metadata(file_path(&self.dir, key, self.shard_depth))
+            .await
+            .ok()
+            .map(|metadata| metadata.len())
+    }
+
+    /// Moves the file to the quarantine directory.
+    async fn quarantine(&mut self, key: &str) -> Result<()> {
+        let quarantine_dir = self.quarantine_dir();
+        tokio_fs::create_dir_all(&quarantine_dir).await?;
+        tokio_fs::rename(
+            file_path(&self.dir, key, self.shard_depth),
+            quarantine_dir.join(key),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+pub(crate) fn file_path(dir: &Path, key: &str, shard_depth: u8) -> PathBuf {
+    shard_dir(dir, key, shard_depth).join(key)
+}
+
+pub(crate) fn temp_file_path(file_path: &Path) -> PathBuf {
+    let mut file_name = file_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(TEMP_FILE_SUFFIX);
+    file_path.with_file_name(file_name)
+}
+
+/// Returns the directory a chunk file named `file_name` belongs in, `shard_depth` levels below
+/// `dir`.
+///
+/// The levels are taken from a hash of the file name rather than the name itself, since the
+/// serialised ids share their leading bytes (enum tags) and would all land in the same directory.
+fn shard_dir(dir: &Path, file_name: &str, shard_depth: u8) -> PathBuf {
+    let hash = XorName::from_content(&[file_name.as_bytes()]);
+    hash.0
+        .iter()
+        .take(shard_depth as usize)
+        .fold(dir.to_path_buf(), |path, byte| {
+            path.join(format!("{:02x}", byte))
+        })
+}
+
+/// Lists the chunk files found exactly `shard_depth` levels of shard directories below `dir`.
+fn chunk_entries(dir: &Path, shard_depth: u8) -> Vec<DirEntry> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok()),
+        Err(_) => return Vec::new(),
+    };
+    if shard_depth == 0 {
+        return entries.filter(is_chunk_file).collect();
+    }
+    entries
+        .filter(is_shard_dir)
+        .flat_map(|entry| chunk_entries(&entry.path(), shard_depth - 1))
+        .collect()
+}
+
+/// Removes the shard directories (down to `shard_depth` levels) which no longer hold anything.
+fn remove_empty_shard_dirs(dir: &Path, shard_depth: u8) {
+    if shard_depth == 0 {
+        return;
+    }
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.filter_map(|entry| entry.ok()).filter(is_shard_dir) {
+            let path = entry.path();
+            remove_empty_shard_dirs(&path, shard_depth - 1);
+            let is_empty = fs::read_dir(&path)
+                .map(|mut contents| contents.next().is_none())
+                .unwrap_or(false);
+            if is_empty {
+                let _ = fs::remove_dir(&path);
+            }
+        }
+    }
+}
+
+/// Lists the temp files of interrupted writes anywhere in the shard directories below `dir`.
+fn temp_files(dir: &Path) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok()),
+        Err(_) => return Vec::new(),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        if is_shard_dir(&entry) {
+            files.extend(temp_files(&entry.path()));
+        } else if entry
+            .file_name()
+            .to_string_lossy()
+            .ends_with(TEMP_FILE_SUFFIX)
+        {
+            files.push(entry.path());
+        }
+    }
+    files
+}
+
+/// Flushes the entries of `dir`, making renames into it durable.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+fn is_shard_dir(entry: &DirEntry) -> bool {
+    let name = entry.file_name();
+    let name = name.to_string_lossy();
+    name.len() == 2
+        && name.chars().all(|c| c.is_ascii_hexdigit())
+        && entry.file_type().map(|kind| kind.is_dir()).unwrap_or(false)
+}
+
+/// Chunk files are named by their hex encoded keys, which tells them apart from the other files
+/// kept in the `ChunkStore`'s directory.
+fn is_chunk_file(entry: &DirEntry) -> bool {
+    let name = entry.file_name();
+    let name = name.to_string_lossy();
+    !name.is_empty()
+        && hex::decode(name.as_ref()).is_ok()
+        && entry
+            .file_type()
+            .map(|kind| kind.is_file())
+            .unwrap_or(false)
+}
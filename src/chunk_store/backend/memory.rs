@@ -0,0 +1,48 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::Backend;
+use crate::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+
+/// Keeps chunks in memory only, so they are lost when the node stops.  Meant for tests and
+/// ephemeral testnets.
+#[derive(Default)]
+pub(crate) struct MemoryBackend {
+    values: HashMap<String, Vec<u8>>,
+}
+
+#[async_trait]
+impl Backend for MemoryBackend {
+    async fn put(&mut self, key: &str, value: Vec<u8>) -> Result<()> {
+        let _ = self.values.insert(key.to_string(), value);
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.values.get(key).cloned())
+    }
+
+    async fn has(&self, key: &str) -> bool {
+        self.values.contains_key(key)
+    }
+
+    async fn delete(&mut self, key: &str) -> Result<()> {
+        let _ = self.values.remove(key);
+        Ok(())
+    }
+
+    async fn keys(&self) -> Vec<String> {
+        self.values.keys().cloned().collect()
+    }
+
+    async fn size(&self, key: &str) -> Option<u64> {
+        self.values.get(key).map(|value| value.len() as u64)
+    }
+}
@@ -0,0 +1,62 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Where a `ChunkStore` keeps its serialised chunks.
+
+mod db;
+mod fs;
+mod memory;
+
+#[cfg(test)]
+pub(super) use self::fs::{file_path, temp_file_path};
+pub(super) use self::{db::DbBackend, fs::FsBackend, memory::MemoryBackend};
+use crate::{Error, Result};
+use async_trait::async_trait;
+use tokio::task;
+
+/// Storage of serialised chunks, keyed by their hex encoded ids.
+///
+/// Implementations must not block the async runtime, which also drives routing: blocking I/O is
+/// done with async APIs or offloaded to the runtime's blocking threads.
+#[async_trait]
+pub(crate) trait Backend: Send + Sync {
+    /// Stores `value` under `key`, replacing any previous value.
+    async fn put(&mut self, key: &str, value: Vec<u8>) -> Result<()>;
+
+    /// Returns the value stored under `key`, if any.
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Tests if a value is stored under `key`.
+    async fn has(&self, key: &str) -> bool;
+
+    /// Deletes the value stored under `key`.  Does nothing if there is none.
+    async fn delete(&mut self, key: &str) -> Result<()>;
+
+    /// Lists the keys of all stored values.
+    async fn keys(&self) -> Vec<String>;
+
+    /// Returns the space taken by the value stored under `key`, if any.
+    async fn size(&self, key: &str) -> Option<u64>;
+
+    /// Sets the corrupt value stored under `key` aside, so it's no longer served.  Backends
+    /// without anywhere to keep it simply delete it.
+    async fn quarantine(&mut self, key: &str) -> Result<()> {
+        self.delete(key).await
+    }
+}
+
+/// Runs `f`, which does blocking I/O, on the runtime's blocking threads.
+async fn blocking<F, R>(f: F) -> Result<R>
+where
+    F: FnOnce() -> Result<R> + Send + 'static,
+    R: Send + 'static,
+{
+    task::spawn_blocking(f)
+        .await
+        .map_err(|error| Error::Logic(format!("Chunk I/O task failed: {}", error)))?
+}
@@ -10,7 +10,7 @@ use crate::ToDbKey;
 use serde::{de::DeserializeOwned, Serialize};
 use xor_name::XorName;
 
-pub(crate) trait Chunk: Serialize + DeserializeOwned {
+pub(crate) trait Chunk: Clone + Serialize + DeserializeOwned {
     type Id: ChunkId;
     fn id(&self) -> &Self::Id;
 }
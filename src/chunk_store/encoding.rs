@@ -0,0 +1,139 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! The format chunks are stored in: a short versioned header followed by the serialised chunk,
+//! optionally compressed and then encrypted.  Chunks stored before the header was introduced are
+//! plain serialised chunks, and are still read as such, as are chunks with a version 1 header,
+//! which has no cipher byte.
+
+use crate::{encryption::EncryptionKey, Error, Result};
+use sn_data_types::MAX_BLOB_SIZE_IN_BYTES;
+
+/// Marks a chunk as having a header.  Serialised chunks start with a small enum tag or id, which
+/// never matches this.
+const MAGIC: [u8; 4] = *b"SNCK";
+const VERSION: u8 = 2;
+const HEADER_LEN: usize = MAGIC.len() + 3;
+const V1_HEADER_LEN: usize = MAGIC.len() + 2;
+
+const UNCOMPRESSED: u8 = 0;
+const ZSTD: u8 = 1;
+
+const UNENCRYPTED: u8 = 0;
+const CHACHA20_POLY1305: u8 = 1;
+
+/// Uses zstd's default level.
+const ZSTD_LEVEL: i32 = 0;
+/// Chunks larger than a blob are only compressed up to this ratio, so that a corrupt decompressed
+/// size can't have a chunk's decoding allocate more than that.
+const MAX_COMPRESSION_RATIO: usize = 1024;
+
+/// Prepends the header to `serialised`, compressing it first if asked to and if that makes it
+/// smaller, and encrypting it if given a key.  The header is authenticated along with the
+/// encrypted payload.
+pub(super) fn encode(
+    serialised: &[u8],
+    compress: bool,
+    key: Option<&EncryptionKey>,
+) -> Result<Vec<u8>> {
+    let mut compression = UNCOMPRESSED;
+    let mut payload = None;
+    if compress {
+        let compressed = zstd::block::compress(serialised, ZSTD_LEVEL)?;
+        // The decompressed size goes first, as zstd blocks don't record it.
+        if 8 + compressed.len() < serialised.len()
+            && serialised.len() <= max_decompressed_len(8 + compressed.len())
+        {
+            let mut encoded = (serialised.len() as u64).to_le_bytes().to_vec();
+            encoded.extend_from_slice(&compressed);
+            compression = ZSTD;
+            payload = Some(encoded);
+        }
+    }
+    let payload = payload.as_deref().unwrap_or(serialised);
+    match key {
+        Some(key) => {
+            let mut encoded = header(compression, CHACHA20_POLY1305);
+            let sealed = key.seal(payload, &encoded)?;
+            encoded.extend_from_slice(&sealed);
+            Ok(encoded)
+        }
+        None => {
+            let mut encoded = header(compression, UNENCRYPTED);
+            encoded.extend_from_slice(payload);
+            Ok(encoded)
+        }
+    }
+}
+
+/// Returns the serialised chunk stored as `encoded`, decrypting it with `key` if it's encrypted.
+/// Unless it's compressed, the chunk is decoded within `encoded`, without copying it.
+pub(super) fn decode(mut encoded: Vec<u8>, key: Option<&EncryptionKey>) -> Result<Vec<u8>> {
+    if !encoded.starts_with(&MAGIC) {
+        return Ok(encoded);
+    }
+    if encoded.len() < V1_HEADER_LEN {
+        return Err(Error::ChunkFormat("truncated header".to_string()));
+    }
+    let (header_len, cipher) = match encoded[MAGIC.len()] {
+        1 => (V1_HEADER_LEN, UNENCRYPTED),
+        VERSION if encoded.len() >= HEADER_LEN => (HEADER_LEN, encoded[MAGIC.len() + 2]),
+        VERSION => return Err(Error::ChunkFormat("truncated header".to_string())),
+        version => return Err(Error::ChunkFormat(format!("unknown version {}", version))),
+    };
+    let compression = encoded[MAGIC.len() + 1];
+    let header = encoded.drain(..header_len).collect::<Vec<_>>();
+    let payload = match cipher {
+        UNENCRYPTED => encoded,
+        CHACHA20_POLY1305 => match key {
+            Some(key) => key.open(encoded, &header)?,
+            None => {
+                return Err(Error::ChunkFormat(
+                    "encrypted, but no encryption key is set".to_string(),
+                ))
+            }
+        },
+        cipher => return Err(Error::ChunkFormat(format!("unknown cipher {}", cipher))),
+    };
+    match compression {
+        UNCOMPRESSED => Ok(payload),
+        ZSTD => {
+            if payload.len() < 8 {
+                return Err(Error::ChunkFormat("truncated payload".to_string()));
+            }
+            let mut len = [0; 8];
+            len.copy_from_slice(&payload[..8]);
+            let len = u64::from_le_bytes(len);
+            if len > max_decompressed_len(payload.len()) as u64 {
+                return Err(Error::ChunkFormat(format!(
+                    "decompressed size {} out of bounds",
+                    len
+                )));
+            }
+            Ok(zstd::block::decompress(&payload[8..], len as usize)?)
+        }
+        compression => Err(Error::ChunkFormat(format!(
+            "unknown compression {}",
+            compression
+        ))),
+    }
+}
+
+// The largest size a compressed payload of `payload_len` bytes is decompressed to.
+fn max_decompressed_len(payload_len: usize) -> usize {
+    (MAX_BLOB_SIZE_IN_BYTES as usize).max(payload_len.saturating_mul(MAX_COMPRESSION_RATIO))
+}
+
+fn header(compression: u8, cipher: u8) -> Vec<u8> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(&MAGIC);
+    header.push(VERSION);
+    header.push(compression);
+    header.push(cipher);
+    header
+}
@@ -8,45 +8,79 @@
 
 //! A simple, persistent, disk-based key-value store.
 
+mod backend;
 mod chunk;
+mod encoding;
 mod immutable;
 mod mutable;
+mod read_cache;
 mod sequence;
 #[cfg(test)]
 mod tests;
 mod used_space;
 
+use crate::encryption::EncryptionKey;
 use crate::error::{Error, Result};
-use crate::utils;
+use crate::{to_db_key::from_db_key, utils, ChunkStoreBackend, ToDbKey};
+use backend::{Backend, DbBackend, FsBackend, MemoryBackend};
 use chunk::{Chunk, ChunkId};
-use log::{info, trace};
+use log::{info, trace, warn};
+pub(crate) use read_cache::CacheStats;
+use read_cache::ReadCache;
 use sn_data_types::{Blob, Map, Sequence};
 use std::{
-    fs::{self, DirEntry, File, Metadata},
-    io::{Read, Write},
-    marker::PhantomData,
+    collections::VecDeque,
+    fs,
     path::{Path, PathBuf},
+    sync::Mutex,
 };
 use used_space::StoreId;
-pub use used_space::UsedSpace;
+pub use used_space::{StoreUsage, UsedSpace};
 
 const CHUNK_STORE_DIR: &str = "chunks";
 
-/// The max name length for a chunk file.
-const MAX_CHUNK_FILE_NAME_LENGTH: usize = 104;
-
 pub(crate) type BlobChunkStore = ChunkStore<Blob>;
 pub(crate) type MapChunkStore = ChunkStore<Map>;
 pub(crate) type SequenceChunkStore = ChunkStore<Sequence>;
 
-/// `ChunkStore` is a store of data held as serialised files on disk, implementing a maximum disk
-/// usage to restrict storage.
+/// Settings shared by all the `ChunkStore`s of a node.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ChunkStoreConfig {
+    /// Where the chunks are kept.
+    pub backend: ChunkStoreBackend,
+    /// Number of levels of hex-prefix subdirectories the chunk files are spread over.
+    /// Zero keeps every chunk directly in the store's directory.
+    pub shard_depth: u8,
+    /// Number of chunks to verify per scrub round.  Zero disables scrubbing.
+    pub scrub_rate: u32,
+    /// Budget in bytes of each store's cache of recently read chunks.  Zero disables caching.
+    pub cache_size: u64,
+    /// Whether to compress chunks before storing them.
+    pub compress: bool,
+    /// Key to encrypt chunks with before storing them, if any.
+    pub encryption: Option<EncryptionKey>,
+    /// Most space the blob store may use, on top of the node's max capacity.
+    pub blob_quota: Option<u64>,
+    /// Most space the map store may use, on top of the node's max capacity.
+    pub map_quota: Option<u64>,
+    /// Most space the sequence store may use, on top of the node's max capacity.
+    pub sequence_quota: Option<u64>,
+}
+
+/// `ChunkStore` is a store of serialised data chunks, implementing a maximum disk usage to
+/// restrict storage.  Where the chunks are kept is up to its `Backend`.
 pub(crate) struct ChunkStore<T: Chunk> {
     dir: PathBuf,
+    backend: Box<dyn Backend>,
     // Maximum space allowed for all `ChunkStore`s to consume.
     used_space: UsedSpace,
     id: StoreId,
-    _phantom: PhantomData<T>,
+    // Chunks still to be verified in the current scrub pass.
+    scrub_queue: VecDeque<(T::Id, String)>,
+    cache: Option<Mutex<ReadCache<T>>>,
+    compress: bool,
+    encryption: Option<EncryptionKey>,
+    quota: Option<u64>,
 }
 
 impl<T> ChunkStore<T>
@@ -61,74 +95,96 @@ where
     ///
     /// The maximum storage space is defined by `max_capacity`.  This specifies the max usable by
     /// _all_ `ChunkStores`, not per `ChunkStore`.
-    pub async fn new<P: AsRef<Path>>(root: P, used_space: UsedSpace) -> Result<Self> {
+    ///
+    /// With the `Fs` backend, writes interrupted by a crash are cleaned up, and chunks found laid
+    /// out with a different shard depth than `config.shard_depth` (e.g. the flat layout of older
+    /// nodes) are moved into place.  Then the recorded used space is reconciled with the chunks.
+    pub async fn new<P: AsRef<Path>>(
+        root: P,
+        used_space: UsedSpace,
+        config: &ChunkStoreConfig,
+    ) -> Result<Self> {
         let dir = root.as_ref().join(CHUNK_STORE_DIR).join(Self::subdir());
-
-        if fs::read(&dir).is_err() {
-            Self::create_new_root(&dir)?
-        }
-
-        let id = used_space.add_local_store(&dir).await?;
-        Ok(ChunkStore {
+        fs::create_dir_all(&dir)?;
+
+        let quota = Self::quota(config);
+        let id = used_space.add_local_store(&dir, quota).await?;
+        let backend: Box<dyn Backend> = match config.backend {
+            ChunkStoreBackend::Fs => {
+                let backend = FsBackend::new(&dir, config.shard_depth)?;
+                backend.remove_interrupted_writes()?;
+                Box::new(backend)
+            }
+            ChunkStoreBackend::Memory => Box::new(MemoryBackend::default()),
+            ChunkStoreBackend::Db => Box::new(DbBackend::new(&dir)?),
+        };
+        let mut chunk_store = ChunkStore {
             dir,
+            backend,
             used_space,
             id,
-            _phantom: PhantomData,
-        })
+            scrub_queue: VecDeque::new(),
+            cache: if config.cache_size > 0 {
+                Some(Mutex::new(ReadCache::new(config.cache_size)))
+            } else {
+                None
+            },
+            compress: config.compress,
+            encryption: config.encryption.clone(),
+            quota,
+        };
+        chunk_store.reconcile_used_space().await?;
+
+        Ok(chunk_store)
     }
 }
 
 impl<T: Chunk> ChunkStore<T> {
-    fn create_new_root(root: &Path) -> Result<()> {
-        fs::create_dir_all(root)?;
-
-        // Verify that chunk files can be created.
-        let temp_file_path = root.join("0".repeat(MAX_CHUNK_FILE_NAME_LENGTH));
-        let _ = File::create(&temp_file_path)?;
-        fs::remove_file(temp_file_path)?;
-
-        Ok(())
-    }
-
     /// Stores a new data chunk.
     ///
-    /// If there is not enough storage space available, returns `Error::NotEnoughSpace`.  In case of
-    /// an IO error, it returns `Error::Io`.
+    /// If there is not enough storage space available, returns `Error::NotEnoughSpace`, or
+    /// `Error::QuotaExceeded` if it's this store's own quota which is reached.  In case of an IO
+    /// error, it returns `Error::Io`.
     ///
     /// If a chunk with the same id already exists, it will be overwritten.
+    ///
+    /// The used space accounted for is that of the stored, possibly compressed, chunk.
     pub async fn put(&mut self, chunk: &T) -> Result<()> {
         info!("Writing chunk");
-        let serialised_chunk = utils::serialise(chunk)?;
+        let serialised_chunk = encoding::encode(
+            &utils::serialise(chunk)?,
+            self.compress,
+            self.encryption.as_ref(),
+        )?;
         let consumed_space = serialised_chunk.len() as u64;
 
         info!("consumed space: {:?}", consumed_space);
         info!("max : {:?}", self.used_space.max_capacity().await);
         info!("use space total : {:?}", self.used_space.total().await);
 
-        let file_path = self.file_path(chunk.id())?;
-        self.do_delete(&file_path).await?;
+        let key = chunk.id().to_db_key()?;
+        self.uncache(&key);
+        let replaced_space = self.backend.size(&key).await.unwrap_or(0);
 
-        // pre-reserve space
-        self.used_space.increase(self.id, consumed_space).await?;
+        // Pre-reserve only what an overwrite adds on top of the chunk it replaces.
+        let reserved_space = consumed_space.saturating_sub(replaced_space);
+        self.used_space.increase(self.id, reserved_space).await?;
         trace!(
             "use space total after add: {:?}",
             self.used_space.total().await
         );
 
-        let res = File::create(&file_path).and_then(|mut file| {
-            file.write_all(&serialised_chunk)?;
-            file.sync_all()
-        });
-
-        match res {
-            Ok(_) => {
+        match self.backend.put(&key, serialised_chunk).await {
+            Ok(()) => {
                 info!("Writing chunk succeeded!");
-                Ok(())
+                self.used_space
+                    .decrease(self.id, replaced_space.saturating_sub(consumed_space))
+                    .await
             }
             Err(e) => {
                 info!("Writing chunk failed!");
-                self.used_space.decrease(self.id, consumed_space).await?;
-                Err(e.into())
+                self.used_space.decrease(self.id, reserved_space).await?;
+                Err(e)
             }
         }
     }
@@ -138,34 +194,81 @@ impl<T: Chunk> ChunkStore<T> {
     /// If the data doesn't exist, it does nothing and returns `Ok`.  In the case of an IO error, it
     /// returns `Error::Io`.
     pub async fn delete(&mut self, id: &T::Id) -> Result<()> {
-        self.do_delete(&self.file_path(id)?).await
+        let key = id.to_db_key()?;
+        self.uncache(&key);
+        if let Some(size) = self.backend.size(&key).await {
+            self.used_space.decrease(self.id, size).await?;
+            self.backend.delete(&key).await
+        } else {
+            Ok(())
+        }
     }
 
-    /// Used space to max space ratio.
+    /// Used space to max space ratio, or this store's used space to quota ratio if that's
+    /// higher.
     pub async fn used_space_ratio(&self) -> f64 {
         let used = self.total_used_space().await;
         let total = self.used_space.max_capacity().await;
-        let used_space_ratio = used as f64 / total as f64;
+        let mut used_space_ratio = used as f64 / total as f64;
         info!("Used space: {:?}", used);
         info!("Total space: {:?}", total);
+        for usage in self.used_space.usage().await {
+            match usage.quota {
+                Some(quota) => info!(
+                    "Used space by {} store: {:?} of {:?} quota",
+                    usage.name, usage.used, quota
+                ),
+                None => info!("Used space by {} store: {:?}", usage.name, usage.used),
+            }
+        }
+        if let Some(quota) = self.quota {
+            let local = self.used_space.local(self.id).await;
+            used_space_ratio = used_space_ratio.max(local as f64 / quota as f64);
+        }
         info!("Used space ratio: {:?}", used_space_ratio);
         used_space_ratio
     }
 
-    /// Returns a data chunk previously stored under `id`.
+    /// Returns a data chunk previously stored under `id`, from the read cache if it's there.
     ///
-    /// If the data file can't be accessed, it returns `Error::NoSuchChunk`.
-    pub fn get(&self, id: &T::Id) -> Result<T> {
-        let mut file = File::open(self.file_path(id)?).map_err(|_| Error::NoSuchChunk)?;
-        let mut contents = vec![];
-        let _ = file.read_to_end(&mut contents)?;
+    /// If the data can't be accessed, it returns `Error::NoSuchChunk`.
+    pub async fn get(&self, id: &T::Id) -> Result<T> {
+        let key = id.to_db_key()?;
+        if let Some(chunk) = self.with_cache(|cache| cache.get(&key)).flatten() {
+            return Ok(chunk);
+        }
+
+        let contents = encoding::decode(
+            self.backend.get(&key).await?.ok_or(Error::NoSuchChunk)?,
+            self.encryption.as_ref(),
+        )?;
         let chunk = bincode::deserialize::<T>(&contents)?;
         // Check it's the requested chunk variant.
-        if chunk.id() == id {
-            Ok(chunk)
-        } else {
-            Err(Error::NoSuchChunk)
+        if chunk.id() != id {
+            return Err(Error::NoSuchChunk);
         }
+        let _ = self.with_cache(|cache| cache.insert(key, chunk.clone(), contents.len() as u64));
+        Ok(chunk)
+    }
+
+    /// Returns the read cache's counters, if caching is enabled.
+    #[cfg_attr(not(test), allow(unused))]
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.with_cache(|cache| cache.stats())
+    }
+
+    fn uncache(&self, key: &str) {
+        let _ = self.with_cache(|cache| cache.remove(key));
+    }
+
+    fn with_cache<R>(&self, f: impl FnOnce(&mut ReadCache<T>) -> R) -> Option<R> {
+        let cache = self.cache.as_ref()?;
+        // The cache only holds copies, so it's safe to use after a panic elsewhere.
+        let mut cache = match cache.lock() {
+            Ok(cache) => cache,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        Some(f(&mut cache))
     }
 
     pub async fn total_used_space(&self) -> u64 {
@@ -173,68 +276,143 @@ impl<T: Chunk> ChunkStore<T> {
     }
 
     /// Tests if a data chunk has been previously stored under `id`.
-    pub fn has(&self, id: &T::Id) -> bool {
-        if let Ok(path) = self.file_path(id) {
-            fs::metadata(path)
-                .as_ref()
-                .map(Metadata::is_file)
-                .unwrap_or(false)
+    pub async fn has(&self, id: &T::Id) -> bool {
+        if let Ok(key) = id.to_db_key() {
+            self.backend.has(&key).await
         } else {
             false
         }
     }
 
     /// Lists all keys of currently stored data.
-    #[cfg_attr(not(test), allow(unused))]
-    pub fn keys(&self) -> Vec<T::Id> {
-        fs::read_dir(&self.dir)
-            .map(|entries| {
-                entries
-                    .filter_map(|entry| to_chunk_id(&entry.ok()?))
-                    .collect()
-            })
-            .unwrap_or_else(|_| Vec::new())
-    }
-
-    async fn do_delete(&mut self, file_path: &Path) -> Result<()> {
-        if let Ok(metadata) = fs::metadata(file_path) {
-            self.used_space.decrease(self.id, metadata.len()).await?;
-            fs::remove_file(file_path).map_err(From::from)
-        } else {
-            Ok(())
+    pub async fn keys(&self) -> Vec<T::Id> {
+        self.backend
+            .keys()
+            .await
+            .iter()
+            .filter_map(|key| to_chunk_id(key))
+            .collect()
+    }
+
+    /// Recomputes the space used by this store from its chunks, and repairs the persisted record
+    /// if it has drifted from that, e.g. after files were removed by hand.
+    pub async fn reconcile_used_space(&mut self) -> Result<()> {
+        let mut actual = 0;
+        for key in self.backend.keys().await {
+            if to_chunk_id::<T::Id>(&key).is_some() {
+                actual += self.backend.size(&key).await.unwrap_or(0);
+            }
         }
+        let recorded = self.used_space.local(self.id).await;
+        if recorded == actual {
+            trace!("Used space at {} is {} bytes", self.dir.display(), actual);
+            return Ok(());
+        }
+
+        warn!(
+            "Used space at {} was recorded as {} bytes, but its chunks take {} bytes. Repairing record.",
+            self.dir.display(),
+            recorded,
+            actual
+        );
+        self.used_space.set_local(self.id, actual).await
     }
 
-    fn file_path(&self, id: &T::Id) -> Result<PathBuf> {
-        Ok(self.dir.join(&hex::encode(utils::serialise(id)?)))
+    /// Verifies up to `count` stored chunks, continuing from where the previous call left off and
+    /// starting over once every chunk has been checked.
+    ///
+    /// A chunk is corrupt if it can't be deserialised, or if it deserialises to a different id
+    /// than the one it's stored under.  For blobs this recomputes the address from the content.
+    /// Corrupt chunks are quarantined by the backend, their space is released, and their ids are
+    /// returned.
+    pub async fn scrub(&mut self, count: usize) -> Result<Vec<T::Id>> {
+        if self.scrub_queue.is_empty() {
+            self.scrub_queue = self
+                .backend
+                .keys()
+                .await
+                .into_iter()
+                .filter_map(|key| Some((to_chunk_id(&key)?, key)))
+                .collect();
+            trace!(
+                "Starting scrub pass over {} chunks at {}",
+                self.scrub_queue.len(),
+                self.dir.display()
+            );
+        }
+
+        let mut corrupt = vec![];
+        for _ in 0..count {
+            let (id, key) = match self.scrub_queue.pop_front() {
+                Some(entry) => entry,
+                None => break,
+            };
+            let contents = match self.backend.get(&key).await {
+                Ok(Some(contents)) => contents,
+                // Deleted since the pass started.
+                Ok(None) => continue,
+                Err(error) => {
+                    warn!("Could not read chunk {} for scrub: {}", key, error);
+                    continue;
+                }
+            };
+            let is_intact = encoding::decode(contents, self.encryption.as_ref())
+                .ok()
+                .and_then(|contents| bincode::deserialize::<T>(&contents).ok())
+                .map(|chunk| chunk.id() == &id)
+                .unwrap_or(false);
+            if !is_intact {
+                warn!("Chunk {} at {} is corrupt", key, self.dir.display());
+                let size = self.backend.size(&key).await.unwrap_or(0);
+                self.uncache(&key);
+                self.backend.quarantine(&key).await?;
+                self.used_space.decrease(self.id, size).await?;
+                corrupt.push(id);
+            }
+        }
+        Ok(corrupt)
     }
 }
 
 pub(crate) trait Subdir {
     fn subdir() -> &'static Path;
+
+    /// The most space this type of store may use, if limited beyond the node's max capacity.
+    fn quota(_config: &ChunkStoreConfig) -> Option<u64> {
+        None
+    }
 }
 
 impl Subdir for BlobChunkStore {
     fn subdir() -> &'static Path {
         Path::new("immutable")
     }
+
+    fn quota(config: &ChunkStoreConfig) -> Option<u64> {
+        config.blob_quota
+    }
 }
 
 impl Subdir for MapChunkStore {
     fn subdir() -> &'static Path {
         Path::new("mutable")
     }
+
+    fn quota(config: &ChunkStoreConfig) -> Option<u64> {
+        config.map_quota
+    }
 }
 
 impl Subdir for SequenceChunkStore {
     fn subdir() -> &'static Path {
         Path::new("sequence")
     }
+
+    fn quota(config: &ChunkStoreConfig) -> Option<u64> {
+        config.sequence_quota
+    }
 }
 
-fn to_chunk_id<T: ChunkId>(entry: &DirEntry) -> Option<T> {
-    let file_name = entry.file_name();
-    let file_name = file_name.into_string().ok()?;
-    let bytes = hex::decode(file_name).ok()?;
-    bincode::deserialize(&bytes).ok()
+fn to_chunk_id<T: ChunkId>(key: &str) -> Option<T> {
+    from_db_key(key).ok()
 }
@@ -0,0 +1,94 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use log::info;
+use lru::LruCache;
+
+/// Number of lookups between two logs of the cache's counters.
+const STATS_LOG_INTERVAL: u64 = 1000;
+
+/// Hit and miss counters of a `ReadCache`, along with what it currently holds.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Serialised size of the cached chunks.
+    pub bytes: u64,
+    pub entries: usize,
+}
+
+/// Keeps the most recently read chunks of a `ChunkStore` in memory, up to a budget of their
+/// serialised size, evicting the least recently used ones first.
+pub(crate) struct ReadCache<T> {
+    budget: u64,
+    entries: LruCache<String, (T, u64)>,
+    stats: CacheStats,
+    // Number of lookups at which the counters are next logged.
+    next_log: u64,
+}
+
+impl<T: Clone> ReadCache<T> {
+    pub(crate) fn new(budget: u64) -> Self {
+        Self {
+            budget,
+            entries: LruCache::unbounded(),
+            stats: CacheStats::default(),
+            next_log: STATS_LOG_INTERVAL,
+        }
+    }
+
+    /// Returns a copy of the chunk cached under `key`, counting a hit or a miss.
+    pub(crate) fn get(&mut self, key: &str) -> Option<T> {
+        let chunk = self
+            .entries
+            .get(&key.to_string())
+            .map(|(chunk, _)| chunk.clone());
+        if chunk.is_some() {
+            self.stats.hits += 1;
+        } else {
+            self.stats.misses += 1;
+        }
+        let lookups = self.stats.hits + self.stats.misses;
+        if lookups >= self.next_log {
+            info!("Chunk read cache: {:?}", self.stats());
+            self.next_log = lookups + STATS_LOG_INTERVAL;
+        }
+        chunk
+    }
+
+    /// Caches `chunk` under `key`, evicting others as needed to stay within budget.  Chunks
+    /// larger than the whole budget aren't cached.
+    pub(crate) fn insert(&mut self, key: String, chunk: T, size: u64) {
+        if size > self.budget {
+            return;
+        }
+        self.remove(&key);
+        while self.stats.bytes + size > self.budget {
+            match self.entries.pop_lru() {
+                Some((_, (_, evicted_size))) => self.stats.bytes -= evicted_size,
+                None => break,
+            }
+        }
+        self.stats.bytes += size;
+        let _ = self.entries.put(key, (chunk, size));
+    }
+
+    /// Drops the chunk cached under `key`, if any.
+    pub(crate) fn remove(&mut self, key: &str) {
+        if let Some((_, size)) = self.entries.pop(&key.to_string()) {
+            self.stats.bytes -= size;
+        }
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.len(),
+            ..self.stats
+        }
+    }
+}
@@ -7,16 +7,27 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
+    backend,
     chunk::{Chunk, ChunkId},
-    ChunkStore, Result as ChunkStoreResult, Subdir, UsedSpace,
+    encoding, ChunkStore, ChunkStoreConfig, Result as ChunkStoreResult, Subdir, UsedSpace,
 };
-use crate::{Error, Result, ToDbKey};
+use crate::{encryption::EncryptionKey, utils, ChunkStoreBackend, Error, Result, ToDbKey};
 use rand::{distributions::Standard, rngs::ThreadRng, Rng};
 use serde::{Deserialize, Serialize};
-use std::{path::Path, u64};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+    u64,
+};
 use tempdir::TempDir;
+use tokio::time::{self, Instant};
 
-#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 struct Data {
     id: Id,
     value: Vec<u8>,
@@ -58,7 +69,7 @@ struct Chunks {
 
 impl Chunks {
     // Construct random amount of randomly-sized chunks, keeping track of the total size of all
-    // chunks when serialised.
+    // chunks when stored.
     fn gen<R: Rng>(rng: &mut R) -> Result<Self> {
         let mut chunks = Self {
             data_and_sizes: vec![],
@@ -71,7 +82,7 @@ impl Chunks {
                 id: Id(0),
                 value: rng.sample_iter(&Standard).take(size as usize).collect(),
             };
-            let serialised_size = bincode::serialized_size(&data).map_err(Error::Bincode)?;
+            let serialised_size = stored_size(&data)?;
 
             chunks.total_size += serialised_size;
             chunks.data_and_sizes.push((data.value, serialised_size));
@@ -80,6 +91,20 @@ impl Chunks {
     }
 }
 
+/// Size of `data` once stored uncompressed, i.e. serialised with a header.
+fn stored_size(data: &Data) -> Result<u64> {
+    Ok(encoding::encode(&utils::serialise(data)?, false, None)?.len() as u64)
+}
+
+/// Path of the file the `Fs` backend keeps the chunk with `id` in.
+fn file_path(chunk_store: &ChunkStore<Data>, id: &Id, shard_depth: u8) -> Result<PathBuf> {
+    Ok(backend::file_path(
+        &chunk_store.dir,
+        &id.to_db_key()?,
+        shard_depth,
+    ))
+}
+
 #[tokio::test]
 async fn successful_put() -> Result<()> {
     let mut rng = new_rng();
@@ -87,7 +112,8 @@ async fn successful_put() -> Result<()> {
 
     let root = temp_dir()?;
     let used_space = UsedSpace::new(u64::MAX);
-    let mut chunk_store = ChunkStore::<Data>::new(root.path(), used_space.clone()).await?;
+    let mut chunk_store =
+        ChunkStore::<Data>::new(root.path(), used_space.clone(), &Default::default()).await?;
 
     for (index, (data, size)) in chunks.data_and_sizes.iter().enumerate().rev() {
         let the_data = &Data {
@@ -95,17 +121,17 @@ async fn successful_put() -> Result<()> {
             value: data.clone(),
         };
         let used_space_before = chunk_store.total_used_space().await;
-        assert!(!chunk_store.has(&the_data.id));
+        assert!(!chunk_store.has(&the_data.id).await);
         chunk_store.put(the_data).await?;
         let used_space_after = chunk_store.total_used_space().await;
         assert_eq!(used_space_after, used_space_before + size);
-        assert!(chunk_store.has(&the_data.id));
+        assert!(chunk_store.has(&the_data.id).await);
         assert!(used_space_after <= chunks.total_size);
     }
 
     assert_eq!(chunk_store.total_used_space().await, chunks.total_size);
 
-    let mut keys = chunk_store.keys();
+    let mut keys = chunk_store.keys().await;
     keys.sort();
     assert_eq!(
         (0..chunks.data_and_sizes.len())
@@ -123,7 +149,8 @@ async fn failed_put_when_not_enough_space() -> Result<()> {
     let root = temp_dir()?;
     let capacity = 32;
     let used_space = UsedSpace::new(capacity);
-    let mut chunk_store = ChunkStore::new(root.path(), used_space.clone()).await?;
+    let mut chunk_store =
+        ChunkStore::new(root.path(), used_space.clone(), &Default::default()).await?;
 
     let data = Data {
         id: Id(rng.gen()),
@@ -148,7 +175,8 @@ async fn delete() -> Result<()> {
 
     let root = temp_dir()?;
     let used_space = UsedSpace::new(u64::MAX);
-    let mut chunk_store = ChunkStore::new(root.path(), used_space.clone()).await?;
+    let mut chunk_store =
+        ChunkStore::new(root.path(), used_space.clone(), &Default::default()).await?;
 
     for (index, (data, size)) in chunks.data_and_sizes.iter().enumerate() {
         let the_data = &Data {
@@ -157,9 +185,9 @@ async fn delete() -> Result<()> {
         };
         chunk_store.put(the_data).await?;
         assert_eq!(chunk_store.total_used_space().await, *size);
-        assert!(chunk_store.has(&the_data.id));
+        assert!(chunk_store.has(&the_data.id).await);
         chunk_store.delete(&the_data.id).await?;
-        assert!(!chunk_store.has(&the_data.id));
+        assert!(!chunk_store.has(&the_data.id).await);
         assert_eq!(chunk_store.total_used_space().await, 0);
     }
 
@@ -173,7 +201,8 @@ async fn put_and_get_value_should_be_same() -> Result<()> {
 
     let root = temp_dir()?;
     let used_space = UsedSpace::new(u64::MAX);
-    let mut chunk_store = ChunkStore::new(root.path(), used_space.clone()).await?;
+    let mut chunk_store =
+        ChunkStore::new(root.path(), used_space.clone(), &Default::default()).await?;
 
     for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
         chunk_store
@@ -185,7 +214,7 @@ async fn put_and_get_value_should_be_same() -> Result<()> {
     }
 
     for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
-        let retrieved_value = chunk_store.get(&Id(index as u64))?;
+        let retrieved_value = chunk_store.get(&Id(index as u64)).await?;
         assert_eq!(*data, retrieved_value.value);
     }
 
@@ -199,7 +228,8 @@ async fn overwrite_value() -> Result<()> {
 
     let root = temp_dir()?;
     let used_space = UsedSpace::new(u64::MAX);
-    let mut chunk_store = ChunkStore::new(root.path(), used_space.clone()).await?;
+    let mut chunk_store =
+        ChunkStore::new(root.path(), used_space.clone(), &Default::default()).await?;
 
     for (data, size) in chunks.data_and_sizes {
         chunk_store
@@ -209,21 +239,43 @@ async fn overwrite_value() -> Result<()> {
             })
             .await?;
         assert_eq!(chunk_store.total_used_space().await, size);
-        let retrieved_data = chunk_store.get(&Id(0))?;
+        let retrieved_data = chunk_store.get(&Id(0)).await?;
         assert_eq!(data, retrieved_data.value);
     }
 
     Ok(())
 }
 
+#[tokio::test]
+async fn overwrite_at_capacity() -> Result<()> {
+    let mut rng = new_rng();
+    let data = Data {
+        id: Id(0),
+        value: rng.sample_iter(&Standard).take(64).collect(),
+    };
+    let size = stored_size(&data)?;
+
+    let root = temp_dir()?;
+    let used_space = UsedSpace::new(size);
+    let mut chunk_store =
+        ChunkStore::new(root.path(), used_space.clone(), &Default::default()).await?;
+
+    chunk_store.put(&data).await?;
+    chunk_store.put(&data).await?;
+    assert_eq!(chunk_store.total_used_space().await, size);
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn get_fails_when_key_does_not_exist() -> Result<()> {
     let root = temp_dir()?;
     let used_space = UsedSpace::new(u64::MAX);
-    let chunk_store: ChunkStore<Data> = ChunkStore::new(root.path(), used_space.clone()).await?;
+    let chunk_store: ChunkStore<Data> =
+        ChunkStore::new(root.path(), used_space.clone(), &Default::default()).await?;
 
     let id = Id(new_rng().gen());
-    match chunk_store.get(&id) {
+    match chunk_store.get(&id).await {
         Err(Error::NoSuchChunk) => (),
         x => return Err(crate::Error::Logic(format!("Unexpected {:?}", x))),
     }
@@ -238,11 +290,12 @@ async fn keys() -> Result<()> {
 
     let root = temp_dir()?;
     let used_space = UsedSpace::new(u64::MAX);
-    let mut chunk_store = ChunkStore::new(root.path(), used_space.clone()).await?;
+    let mut chunk_store =
+        ChunkStore::new(root.path(), used_space.clone(), &Default::default()).await?;
 
     for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
         let id = Id(index as u64);
-        assert!(!chunk_store.keys().contains(&id));
+        assert!(!chunk_store.keys().await.contains(&id));
         chunk_store
             .put(&Data {
                 id,
@@ -250,7 +303,7 @@ async fn keys() -> Result<()> {
             })
             .await?;
 
-        let keys = chunk_store.keys();
+        let keys = chunk_store.keys().await;
         assert!(keys.contains(&id));
         assert_eq!(keys.len(), index + 1);
     }
@@ -258,13 +311,509 @@ async fn keys() -> Result<()> {
     for (index, _) in chunks.data_and_sizes.iter().enumerate() {
         let id = Id(index as u64);
 
-        assert!(chunk_store.keys().contains(&id));
+        assert!(chunk_store.keys().await.contains(&id));
         chunk_store.delete(&id).await?;
 
-        let keys = chunk_store.keys();
+        let keys = chunk_store.keys().await;
         assert!(!keys.contains(&id));
         assert_eq!(keys.len(), chunks.data_and_sizes.len() - index - 1);
     }
 
     Ok(())
 }
+
+#[tokio::test]
+async fn sharded_layout() -> Result<()> {
+    let mut rng = new_rng();
+    let chunks = Chunks::gen(&mut rng)?;
+
+    let root = temp_dir()?;
+    let used_space = UsedSpace::new(u64::MAX);
+    let config = ChunkStoreConfig {
+        shard_depth: 2,
+        ..Default::default()
+    };
+    let mut chunk_store = ChunkStore::new(root.path(), used_space.clone(), &config).await?;
+
+    for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
+        let id = Id(index as u64);
+        chunk_store
+            .put(&Data {
+                id,
+                value: data.clone(),
+            })
+            .await?;
+
+        let path = file_path(&chunk_store, &id, 2)?;
+        assert!(path.is_file());
+        assert_eq!(
+            path.components().count(),
+            chunk_store.dir.components().count() + 3
+        );
+        assert_eq!(chunk_store.get(&id).await?.value, *data);
+    }
+
+    assert_eq!(chunk_store.keys().await.len(), chunks.data_and_sizes.len());
+    assert_eq!(chunk_store.total_used_space().await, chunks.total_size);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn migrates_between_layouts() -> Result<()> {
+    let mut rng = new_rng();
+    let chunks = Chunks::gen(&mut rng)?;
+    let root = temp_dir()?;
+
+    let used_space = UsedSpace::new(u64::MAX);
+    let flat = ChunkStoreConfig {
+        shard_depth: 0,
+        ..Default::default()
+    };
+    let mut chunk_store = ChunkStore::new(root.path(), used_space, &flat).await?;
+    for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
+        chunk_store
+            .put(&Data {
+                id: Id(index as u64),
+                value: data.clone(),
+            })
+            .await?;
+    }
+    drop(chunk_store);
+
+    for shard_depth in &[2, 1, 0] {
+        let used_space = UsedSpace::new(u64::MAX);
+        let config = ChunkStoreConfig {
+            shard_depth: *shard_depth,
+            ..Default::default()
+        };
+        let chunk_store = ChunkStore::<Data>::new(root.path(), used_space.clone(), &config).await?;
+
+        let mut keys = chunk_store.keys().await;
+        keys.sort();
+        assert_eq!(
+            (0..chunks.data_and_sizes.len())
+                .map(|i| Id(i as u64))
+                .collect::<Vec<_>>(),
+            keys
+        );
+        for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
+            assert_eq!(chunk_store.get(&Id(index as u64)).await?.value, *data);
+        }
+        assert_eq!(used_space.local(chunk_store.id).await, chunks.total_size);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn scrub_quarantines_corrupt_chunks() -> Result<()> {
+    let mut rng = new_rng();
+    let chunks = Chunks::gen(&mut rng)?;
+    if chunks.data_and_sizes.len() < 3 {
+        return Ok(());
+    }
+
+    let root = temp_dir()?;
+    let used_space = UsedSpace::new(u64::MAX);
+    let mut chunk_store = ChunkStore::new(root.path(), used_space, &Default::default()).await?;
+    for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
+        chunk_store
+            .put(&Data {
+                id: Id(index as u64),
+                value: data.clone(),
+            })
+            .await?;
+    }
+
+    // A truncated file, and a file holding another chunk's content.
+    let truncated = file_path(&chunk_store, &Id(0), 0)?;
+    let contents = fs::read(&truncated)?;
+    fs::write(&truncated, &contents[..contents.len() / 2])?;
+    let misplaced = file_path(&chunk_store, &Id(1), 0)?;
+    let _ = fs::copy(file_path(&chunk_store, &Id(2), 0)?, &misplaced)?;
+    let remaining_size = chunk_store.total_used_space().await
+        - fs::metadata(&truncated)?.len()
+        - fs::metadata(&misplaced)?.len();
+
+    // Scrub in batches, which together cover the whole store once.
+    let mut corrupt = vec![];
+    for _ in 0..chunks.data_and_sizes.len() {
+        corrupt.extend(chunk_store.scrub(1).await?);
+    }
+    corrupt.sort();
+    assert_eq!(corrupt, vec![Id(0), Id(1)]);
+
+    assert!(!truncated.exists());
+    assert!(!misplaced.exists());
+    let quarantine_dir = root.path().join("chunks").join("quarantine").join("test");
+    assert_eq!(quarantine_dir.read_dir()?.count(), 2);
+    assert!(!chunk_store.has(&Id(0)).await);
+    assert!(chunk_store.get(&Id(2)).await.is_ok());
+    assert_eq!(
+        chunk_store.keys().await.len(),
+        chunks.data_and_sizes.len() - 2
+    );
+    assert_eq!(chunk_store.total_used_space().await, remaining_size);
+
+    // Intact chunks are left alone on the next pass.
+    assert!(chunk_store.scrub(usize::MAX).await?.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn recovers_from_interrupted_put() -> Result<()> {
+    let root = temp_dir()?;
+    let config = ChunkStoreConfig {
+        shard_depth: 2,
+        ..Default::default()
+    };
+    let data = Data {
+        id: Id(0),
+        value: vec![1; 64],
+    };
+    let size = stored_size(&data)?;
+
+    let used_space = UsedSpace::new(u64::MAX);
+    let mut chunk_store = ChunkStore::new(root.path(), used_space.clone(), &config).await?;
+    chunk_store.put(&data).await?;
+
+    // Simulate a crash while writing another chunk: its space is reserved, but its content only
+    // partially written to the temp file.
+    let file_path = file_path(&chunk_store, &Id(1), 2)?;
+    fs::create_dir_all(file_path.parent().unwrap_or(&chunk_store.dir))?;
+    chunk_store.used_space.increase(chunk_store.id, 100).await?;
+    let temp_path = backend::temp_file_path(&file_path);
+    fs::write(&temp_path, [0; 10])?;
+    assert_eq!(used_space.local(chunk_store.id).await, size + 100);
+    drop(chunk_store);
+
+    let used_space = UsedSpace::new(u64::MAX);
+    let chunk_store = ChunkStore::<Data>::new(root.path(), used_space.clone(), &config).await?;
+    assert!(!temp_path.exists());
+    assert_eq!(used_space.local(chunk_store.id).await, size);
+    assert_eq!(chunk_store.keys().await, vec![Id(0)]);
+    assert_eq!(chunk_store.get(&Id(0)).await?, data);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn reconciles_used_space() -> Result<()> {
+    let mut rng = new_rng();
+    let chunks = Chunks::gen(&mut rng)?;
+    if chunks.data_and_sizes.len() < 2 {
+        return Ok(());
+    }
+    let root = temp_dir()?;
+
+    let used_space = UsedSpace::new(u64::MAX);
+    let mut chunk_store = ChunkStore::new(root.path(), used_space, &Default::default()).await?;
+    for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
+        chunk_store
+            .put(&Data {
+                id: Id(index as u64),
+                value: data.clone(),
+            })
+            .await?;
+    }
+    let dir = chunk_store.dir.clone();
+    drop(chunk_store);
+
+    // Chunk removed by hand, and an unparsable record.
+    let (_, removed_size) = chunks.data_and_sizes[0];
+    fs::remove_file(dir.join(hex::encode(utils::serialise(&Id(0))?)))?;
+    fs::write(dir.join("used_space"), [1, 2, 3])?;
+
+    let used_space = UsedSpace::new(u64::MAX);
+    let mut chunk_store =
+        ChunkStore::<Data>::new(root.path(), used_space.clone(), &Default::default()).await?;
+    assert_eq!(
+        used_space.local(chunk_store.id).await,
+        chunks.total_size - removed_size
+    );
+    assert_eq!(used_space.total().await, chunks.total_size - removed_size);
+
+    // On demand, while the store is open.
+    let (_, removed_size_2) = chunks.data_and_sizes[1];
+    fs::remove_file(file_path(&chunk_store, &Id(1), 0)?)?;
+    chunk_store.reconcile_used_space().await?;
+    assert_eq!(
+        used_space.total().await,
+        chunks.total_size - removed_size - removed_size_2
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn memory_and_db_backends() -> Result<()> {
+    let mut rng = new_rng();
+    let chunks = Chunks::gen(&mut rng)?;
+
+    for backend in &[ChunkStoreBackend::Memory, ChunkStoreBackend::Db] {
+        let root = temp_dir()?;
+        let config = ChunkStoreConfig {
+            backend: *backend,
+            ..Default::default()
+        };
+        let used_space = UsedSpace::new(u64::MAX);
+        let mut chunk_store = ChunkStore::new(root.path(), used_space.clone(), &config).await?;
+        for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
+            let id = Id(index as u64);
+            chunk_store
+                .put(&Data {
+                    id,
+                    value: data.clone(),
+                })
+                .await?;
+            assert!(chunk_store.has(&id).await);
+            assert_eq!(chunk_store.get(&id).await?.value, *data);
+        }
+        assert_eq!(chunk_store.keys().await.len(), chunks.data_and_sizes.len());
+        assert_eq!(used_space.total().await, chunks.total_size);
+
+        for index in 0..chunks.data_and_sizes.len() {
+            chunk_store.delete(&Id(index as u64)).await?;
+        }
+        assert!(chunk_store.keys().await.is_empty());
+        assert_eq!(used_space.total().await, 0);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn db_backend_persists() -> Result<()> {
+    let mut rng = new_rng();
+    let chunks = Chunks::gen(&mut rng)?;
+    let root = temp_dir()?;
+    let config = ChunkStoreConfig {
+        backend: ChunkStoreBackend::Db,
+        ..Default::default()
+    };
+
+    let mut chunk_store = ChunkStore::new(root.path(), UsedSpace::new(u64::MAX), &config).await?;
+    for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
+        chunk_store
+            .put(&Data {
+                id: Id(index as u64),
+                value: data.clone(),
+            })
+            .await?;
+    }
+    drop(chunk_store);
+
+    let used_space = UsedSpace::new(u64::MAX);
+    let chunk_store = ChunkStore::<Data>::new(root.path(), used_space.clone(), &config).await?;
+    assert_eq!(chunk_store.keys().await.len(), chunks.data_and_sizes.len());
+    for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
+        assert_eq!(chunk_store.get(&Id(index as u64)).await?.value, *data);
+    }
+    assert_eq!(used_space.local(chunk_store.id).await, chunks.total_size);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn read_cache() -> Result<()> {
+    let root = temp_dir()?;
+    let chunk = |id, value| Data {
+        id: Id(id),
+        value: vec![value; 100],
+    };
+    let size = bincode::serialized_size(&chunk(0, 0)).map_err(Error::Bincode)?;
+    let config = ChunkStoreConfig {
+        cache_size: 2 * size,
+        ..Default::default()
+    };
+    let mut chunk_store = ChunkStore::new(root.path(), UsedSpace::new(u64::MAX), &config).await?;
+    for id in 0..3 {
+        chunk_store.put(&chunk(id, 0)).await?;
+    }
+
+    // First reads miss, repeated ones hit.
+    assert_eq!(chunk_store.get(&Id(0)).await?, chunk(0, 0));
+    assert_eq!(chunk_store.get(&Id(0)).await?, chunk(0, 0));
+    let _ = chunk_store.get(&Id(1)).await?;
+    let stats = chunk_store.cache_stats().unwrap_or_default();
+    assert_eq!((stats.hits, stats.misses), (1, 2));
+    assert_eq!((stats.entries, stats.bytes), (2, 2 * size));
+
+    // Reading a third chunk evicts the least recently used one.
+    let _ = chunk_store.get(&Id(2)).await?;
+    let _ = chunk_store.get(&Id(1)).await?;
+    let _ = chunk_store.get(&Id(0)).await?;
+    let stats = chunk_store.cache_stats().unwrap_or_default();
+    assert_eq!((stats.hits, stats.misses), (2, 4));
+    assert_eq!((stats.entries, stats.bytes), (2, 2 * size));
+
+    // Overwriting and deleting invalidate the cached copy.
+    chunk_store.put(&chunk(0, 1)).await?;
+    assert_eq!(chunk_store.get(&Id(0)).await?, chunk(0, 1));
+    chunk_store.delete(&Id(0)).await?;
+    assert!(chunk_store.get(&Id(0)).await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn compression() -> Result<()> {
+    let root = temp_dir()?;
+    let compressible = Data {
+        id: Id(0),
+        value: vec![7; 4096],
+    };
+    let incompressible = Data {
+        id: Id(1),
+        value: new_rng().sample_iter(&Standard).take(4096).collect(),
+    };
+    let config = ChunkStoreConfig {
+        compress: true,
+        ..Default::default()
+    };
+    let used_space = UsedSpace::new(u64::MAX);
+    let mut chunk_store = ChunkStore::new(root.path(), used_space.clone(), &config).await?;
+
+    chunk_store.put(&compressible).await?;
+    let compressed_size = fs::metadata(file_path(&chunk_store, &Id(0), 0)?)?.len();
+    assert!(compressed_size < stored_size(&compressible)? / 10);
+    assert_eq!(used_space.total().await, compressed_size);
+    assert_eq!(chunk_store.get(&Id(0)).await?, compressible);
+
+    // Stored as is when compressing doesn't pay off.
+    chunk_store.put(&incompressible).await?;
+    let stored = fs::read(file_path(&chunk_store, &Id(1), 0)?)?;
+    assert_eq!(stored.len() as u64, stored_size(&incompressible)?);
+    assert_eq!(chunk_store.get(&Id(1)).await?, incompressible);
+
+    // Chunks stored before headers were introduced are still read.
+    let legacy = Data {
+        id: Id(2),
+        value: vec![1, 2, 3],
+    };
+    fs::write(
+        file_path(&chunk_store, &Id(2), 0)?,
+        utils::serialise(&legacy)?,
+    )?;
+    assert_eq!(chunk_store.get(&Id(2)).await?, legacy);
+
+    // A corrupt decompressed size is rejected rather than allocated.
+    let mut corrupt = fs::read(file_path(&chunk_store, &Id(0), 0)?)?;
+    // i.e. after the 7 byte header
+    corrupt[7..15].copy_from_slice(&u64::MAX.to_le_bytes());
+    fs::write(file_path(&chunk_store, &Id(0), 0)?, corrupt)?;
+    assert!(matches!(
+        chunk_store.get(&Id(0)).await,
+        Err(Error::ChunkFormat(_))
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn encryption() -> Result<()> {
+    let root = temp_dir()?;
+    let data = Data {
+        id: Id(0),
+        value: b"private contents".to_vec(),
+    };
+    let key = EncryptionKey::generate()?;
+    let config = ChunkStoreConfig {
+        compress: true,
+        encryption: Some(key.clone()),
+        ..Default::default()
+    };
+    let used_space = UsedSpace::new(u64::MAX);
+    let mut chunk_store = ChunkStore::new(root.path(), used_space.clone(), &config).await?;
+
+    chunk_store.put(&data).await?;
+    let stored = fs::read(file_path(&chunk_store, &Id(0), 0)?)?;
+    assert!(!stored
+        .windows(data.value.len())
+        .any(|window| window == &data.value[..]));
+    assert_eq!(chunk_store.get(&Id(0)).await?, data);
+
+    // Chunks stored unencrypted, with or without a version 1 header, are still read.
+    let plain = Data {
+        id: Id(1),
+        value: vec![1, 2, 3],
+    };
+    fs::write(
+        file_path(&chunk_store, &Id(1), 0)?,
+        utils::serialise(&plain)?,
+    )?;
+    assert_eq!(chunk_store.get(&Id(1)).await?, plain);
+    let mut v1 = b"SNCK\x01\x00".to_vec();
+    v1.extend_from_slice(&utils::serialise(&plain)?);
+    fs::write(file_path(&chunk_store, &Id(1), 0)?, v1)?;
+    assert_eq!(chunk_store.get(&Id(1)).await?, plain);
+
+    // Encrypted chunks can't be read without the key, or with another one.
+    for encryption in &[None, Some(EncryptionKey::generate()?)] {
+        let config = ChunkStoreConfig {
+            encryption: encryption.clone(),
+            ..Default::default()
+        };
+        let chunk_store: ChunkStore<Data> =
+            ChunkStore::new(root.path(), UsedSpace::new(u64::MAX), &config).await?;
+        assert!(chunk_store.get(&Id(0)).await.is_err());
+    }
+
+    Ok(())
+}
+
+/// Reads chunks on a single-threaded runtime while a timer ticks every millisecond on it.  Were
+/// the file reads done on the runtime's thread, the timer couldn't tick until they're all done.
+#[tokio::test]
+async fn reads_do_not_block_event_loop() -> Result<()> {
+    const CHUNK_COUNT: u64 = 32;
+    const CHUNK_SIZE: usize = 16 * 1024;
+    const ROUNDS: usize = 8;
+
+    let root = temp_dir()?;
+    let used_space = UsedSpace::new(u64::MAX);
+    let mut chunk_store = ChunkStore::new(root.path(), used_space, &Default::default()).await?;
+    for id in 0..CHUNK_COUNT {
+        chunk_store
+            .put(&Data {
+                id: Id(id),
+                value: vec![id as u8; CHUNK_SIZE],
+            })
+            .await?;
+    }
+
+    let done = Arc::new(AtomicBool::new(false));
+    let ticking = done.clone();
+    let ticker = tokio::spawn(async move {
+        let mut interval = time::interval(Duration::from_millis(1));
+        let mut ticks = 0;
+        while !ticking.load(Ordering::Relaxed) {
+            let _ = interval.tick().await;
+            ticks += 1;
+        }
+        ticks
+    });
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for id in 0..CHUNK_COUNT {
+            let _ = chunk_store.get(&Id(id)).await?;
+        }
+    }
+    let elapsed = start.elapsed();
+    done.store(true, Ordering::Relaxed);
+    let ticks = ticker
+        .await
+        .map_err(|error| Error::Logic(error.to_string()))?;
+
+    assert!(
+        ticks > ROUNDS,
+        "timer ticked {} times while reading for {:?}",
+        ticks,
+        elapsed
+    );
+
+    Ok(())
+}
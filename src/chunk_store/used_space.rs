@@ -7,9 +7,9 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{Error, Result};
-use log::warn;
+use log::{trace, warn};
 use std::{path::Path, sync::Arc};
-use tokio::{io::AsyncSeekExt, sync::Mutex};
+use tokio::{io::AsyncSeekExt, sync::Mutex, task};
 
 const USED_SPACE_FILENAME: &str = "used_space";
 
@@ -24,6 +24,17 @@ pub struct UsedSpace {
 /// used space tracking
 pub type StoreId = u64;
 
+/// Snapshot of the space used by a single `ChunkStore`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoreUsage {
+    /// Name of the store, i.e. of its directory
+    pub name: String,
+    /// Space consumed by the store
+    pub used: u64,
+    /// Most space the store may consume, if it has a quota of its own
+    pub quota: Option<u64>,
+}
+
 impl UsedSpace {
     /// construct a new used space instance
     /// NOTE: this constructs a new async-safe instance,
@@ -43,11 +54,29 @@ impl UsedSpace {
     }
 
     /// Returns the maximum capacity (e.g. the maximum
-    /// value that total() can return)
+    /// value that total() can return): the configured one,
+    /// or less if the disk doesn't have room for it
     pub async fn max_capacity(&self) -> u64 {
         inner::UsedSpace::max_capacity(self.inner.clone()).await
     }
 
+    /// Sets the configured maximum capacity, e.g. on resizing the running node.
+    /// Space already used above it stays used, but nothing more can be stored
+    pub async fn set_max_capacity(&self, max_capacity: u64) {
+        inner::UsedSpace::set_max_capacity(self.inner.clone(), max_capacity).await
+    }
+
+    /// Measures the free space on the filesystem holding `dir`, and limits
+    /// the capacity to the space used plus that until the next measurement
+    pub async fn refresh_free_space<T: AsRef<Path>>(&self, dir: T) -> Result<()> {
+        let dir = dir.as_ref().to_path_buf();
+        let available = task::spawn_blocking(move || fs2::available_space(dir))
+            .await
+            .map_err(|error| Error::Logic(format!("Free space task failed: {}", error)))??;
+        inner::UsedSpace::set_free_space(self.inner.clone(), available).await;
+        Ok(())
+    }
+
     /// Returns the total used space as a snapshot
     /// Note, due to the async nature of this, the value
     /// may be stale by the time it is read if there are multiple
@@ -60,15 +89,24 @@ impl UsedSpace {
     /// Note, due to the async nature of this, the value
     /// may be stale by the time it is read if there are multiple
     /// writers
-    #[allow(unused)]
     pub async fn local(&self, id: StoreId) -> u64 {
         inner::UsedSpace::local(self.inner.clone(), id).await
     }
 
+    /// Returns the used space of each local store as a snapshot, in the order they were added
+    pub async fn usage(&self) -> Vec<StoreUsage> {
+        inner::UsedSpace::usage(self.inner.clone()).await
+    }
+
     /// Add an object and file store to track used space of a single
-    /// `ChunkStore`
-    pub async fn add_local_store<T: AsRef<Path>>(&self, dir: T) -> Result<StoreId> {
-        inner::UsedSpace::add_local_store(self.inner.clone(), dir).await
+    /// `ChunkStore`, optionally limited to a `quota` on top of the
+    /// maximum capacity shared by all stores
+    pub async fn add_local_store<T: AsRef<Path>>(
+        &self,
+        dir: T,
+        quota: Option<u64>,
+    ) -> Result<StoreId> {
+        inner::UsedSpace::add_local_store(self.inner.clone(), dir, quota).await
     }
 
     /// Increase the used amount of a single chunk store and the global used value
@@ -80,6 +118,18 @@ impl UsedSpace {
     pub async fn decrease(&self, id: StoreId, released: u64) -> Result<()> {
         inner::UsedSpace::decrease(self.inner.clone(), id, released).await
     }
+
+    /// Overwrite the used amount of a single chunk store, e.g. with what was measured on disk,
+    /// adjusting the global used value by the difference
+    pub async fn set_local(&self, id: StoreId, value: u64) -> Result<()> {
+        inner::UsedSpace::set_local(self.inner.clone(), id, value).await
+    }
+
+    /// Writes the used space of each local store to its record on disk,
+    /// waiting for the changes in progress, e.g. before shutting down
+    pub async fn flush(&self) -> Result<()> {
+        inner::UsedSpace::flush(self.inner.clone()).await
+    }
 }
 
 mod inner {
@@ -97,6 +147,9 @@ mod inner {
     pub struct UsedSpace {
         /// the maximum value (inclusive) that `total_value` can attain
         max_capacity: u64,
+        /// the most `total_value` can attain before the disk is full,
+        /// as of the last free space measurement, if any
+        disk_limit: Option<u64>,
         /// Total space consumed across all `ChunkStore`s, including this one
         total_value: u64,
         /// the used space tracking for each chunk store
@@ -108,8 +161,12 @@ mod inner {
     /// An entry used to track the used space of a single `ChunkStore`
     #[derive(Debug)]
     struct LocalUsedSpace {
+        // Name of the `ChunkStore`'s directory.
+        pub name: String,
         // Space consumed by this one `ChunkStore`.
         pub local_value: u64,
+        // The most `local_value` may reach, if limited beyond `max_capacity`.
+        pub quota: Option<u64>,
         // File used to maintain on-disk record of `local_value`.
         // TODO: maybe a good idea to maintain a journal that is only flushed occasionally
         // to ensure stale entries aren't recorded, and to avoid holding the lock for the
@@ -121,6 +178,7 @@ mod inner {
         pub fn new(max_capacity: u64) -> Self {
             Self {
                 max_capacity,
+                disk_limit: None,
                 total_value: 0u64,
                 local_stores: HashMap::new(),
                 next_id: 0u64,
@@ -147,7 +205,37 @@ mod inner {
         /// value that total() can return)
         pub async fn max_capacity(used_space: Arc<Mutex<UsedSpace>>) -> u64 {
             let used_space_lock = used_space.lock().await;
-            used_space_lock.max_capacity
+            used_space_lock.effective_capacity()
+        }
+
+        /// Replaces the configured maximum capacity
+        pub async fn set_max_capacity(used_space: Arc<Mutex<UsedSpace>>, max_capacity: u64) {
+            let mut used_space_lock = used_space.lock().await;
+            trace!(
+                "Changing max capacity from {} to {} bytes",
+                used_space_lock.max_capacity,
+                max_capacity
+            );
+            used_space_lock.max_capacity = max_capacity;
+        }
+
+        /// Records that `available` more bytes fit on the disk
+        pub async fn set_free_space(used_space: Arc<Mutex<UsedSpace>>, available: u64) {
+            let mut used_space_lock = used_space.lock().await;
+            let disk_limit = used_space_lock.total_value.saturating_add(available);
+            trace!(
+                "{} bytes free on disk, limiting used space to {} bytes",
+                available,
+                disk_limit.min(used_space_lock.max_capacity)
+            );
+            used_space_lock.disk_limit = Some(disk_limit);
+        }
+
+        fn effective_capacity(&self) -> u64 {
+            match self.disk_limit {
+                Some(disk_limit) => disk_limit.min(self.max_capacity),
+                None => self.max_capacity,
+            }
         }
 
         /// Returns the total used space as a snapshot
@@ -171,12 +259,28 @@ mod inner {
                 .map_or(0, |res| res.local_value)
         }
 
+        /// Returns the used space of each local store as a snapshot
+        pub async fn usage(used_space: Arc<Mutex<UsedSpace>>) -> Vec<StoreUsage> {
+            let used_space_lock = used_space.lock().await;
+            let mut ids: Vec<_> = used_space_lock.local_stores.keys().copied().collect();
+            ids.sort_unstable();
+            ids.iter()
+                .filter_map(|id| used_space_lock.local_stores.get(id))
+                .map(|local_store| StoreUsage {
+                    name: local_store.name.clone(),
+                    used: local_store.local_value,
+                    quota: local_store.quota,
+                })
+                .collect()
+        }
+
         /// Adds a new record for tracking the actions
         /// of a local chunk store as part of the global
         /// used amount tracking
         pub async fn add_local_store<T: AsRef<Path>>(
             used_space: Arc<Mutex<UsedSpace>>,
             dir: T,
+            quota: Option<u64>,
         ) -> Result<StoreId> {
             let mut local_record = OpenOptions::new()
                 .read(true)
@@ -190,8 +294,15 @@ mod inner {
             let could_read = local_record.read_to_end(&mut buffer).await.is_ok();
             let has_value = !buffer.is_empty();
             let local_value = if could_read && has_value {
-                // TODO - if this can't be parsed, we should consider emptying `dir` of any chunks.
-                bincode::deserialize::<u64>(&buffer)?
+                // An unparsable record is repaired when the `ChunkStore` reconciles its used space.
+                bincode::deserialize::<u64>(&buffer).unwrap_or_else(|error| {
+                    warn!(
+                        "Could not parse used space record in {}: {}",
+                        dir.as_ref().display(),
+                        error
+                    );
+                    0
+                })
             } else {
                 let mut bytes = Vec::<u8>::new();
                 bincode::serialize_into(&mut bytes, &0_u64)?;
@@ -200,12 +311,19 @@ mod inner {
             };
 
             let local_store = LocalUsedSpace {
+                name: dir
+                    .as_ref()
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
                 local_value,
+                quota,
                 local_record,
             };
             let mut used_space_lock = used_space.lock().await;
             let id = used_space_lock.next_id;
             used_space_lock.next_id += 1;
+            used_space_lock.total_value = used_space_lock.total_value.saturating_add(local_value);
             let _ = used_space_lock.local_stores.insert(id, local_store);
             Ok(id)
         }
@@ -222,16 +340,22 @@ mod inner {
                 .total_value
                 .checked_add(consumed)
                 .ok_or(Error::NotEnoughSpace)?;
-            if new_total > used_space_lock.max_capacity {
+            if new_total > used_space_lock.effective_capacity() {
                 return Err(Error::NotEnoughSpace);
             }
-            let new_local = used_space_lock
+            let local_store = used_space_lock
                 .local_stores
                 .get(&id)
-                .ok_or(Error::NoStoreId)?
+                .ok_or(Error::NoStoreId)?;
+            let new_local = local_store
                 .local_value
                 .checked_add(consumed)
                 .ok_or(Error::NotEnoughSpace)?;
+            if let Some(quota) = local_store.quota {
+                if new_local > quota {
+                    return Err(Error::QuotaExceeded(local_store.name.clone()));
+                }
+            }
 
             {
                 let record = &mut used_space_lock
@@ -283,6 +407,39 @@ mod inner {
             Ok(())
         }
 
+        /// Asynchronous implementation to overwrite the used space of a local store,
+        /// adjusting the global value by the difference
+        pub async fn set_local(
+            used_space: Arc<Mutex<UsedSpace>>,
+            id: StoreId,
+            value: u64,
+        ) -> Result<()> {
+            let mut used_space_lock = used_space.lock().await;
+            let previous = {
+                let local_store = used_space_lock
+                    .local_stores
+                    .get_mut(&id)
+                    .ok_or(Error::NoStoreId)?;
+                Self::write_local_to_file(&mut local_store.local_record, value).await?;
+                std::mem::replace(&mut local_store.local_value, value)
+            };
+            used_space_lock.total_value = used_space_lock
+                .total_value
+                .saturating_sub(previous)
+                .saturating_add(value);
+            Ok(())
+        }
+
+        /// Asynchronous implementation to write the used space of all local stores to file
+        pub async fn flush(used_space: Arc<Mutex<UsedSpace>>) -> Result<()> {
+            let mut used_space_lock = used_space.lock().await;
+            for local_store in used_space_lock.local_stores.values_mut() {
+                let value = local_store.local_value;
+                Self::write_local_to_file(&mut local_store.local_record, value).await?;
+            }
+            Ok(())
+        }
+
         /// helper to write the contents of local to file
         /// NOTE: For now, ou should hold the lock on the inner while doing this
         /// It's slow, but maintains behaviour from the previous implementation
@@ -302,7 +459,7 @@ mod inner {
 
 #[cfg(test)]
 mod tests {
-    use super::{Error, Result, UsedSpace};
+    use super::{Error, Result, StoreUsage, UsedSpace};
     use tempdir::TempDir;
 
     const TEST_STORE_MAX_SIZE: u64 = u64::MAX;
@@ -329,7 +486,7 @@ mod tests {
         let root_dir = create_temp_root()?;
         let store_dir = create_temp_store(&root_dir)?;
         let used_space = UsedSpace::new(TEST_STORE_MAX_SIZE);
-        let id = used_space.add_local_store(&store_dir).await?;
+        let id = used_space.add_local_store(&store_dir, None).await?;
         // get a random vec of u64 by adding u32 (avoid overflow)
         let mut rng = rand::thread_rng();
         let bytes = crate::utils::random_vec(&mut rng, std::mem::size_of::<u32>() * NUMS_TO_ADD);
@@ -365,4 +522,118 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn used_space_quota_test() -> Result<()> {
+        let root_dir = create_temp_root()?;
+        let limited_dir = root_dir.path().join("limited");
+        let unlimited_dir = root_dir.path().join("unlimited");
+        std::fs::create_dir_all(&limited_dir)?;
+        std::fs::create_dir_all(&unlimited_dir)?;
+        let used_space = UsedSpace::new(100);
+        let limited = used_space.add_local_store(&limited_dir, Some(30)).await?;
+        let unlimited = used_space.add_local_store(&unlimited_dir, None).await?;
+
+        // the quota applies to its own store only
+        used_space.increase(limited, 30).await?;
+        match used_space.increase(limited, 1).await {
+            Err(Error::QuotaExceeded(name)) => assert_eq!(name, "limited"),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        used_space.increase(unlimited, 70).await?;
+
+        // while the global limit still applies to all of them
+        match used_space.increase(unlimited, 1).await {
+            Err(Error::NotEnoughSpace) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let usage = used_space.usage().await;
+        assert_eq!(
+            usage,
+            vec![
+                StoreUsage {
+                    name: "limited".to_string(),
+                    used: 30,
+                    quota: Some(30),
+                },
+                StoreUsage {
+                    name: "unlimited".to_string(),
+                    used: 70,
+                    quota: None,
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn used_space_free_space_test() -> Result<()> {
+        let root_dir = create_temp_root()?;
+        let store_dir = create_temp_store(&root_dir)?;
+        let used_space = UsedSpace::new(TEST_STORE_MAX_SIZE);
+        let id = used_space.add_local_store(&store_dir, None).await?;
+        used_space.increase(id, 10).await?;
+
+        // the disk can't hold the configured capacity
+        used_space.refresh_free_space(&store_dir).await?;
+        let capacity = used_space.max_capacity().await;
+        assert!(capacity < TEST_STORE_MAX_SIZE);
+        assert!(capacity >= 10);
+        match used_space.increase(id, capacity - 9).await {
+            Err(Error::NotEnoughSpace) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // but the configured capacity still applies if it's the smaller
+        let used_space = UsedSpace::new(100);
+        used_space.refresh_free_space(&store_dir).await?;
+        assert_eq!(used_space.max_capacity().await, 100);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn used_space_set_max_capacity_test() -> Result<()> {
+        let root_dir = create_temp_root()?;
+        let store_dir = create_temp_store(&root_dir)?;
+        let used_space = UsedSpace::new(100);
+        let id = used_space.add_local_store(&store_dir, None).await?;
+        used_space.increase(id, 80).await?;
+
+        // shrinking below the used space keeps it, but refuses more
+        used_space.set_max_capacity(50).await;
+        assert_eq!(used_space.max_capacity().await, 50);
+        assert_eq!(used_space.total().await, 80);
+        match used_space.increase(id, 1).await {
+            Err(Error::NotEnoughSpace) => (),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        // growing makes room again
+        used_space.set_max_capacity(200).await;
+        used_space.increase(id, 120).await?;
+        assert_eq!(used_space.total().await, 200);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn used_space_flush_test() -> Result<()> {
+        let root_dir = create_temp_root()?;
+        let store_dir = create_temp_store(&root_dir)?;
+        let used_space = UsedSpace::new(100);
+        let id = used_space.add_local_store(&store_dir, None).await?;
+        used_space.increase(id, 30).await?;
+        used_space.flush().await?;
+
+        // the flushed value is picked up on restarting
+        let restarted = UsedSpace::new(100);
+        let id = restarted.add_local_store(&store_dir, None).await?;
+        assert_eq!(restarted.local(id).await, 30);
+        assert_eq!(restarted.total().await, 30);
+
+        Ok(())
+    }
 }
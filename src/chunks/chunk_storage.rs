@@ -6,14 +6,16 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::replication::{content_address, Fallback, PendingReplications};
 use crate::{
-    chunk_store::{BlobChunkStore, UsedSpace},
+    chunk_store::{BlobChunkStore, ChunkStoreConfig, UsedSpace},
     error::convert_to_error_message,
-    node_ops::{NodeDuty, OutgoingMsg},
+    node_msg::NodeMsg,
+    node_ops::{NodeDuties, NodeDuty, OutgoingMsg, OutgoingNodeMsg},
     section_funds::elder_signing,
     Error, NodeInfo, Result,
 };
-use log::{error, info};
+use log::{error, info, warn};
 use sn_data_types::{Blob, BlobAddress};
 use sn_messaging::{
     client::{
@@ -27,6 +29,7 @@ use std::{
     env::current_dir,
     fmt::{self, Display, Formatter},
     path::Path,
+    time::Instant,
 };
 use xor_name::XorName;
 
@@ -34,6 +37,9 @@ use xor_name::XorName;
 pub(crate) struct ChunkStorage {
     node_name: XorName,
     chunks: BlobChunkStore,
+    replications: PendingReplications,
+    /// The chunks yet to be handed off, once decommissioning.
+    hand_offs: Option<BTreeSet<BlobAddress>>,
 }
 
 impl ChunkStorage {
@@ -41,36 +47,52 @@ impl ChunkStorage {
         node_name: XorName,
         path: &Path,
         used_space: UsedSpace,
+        store_config: &ChunkStoreConfig,
     ) -> Result<Self> {
-        let chunks = BlobChunkStore::new(path, used_space).await?;
-        Ok(Self { chunks, node_name })
+        let chunks = BlobChunkStore::new(path, used_space, store_config).await?;
+        Ok(Self {
+            chunks,
+            node_name,
+            replications: PendingReplications::new(),
+            hand_offs: None,
+        })
     }
 
+    /// Stores a chunk a client wrote, answering the elders that asked for it with whether it
+    /// is stored here, so that they can have another adult store it otherwise.
     pub(crate) async fn store(
         &mut self,
         data: &Blob,
         msg_id: MessageId,
         origin: EndUser,
     ) -> Result<NodeDuty> {
-        if let Err(error) = self.try_store(data, origin).await {
-            Ok(NodeDuty::Send(OutgoingMsg {
-                msg: Message::CmdError {
-                    error: CmdError::Data(convert_to_error_message(error)?),
-                    id: MessageId::in_response_to(&msg_id),
+        let address = *data.address();
+        let msg = match self.try_store(data, origin).await {
+            Ok(()) | Err(Error::DataExists) => NodeMsg::ChunkStored {
+                address,
+                correlation_id: msg_id,
+                id: MessageId::in_response_to(&msg_id),
+            },
+            Err(error) => {
+                info!("{}: Failed to store chunk {:?}: {}", self, address, error);
+                NodeMsg::ChunkWriteFailed {
+                    address,
+                    error: convert_to_error_message(error)
+                        .unwrap_or(ErrorMessage::FailedToWriteFile),
                     correlation_id: msg_id,
-                    target_section_pk: None,
-                },
-                section_source: false, // sent as single node
-                dst: DstLocation::EndUser(origin),
-                aggregation: Aggregation::None, // TODO: to_be_aggregated: Aggregation::AtDestination,
-            }))
-        } else {
-            Ok(NodeDuty::NoOp)
-        }
+                    id: MessageId::in_response_to(&msg_id),
+                }
+            }
+        };
+        Ok(report_to_section(&address, msg))
     }
 
     async fn try_store(&mut self, data: &Blob, origin: EndUser) -> Result<()> {
         info!("TRYING TO STORE BLOB");
+        if self.hand_offs.is_some() {
+            info!("{}: Decommissioning, not storing new chunks", self);
+            return Err(Error::NotEnoughSpace);
+        }
         if data.is_private() {
             let data_owner = data
                 .owner()
@@ -84,7 +106,7 @@ impl ChunkStorage {
             }
         }
 
-        if self.chunks.has(data.address()) {
+        if self.chunks.has(data.address()).await {
             info!(
                 "{}: Immutable chunk already exists, not storing: {:?}",
                 self,
@@ -95,49 +117,153 @@ impl ChunkStorage {
         self.chunks.put(&data).await
     }
 
+    /// Sends a chunk a client read to it, reporting to the elders that asked for it that it
+    /// was served, or that it couldn't be, so that they ask another holder.
     pub(crate) async fn get(
         &self,
         address: &BlobAddress,
         msg_id: MessageId,
         origin: EndUser,
-    ) -> Result<NodeDuty> {
-        let result = self
-            .chunks
-            .get(address)
-            .map_err(|_| ErrorMessage::NoSuchData);
-        Ok(NodeDuty::Send(OutgoingMsg {
-            msg: Message::QueryResponse {
-                id: MessageId::in_response_to(&msg_id),
-                response: QueryResponse::GetBlob(result),
-                correlation_id: msg_id,
-                target_section_pk: None,
-            },
-            section_source: false, // sent as single node
-            dst: DstLocation::EndUser(origin),
-            aggregation: Aggregation::None, // TODO: to_be_aggregated: Aggregation::AtDestination,
-        }))
+    ) -> Result<NodeDuties> {
+        let blob = match self.chunks.get(address).await {
+            Ok(blob) => blob,
+            Err(error) => {
+                info!("{}: Failed to read chunk {:?}: {}", self, address, error);
+                // Only a chunk not held has the elders replicate it, as other errors, e.g. I/O
+                // ones, may be transient.
+                let error = match error {
+                    Error::NoSuchChunk => ErrorMessage::NoSuchData,
+                    _ => ErrorMessage::InvalidOperation,
+                };
+                return Ok(vec![report_to_section(
+                    address,
+                    NodeMsg::ChunkReadFailed {
+                        address: *address,
+                        error,
+                        correlation_id: msg_id,
+                        id: MessageId::in_response_to(&msg_id),
+                    },
+                )]);
+            }
+        };
+        Ok(vec![
+            NodeDuty::Send(OutgoingMsg {
+                msg: Message::QueryResponse {
+                    id: MessageId::in_response_to(&msg_id),
+                    response: QueryResponse::GetBlob(Ok(blob)),
+                    correlation_id: msg_id,
+                    target_section_pk: None,
+                },
+                section_source: false, // sent as single node
+                dst: DstLocation::EndUser(origin),
+                aggregation: Aggregation::None, // TODO: to_be_aggregated: Aggregation::AtDestination,
+            }),
+            report_to_section(
+                address,
+                NodeMsg::ChunkServed {
+                    address: *address,
+                    correlation_id: msg_id,
+                    id: MessageId::new(),
+                },
+            ),
+        ])
     }
 
+    /// Starts fetching a chunk to replicate from one of its current holders.
     pub async fn replicate_chunk(
-        &self,
+        &mut self,
         address: BlobAddress,
         current_holders: BTreeSet<XorName>,
         msg_id: MessageId,
-    ) -> Result<NodeDuty> {
-        let msg = Message::NodeQuery {
-            query: NodeQuery::System(NodeSystemQuery::GetChunk {
+    ) -> Result<NodeDuties> {
+        if self.hand_offs.is_some() {
+            info!(
+                "{}: Decommissioning, not replicating chunk: {:?}",
+                self, address
+            );
+            return Ok(vec![]);
+        }
+        if self.chunks.has(&address).await {
+            info!(
+                "{}: Already holding chunk to replicate: {:?}",
+                self, address
+            );
+            return Ok(vec![NodeDuty::ReportReplicationCompleted {
                 address,
-                new_holder: self.node_name,
-                current_holders: BTreeSet::default(), //TODO: remove this in sn_messaging
-            }),
-            id: msg_id,
-            target_section_pk: None,
-        };
-        info!("Sending NodeSystemQuery::GetChunk to existing holders");
+                correlation_id: msg_id,
+            }]);
+        }
+        match self.replications.start(msg_id, address, current_holders) {
+            Some(holder) => Ok(vec![self.fetch_for_replication(address, holder, msg_id)]),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Asks the next holder for a chunk being replicated, after `holder` answered with an error.
+    pub async fn replication_fetch_failed(
+        &mut self,
+        msg_id: MessageId,
+        holder: XorName,
+    ) -> Result<NodeDuties> {
+        Ok(self
+            .replications
+            .fail(&msg_id, &holder)
+            .map(|fallback| self.fall_back(msg_id, fallback))
+            .into_iter()
+            .collect())
+    }
+
+    /// Asks the next holder for each chunk being replicated whose holder didn't send it in time.
+    pub async fn check_pending_replications(&mut self) -> Result<NodeDuties> {
+        let fallbacks = self.replications.timed_out(Instant::now());
+        Ok(fallbacks
+            .into_iter()
+            .map(|(msg_id, fallback)| self.fall_back(msg_id, fallback))
+            .collect())
+    }
+
+    fn fall_back(&self, msg_id: MessageId, fallback: Fallback) -> NodeDuty {
+        match fallback {
+            Fallback::Fetch { address, holder } => {
+                self.fetch_for_replication(address, holder, msg_id)
+            }
+            Fallback::GiveUp { address } => {
+                warn!(
+                    "{}: No holder sent chunk {:?} for replication",
+                    self, address
+                );
+                // Have the section choose another adult to replicate the chunk to.
+                report_to_section(
+                    &address,
+                    NodeMsg::ChunkLost {
+                        address,
+                        id: MessageId::new(),
+                    },
+                )
+            }
+        }
+    }
 
-        Ok(NodeDuty::SendToNodes {
-            msg,
-            targets: current_holders,
+    fn fetch_for_replication(
+        &self,
+        address: BlobAddress,
+        holder: XorName,
+        msg_id: MessageId,
+    ) -> NodeDuty {
+        info!("Sending NodeSystemQuery::GetChunk to holder {:?}", holder);
+        NodeDuty::Send(OutgoingMsg {
+            msg: Message::NodeQuery {
+                query: NodeQuery::System(NodeSystemQuery::GetChunk {
+                    address,
+                    new_holder: self.node_name,
+                    current_holders: BTreeSet::default(), //TODO: remove this in sn_messaging
+                }),
+                id: msg_id,
+                target_section_pk: None,
+            },
+            section_source: false, // sent as single node
+            dst: DstLocation::Node(holder),
+            aggregation: Aggregation::None,
         })
     }
 
@@ -148,43 +274,206 @@ impl ChunkStorage {
         msg_id: MessageId,
         new_holder: XorName,
     ) -> Result<NodeDuty> {
-        let result = match self.chunks.get(&address) {
+        let result = match self.chunks.get(&address).await {
             Ok(res) => Ok(res),
-            Err(error) => Err(convert_to_error_message(error)?),
+            Err(error) => {
+                log::warn!("Could not read chunk for replication: {:?}", error);
+                // Lets the new holder ask another holder right away.
+                Err(convert_to_error_message(error)?)
+            }
         };
 
-        if let Ok(data) = result {
-            Ok(NodeDuty::Send(OutgoingMsg {
-                msg: Message::NodeQueryResponse {
-                    response: NodeQueryResponse::Data(NodeDataQueryResponse::GetChunk(Ok(data))),
-                    id: MessageId::in_response_to(&msg_id),
-                    correlation_id: msg_id,
-                    target_section_pk: None,
-                },
-                section_source: false, // sent as single node
-                dst: DstLocation::Node(new_holder),
-                aggregation: Aggregation::None, // TODO: to_be_aggregated: Aggregation::AtDestination,
-            }))
-        } else {
-            log::warn!("Could not read chunk for replication: {:?}", result);
-            Ok(NodeDuty::NoOp)
-        }
+        Ok(NodeDuty::Send(OutgoingMsg {
+            msg: Message::NodeQueryResponse {
+                response: NodeQueryResponse::Data(NodeDataQueryResponse::GetChunk(result)),
+                id: MessageId::in_response_to(&msg_id),
+                correlation_id: msg_id,
+                target_section_pk: None,
+            },
+            section_source: false, // sent as single node
+            dst: DstLocation::Node(new_holder),
+            aggregation: Aggregation::None, // TODO: to_be_aggregated: Aggregation::AtDestination,
+        }))
     }
 
-    ///
-    pub async fn store_for_replication(&mut self, blob: Blob) -> Result<NodeDuty> {
-        if self.chunks.has(blob.address()) {
+    /// Stores a chunk `holder` sent for replication, once verified to be the one asked for.  If
+    /// it isn't, the holder is reported to the section and the next one is asked.  If it can't
+    /// be stored, the section is asked to replicate it elsewhere.
+    pub async fn store_for_replication(
+        &mut self,
+        blob: Blob,
+        correlation_id: MessageId,
+        holder: XorName,
+    ) -> Result<NodeDuties> {
+        let requested = match self.replications.address(&correlation_id) {
+            Some(address) => address,
+            // e.g. from a holder that answered after timing out; the section
+            // asked us to replicate the chunk under this id.
+            None if correlation_id == MessageId::combine(vec![*blob.name(), self.node_name]) => {
+                *blob.address()
+            }
+            None => {
+                warn!(
+                    "{}: Received chunk not asked for: {:?}",
+                    self,
+                    blob.address()
+                );
+                return Ok(vec![]);
+            }
+        };
+        let received = content_address(&blob);
+        if received != requested {
+            warn!(
+                "{}: Holder {:?} sent chunk {:?} when asked for {:?}",
+                self, holder, received, requested
+            );
+            let mut duties = vec![self.report_corrupt_copy(requested, holder)];
+            duties.extend(
+                self.replication_fetch_failed(correlation_id, holder)
+                    .await?,
+            );
+            return Ok(duties);
+        }
+        if self.chunks.has(blob.address()).await {
             info!(
                 "{}: Immutable chunk already exists, not storing: {:?}",
                 self,
                 blob.address()
             );
-            return Ok(NodeDuty::NoOp);
+        } else if let Err(error) = self.chunks.put(&blob).await {
+            warn!(
+                "{}: Could not store chunk {:?} for replication: {:?}",
+                self, requested, error
+            );
+            let _ = self.replications.complete(&correlation_id);
+            // Have the section choose another adult to replicate the chunk to.
+            return Ok(vec![report_to_section(
+                &requested,
+                NodeMsg::ChunkLost {
+                    address: requested,
+                    id: MessageId::new(),
+                },
+            )]);
         }
+        let _ = self.replications.complete(&correlation_id);
 
-        self.chunks.put(&blob).await?;
+        // i.e. the id of the cmd that had this node replicate the chunk
+        Ok(vec![NodeDuty::ReportReplicationCompleted {
+            address: requested,
+            correlation_id: MessageId::combine(vec![*requested.name(), self.node_name]),
+        }])
+    }
 
-        Ok(NodeDuty::NoOp)
+    /// Stops taking in new chunks, and asks the section to have each stored one replicated
+    /// elsewhere.  Stored chunks keep being served until the section confirms their hand-off.
+    pub async fn decommission(&mut self) -> NodeDuties {
+        if self.hand_offs.is_none() {
+            let stored = self.chunks.keys().await.into_iter().collect();
+            self.hand_offs = Some(stored);
+        }
+        self.hand_off_reports()
+    }
+
+    /// Asks the section again for the hand-off of the chunks it didn't confirm yet.
+    pub fn hand_off_reports(&self) -> NodeDuties {
+        let hand_offs = match &self.hand_offs {
+            Some(hand_offs) => hand_offs,
+            None => return vec![],
+        };
+        info!("{}: {} chunks left to hand off", self, hand_offs.len());
+        hand_offs
+            .iter()
+            .map(|address| {
+                report_to_section(
+                    address,
+                    NodeMsg::HandOffChunk {
+                        address: *address,
+                        id: MessageId::new(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Records the section confirming the hand-off of a chunk.
+    pub fn complete_hand_off(&mut self, address: &BlobAddress) {
+        if let Some(hand_offs) = &mut self.hand_offs {
+            if hand_offs.remove(address) {
+                info!("{}: Handed off chunk {:?}", self, address);
+            }
+        }
+    }
+
+    /// Whether decommissioning, with all chunks handed off.
+    pub fn handed_off(&self) -> bool {
+        matches!(&self.hand_offs, Some(hand_offs) if hand_offs.is_empty())
+    }
+
+    /// Reports to the section that `holder` sent a corrupt copy of a chunk.
+    fn report_corrupt_copy(&self, address: BlobAddress, holder: XorName) -> NodeDuty {
+        report_to_section(
+            &address,
+            NodeMsg::CorruptCopy {
+                address,
+                holder,
+                id: MessageId::new(),
+            },
+        )
+    }
+
+    /// Answers an elder's storage challenge with the hash of the nonce and the chunk's bytes,
+    /// which only a holder of the chunk can compute, or with `None` if it isn't held.
+    pub async fn answer_challenge(
+        &self,
+        address: BlobAddress,
+        nonce: [u8; 32],
+        correlation_id: MessageId,
+        elder: XorName,
+    ) -> NodeDuty {
+        let proof = match self.chunks.get(&address).await {
+            Ok(blob) => Some(XorName::from_content(&[&nonce, blob.value()])),
+            Err(error) => {
+                warn!(
+                    "{}: Could not read challenged chunk {:?}: {:?}",
+                    self, address, error
+                );
+                None
+            }
+        };
+        NodeDuty::SendNodeMsg(OutgoingNodeMsg {
+            msg: NodeMsg::ChallengeAnswer {
+                address,
+                proof,
+                correlation_id,
+                id: MessageId::in_response_to(&correlation_id),
+            },
+            section_source: false, // sent as single node
+            dst: DstLocation::Node(elder),
+            aggregation: Aggregation::None,
+        })
+    }
+
+    /// Verifies the next `count` stored chunks, and reports each corrupt one
+    /// to the section so that the lost copy is replicated again.
+    pub async fn scrub(&mut self, count: usize) -> Result<NodeDuties> {
+        let corrupt = self.chunks.scrub(count).await?;
+        Ok(corrupt
+            .into_iter()
+            .map(|address| {
+                warn!("{}: Quarantined corrupt chunk {:?}", self, address);
+                report_to_section(
+                    &address,
+                    NodeMsg::ChunkLost {
+                        address,
+                        id: MessageId::new(),
+                    },
+                )
+            })
+            .collect())
+    }
+
+    pub async fn reconcile_used_space(&mut self) -> Result<()> {
+        self.chunks.reconcile_used_space().await
     }
 
     pub async fn used_space_ratio(&self) -> f64 {
@@ -197,12 +486,12 @@ impl ChunkStorage {
         msg_id: MessageId,
         origin: EndUser,
     ) -> Result<NodeDuty> {
-        if !self.chunks.has(&address) {
+        if !self.chunks.has(&address).await {
             info!("{}: Immutable chunk doesn't exist: {:?}", self, address);
             return Ok(NodeDuty::NoOp);
         }
 
-        let result = match self.chunks.get(&address) {
+        let result = match self.chunks.get(&address).await {
             Ok(Blob::Private(data)) => {
                 if data.owner() == origin.id() {
                     self.chunks
@@ -240,6 +529,16 @@ impl ChunkStorage {
     }
 }
 
+// Sends a report on a chunk to the section responsible for it.
+fn report_to_section(address: &BlobAddress, msg: NodeMsg) -> NodeDuty {
+    NodeDuty::SendNodeMsg(OutgoingNodeMsg {
+        msg,
+        section_source: false, // sent as single node
+        dst: DstLocation::Section(*address.name()),
+        aggregation: Aggregation::None,
+    })
+}
+
 impl Display for ChunkStorage {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         write!(formatter, "ChunkStorage")
@@ -249,7 +548,7 @@ impl Display for ChunkStorage {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::chunk_store::UsedSpace;
+    use crate::chunk_store::{ChunkStoreConfig, UsedSpace};
     use crate::error::Error::InvalidOwners;
     use crate::error::Result;
     use bls::SecretKey;
@@ -271,14 +570,20 @@ mod tests {
     pub async fn try_store_stores_public_blob() -> Result<()> {
         let xor_name = XorName::random();
         let path = PathBuf::from(temp_dir()?.path());
-        let mut storage = ChunkStorage::new(xor_name, &path, UsedSpace::new(u64::MAX)).await?;
+        let mut storage = ChunkStorage::new(
+            xor_name,
+            &path,
+            UsedSpace::new(u64::MAX),
+            &ChunkStoreConfig::default(),
+        )
+        .await?;
         let value = "immutable data value".to_owned().into_bytes();
         let blob = Blob::Public(PublicBlob::new(value));
         assert!(storage
             .try_store(&blob, EndUser::AllClients(get_random_pk()))
             .await
             .is_ok());
-        assert!(storage.chunks.has(blob.address()));
+        assert!(storage.chunks.has(blob.address()).await);
 
         Ok(())
     }
@@ -287,7 +592,13 @@ mod tests {
     pub async fn try_store_stores_private_blob() -> Result<()> {
         let xor_name = XorName::random();
         let path = PathBuf::from(temp_dir()?.path());
-        let mut storage = ChunkStorage::new(xor_name, &path, UsedSpace::new(u64::MAX)).await?;
+        let mut storage = ChunkStorage::new(
+            xor_name,
+            &path,
+            UsedSpace::new(u64::MAX),
+            &ChunkStoreConfig::default(),
+        )
+        .await?;
         let value = "immutable data value".to_owned().into_bytes();
         let key = get_random_pk();
         let blob = Blob::Private(PrivateBlob::new(value, key));
@@ -295,7 +606,7 @@ mod tests {
             .try_store(&blob, EndUser::AllClients(key))
             .await
             .is_ok());
-        assert!(storage.chunks.has(blob.address()));
+        assert!(storage.chunks.has(blob.address()).await);
 
         Ok(())
     }
@@ -304,7 +615,13 @@ mod tests {
     pub async fn try_store_errors_if_end_user_doesnt_own_data() -> Result<()> {
         let xor_name = XorName::random();
         let path = PathBuf::from(temp_dir()?.path());
-        let mut storage = ChunkStorage::new(xor_name, &path, UsedSpace::new(u64::MAX)).await?;
+        let mut storage = ChunkStorage::new(
+            xor_name,
+            &path,
+            UsedSpace::new(u64::MAX),
+            &ChunkStoreConfig::default(),
+        )
+        .await?;
         let value = "immutable data value".to_owned().into_bytes();
         let data_owner = get_random_pk();
         let end_user = get_random_pk();
@@ -315,4 +632,338 @@ mod tests {
         assert!(matches!(result, Err(InvalidOwners(end_user))));
         Ok(())
     }
+
+    #[tokio::test]
+    pub async fn store_for_replication_rejects_other_chunk() -> Result<()> {
+        let xor_name = XorName::random();
+        let path = PathBuf::from(temp_dir()?.path());
+        let mut storage = ChunkStorage::new(
+            xor_name,
+            &path,
+            UsedSpace::new(u64::MAX),
+            &ChunkStoreConfig::default(),
+        )
+        .await?;
+        let blob = Blob::Public(PublicBlob::new(b"requested".to_vec()));
+        let other = Blob::Public(PublicBlob::new(b"other".to_vec()));
+        let msg_id = MessageId::combine(vec![*blob.name(), xor_name]);
+        let holders = (0..2).map(|_| XorName::random()).collect();
+
+        let asked = |duties: &[NodeDuty]| match duties {
+            [NodeDuty::Send(OutgoingMsg {
+                dst: DstLocation::Node(holder),
+                ..
+            })] => *holder,
+            duties => panic!("Unexpected duties: {:?}", duties),
+        };
+        let first = asked(
+            &storage
+                .replicate_chunk(*blob.address(), holders, msg_id)
+                .await?,
+        );
+
+        // the other chunk is reported, and the next holder asked
+        let duties = storage
+            .store_for_replication(other.clone(), msg_id, first)
+            .await?;
+        match duties.as_slice() {
+            [NodeDuty::SendNodeMsg(OutgoingNodeMsg {
+                msg:
+                    NodeMsg::CorruptCopy {
+                        address, holder, ..
+                    },
+                ..
+            }), NodeDuty::Send(OutgoingMsg {
+                dst: DstLocation::Node(second),
+                ..
+            })] => {
+                assert_eq!(address, blob.address());
+                assert_eq!(*holder, first);
+                assert_ne!(*second, first);
+            }
+            duties => panic!("Unexpected duties: {:?}", duties),
+        }
+        assert!(!storage.chunks.has(other.address()).await);
+
+        // the requested one is stored, and reported as replicated
+        let second = XorName::random();
+        assert!(matches!(
+            storage
+                .store_for_replication(blob.clone(), msg_id, second)
+                .await?
+                .as_slice(),
+            [NodeDuty::ReportReplicationCompleted { address, correlation_id }]
+                if address == blob.address()
+                    && *correlation_id == MessageId::combine(vec![*blob.name(), xor_name])
+        ));
+        assert!(storage.chunks.has(blob.address()).await);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn store_for_replication_reports_chunk_it_cannot_store() -> Result<()> {
+        let xor_name = XorName::random();
+        let path = PathBuf::from(temp_dir()?.path());
+        let mut storage = ChunkStorage::new(
+            xor_name,
+            &path,
+            UsedSpace::new(0),
+            &ChunkStoreConfig::default(),
+        )
+        .await?;
+        let blob = Blob::Public(PublicBlob::new(b"too big".to_vec()));
+        let msg_id = MessageId::combine(vec![*blob.name(), xor_name]);
+        let holder = XorName::random();
+        let _ = storage
+            .replicate_chunk(*blob.address(), std::iter::once(holder).collect(), msg_id)
+            .await?;
+
+        match storage
+            .store_for_replication(blob.clone(), msg_id, holder)
+            .await?
+            .as_slice()
+        {
+            [NodeDuty::SendNodeMsg(OutgoingNodeMsg {
+                msg: NodeMsg::ChunkLost { address, .. },
+                ..
+            })] => assert_eq!(address, blob.address()),
+            duties => panic!("Unexpected duties: {:?}", duties),
+        }
+        assert!(!storage.chunks.has(blob.address()).await);
+        // no longer pending, so not fetched again on timeout
+        assert!(storage.replications.address(&msg_id).is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn store_answers_the_section() -> Result<()> {
+        let path = PathBuf::from(temp_dir()?.path());
+        let mut storage = ChunkStorage::new(
+            XorName::random(),
+            &path,
+            UsedSpace::new(100),
+            &ChunkStoreConfig::default(),
+        )
+        .await?;
+        let owner = EndUser::AllClients(get_random_pk());
+        let blob = Blob::Public(PublicBlob::new(b"fits".to_vec()));
+        let msg_id = MessageId::new();
+
+        // stored chunks are acknowledged, also when already held
+        for _ in 0..2 {
+            match storage.store(&blob, msg_id, owner).await? {
+                NodeDuty::SendNodeMsg(OutgoingNodeMsg {
+                    msg:
+                        NodeMsg::ChunkStored {
+                            address,
+                            correlation_id,
+                            ..
+                        },
+                    dst: DstLocation::Section(name),
+                    ..
+                }) => {
+                    assert_eq!(address, *blob.address());
+                    assert_eq!(correlation_id, msg_id);
+                    assert_eq!(name, *blob.name());
+                }
+                duty => panic!("Unexpected duty: {:?}", duty),
+            }
+        }
+
+        let other = Blob::Public(PublicBlob::new(vec![0; 100]));
+        match storage.store(&other, msg_id, owner).await? {
+            NodeDuty::SendNodeMsg(OutgoingNodeMsg {
+                msg:
+                    NodeMsg::ChunkWriteFailed {
+                        error: ErrorMessage::NotEnoughSpace,
+                        correlation_id,
+                        ..
+                    },
+                dst: DstLocation::Section(name),
+                ..
+            }) => {
+                assert_eq!(correlation_id, msg_id);
+                assert_eq!(name, *other.name());
+            }
+            duty => panic!("Unexpected duty: {:?}", duty),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn get_reports_whether_chunk_was_served() -> Result<()> {
+        let path = PathBuf::from(temp_dir()?.path());
+        let mut storage = ChunkStorage::new(
+            XorName::random(),
+            &path,
+            UsedSpace::new(u64::MAX),
+            &ChunkStoreConfig::default(),
+        )
+        .await?;
+        let owner = EndUser::AllClients(get_random_pk());
+        let blob = Blob::Public(PublicBlob::new(b"served".to_vec()));
+        let msg_id = MessageId::new();
+
+        match storage.get(blob.address(), msg_id, owner).await?.as_slice() {
+            [NodeDuty::SendNodeMsg(OutgoingNodeMsg {
+                msg:
+                    NodeMsg::ChunkReadFailed {
+                        error: ErrorMessage::NoSuchData,
+                        correlation_id,
+                        ..
+                    },
+                dst: DstLocation::Section(name),
+                ..
+            })] => {
+                assert_eq!(*correlation_id, msg_id);
+                assert_eq!(name, blob.name());
+            }
+            duties => panic!("Unexpected duties: {:?}", duties),
+        }
+
+        storage.try_store(&blob, owner).await?;
+        match storage.get(blob.address(), msg_id, owner).await?.as_slice() {
+            [NodeDuty::Send(OutgoingMsg {
+                msg:
+                    Message::QueryResponse {
+                        response: QueryResponse::GetBlob(Ok(served)),
+                        ..
+                    },
+                dst: DstLocation::EndUser(_),
+                ..
+            }), NodeDuty::SendNodeMsg(OutgoingNodeMsg {
+                msg:
+                    NodeMsg::ChunkServed {
+                        address,
+                        correlation_id,
+                        ..
+                    },
+                ..
+            })] => {
+                assert_eq!(served, &blob);
+                assert_eq!(address, blob.address());
+                assert_eq!(*correlation_id, msg_id);
+            }
+            duties => panic!("Unexpected duties: {:?}", duties),
+        }
+
+        // a chunk that can't be read isn't reported as missing
+        for entry in std::fs::read_dir(path.join("chunks").join("immutable"))? {
+            let file = entry?.path();
+            if file.is_file() {
+                std::fs::write(file, [0; 4])?;
+            }
+        }
+        match storage.get(blob.address(), msg_id, owner).await?.as_slice() {
+            [NodeDuty::SendNodeMsg(OutgoingNodeMsg {
+                msg: NodeMsg::ChunkReadFailed { error, .. },
+                ..
+            })] => assert_eq!(*error, ErrorMessage::InvalidOperation),
+            duties => panic!("Unexpected duties: {:?}", duties),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn decommission_hands_off_stored_chunks() -> Result<()> {
+        let xor_name = XorName::random();
+        let path = PathBuf::from(temp_dir()?.path());
+        let mut storage = ChunkStorage::new(
+            xor_name,
+            &path,
+            UsedSpace::new(u64::MAX),
+            &ChunkStoreConfig::default(),
+        )
+        .await?;
+        let owner = EndUser::AllClients(get_random_pk());
+        let blob = Blob::Public(PublicBlob::new(b"stored".to_vec()));
+        storage.try_store(&blob, owner).await?;
+
+        let duties = storage.decommission().await;
+        match duties.as_slice() {
+            [NodeDuty::SendNodeMsg(OutgoingNodeMsg {
+                msg: NodeMsg::HandOffChunk { address, .. },
+                dst: DstLocation::Section(name),
+                ..
+            })] => {
+                assert_eq!(address, blob.address());
+                assert_eq!(name, blob.name());
+            }
+            duties => panic!("Unexpected duties: {:?}", duties),
+        }
+        assert!(!storage.handed_off());
+
+        // no new chunks are taken in, while stored ones are still served
+        let other = Blob::Public(PublicBlob::new(b"other".to_vec()));
+        assert!(matches!(
+            storage.try_store(&other, owner).await,
+            Err(Error::NotEnoughSpace)
+        ));
+        assert!(storage
+            .replicate_chunk(*other.address(), BTreeSet::new(), MessageId::new())
+            .await?
+            .is_empty());
+        assert!(storage.chunks.get(blob.address()).await.is_ok());
+
+        // unconfirmed hand-offs are asked for again
+        assert_eq!(storage.hand_off_reports().len(), 1);
+        storage.complete_hand_off(blob.address());
+        assert!(storage.handed_off());
+        assert!(storage.hand_off_reports().is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    pub async fn challenges_are_answered_with_a_proof_of_the_chunk() -> Result<()> {
+        let path = PathBuf::from(temp_dir()?.path());
+        let mut storage = ChunkStorage::new(
+            XorName::random(),
+            &path,
+            UsedSpace::new(u64::MAX),
+            &ChunkStoreConfig::default(),
+        )
+        .await?;
+        let owner = EndUser::AllClients(get_random_pk());
+        let blob = Blob::Public(PublicBlob::new(b"challenged".to_vec()));
+        storage.try_store(&blob, owner).await?;
+        let elder = XorName::random();
+
+        let proof_for = |duty: NodeDuty| match duty {
+            NodeDuty::SendNodeMsg(OutgoingNodeMsg {
+                msg: NodeMsg::ChallengeAnswer { proof, .. },
+                dst: DstLocation::Node(name),
+                ..
+            }) if name == elder => proof,
+            duty => panic!("Unexpected duty: {:?}", duty),
+        };
+        let (nonce, other_nonce) = ([1; 32], [2; 32]);
+        let proof = proof_for(
+            storage
+                .answer_challenge(*blob.address(), nonce, MessageId::new(), elder)
+                .await,
+        );
+        assert_eq!(proof, Some(XorName::from_content(&[&nonce, b"challenged"])));
+        let other_proof = proof_for(
+            storage
+                .answer_challenge(*blob.address(), other_nonce, MessageId::new(), elder)
+                .await,
+        );
+        assert!(other_proof.is_some());
+        assert_ne!(other_proof, proof);
+
+        let missing = Blob::Public(PublicBlob::new(b"missing".to_vec()));
+        let proof = proof_for(
+            storage
+                .answer_challenge(*missing.address(), nonce, MessageId::new(), elder)
+                .await,
+        );
+        assert_eq!(proof, None);
+
+        Ok(())
+    }
 }
@@ -8,10 +8,11 @@
 
 mod chunk_storage;
 mod reading;
+mod replication;
 mod writing;
 
 use crate::{
-    chunk_store::UsedSpace,
+    chunk_store::{ChunkStoreConfig, UsedSpace},
     node_ops::{NodeDuties, NodeDuty},
     NodeInfo, Result,
 };
@@ -32,15 +33,26 @@ use xor_name::XorName;
 /// At 50% full, the node will report that it's reaching full capacity.
 pub const MAX_STORAGE_USAGE_RATIO: f64 = 0.5;
 
+/// Once reported full, the node reports having storage available again only when under 40%
+/// full, so that its status doesn't flap around `MAX_STORAGE_USAGE_RATIO`.
+pub const STORAGE_AVAILABLE_RATIO: f64 = 0.4;
+
 /// Operations on data chunks.
 pub(crate) struct Chunks {
     chunk_storage: ChunkStorage,
+    reported_full: bool,
 }
 
 impl Chunks {
-    pub async fn new(node_name: XorName, path: &Path, used_space: UsedSpace) -> Result<Self> {
+    pub async fn new(
+        node_name: XorName,
+        path: &Path,
+        used_space: UsedSpace,
+        store_config: &ChunkStoreConfig,
+    ) -> Result<Self> {
         Ok(Self {
-            chunk_storage: ChunkStorage::new(node_name, path, used_space).await?,
+            chunk_storage: ChunkStorage::new(node_name, path, used_space, store_config).await?,
+            reported_full: false,
         })
     }
 
@@ -49,7 +61,7 @@ impl Chunks {
         read: &BlobRead,
         msg_id: MessageId,
         origin: EndUser,
-    ) -> Result<NodeDuty> {
+    ) -> Result<NodeDuties> {
         reading::get_result(read, msg_id, origin, &self.chunk_storage).await
     }
 
@@ -62,28 +74,57 @@ impl Chunks {
         writing::get_result(write, msg_id, origin, &mut self.chunk_storage).await
     }
 
-    pub async fn check_storage(&self) -> Result<NodeDuties> {
+    /// Reports when the used space nears the capacity, which is limited by the free space on disk
+    /// as well as by the configured max capacity, and when it's back well under that after having
+    /// been reported, e.g. after deletions or as the capacity was raised.
+    pub async fn check_storage(&mut self) -> Result<NodeDuties> {
         info!("Checking used storage");
-        if self.chunk_storage.used_space_ratio().await > MAX_STORAGE_USAGE_RATIO {
+        let used_space_ratio = self.chunk_storage.used_space_ratio().await;
+        if used_space_ratio > MAX_STORAGE_USAGE_RATIO {
+            self.reported_full = true;
             Ok(NodeDuties::from(NodeDuty::ReachingMaxCapacity))
+        } else if self.reported_full && used_space_ratio < STORAGE_AVAILABLE_RATIO {
+            self.reported_full = false;
+            Ok(NodeDuties::from(NodeDuty::ReachingFreeCapacity))
         } else {
             Ok(vec![])
         }
     }
 
+    /// Used space to capacity ratio.
+    pub async fn used_space_ratio(&self) -> f64 {
+        self.chunk_storage.used_space_ratio().await
+    }
+
     ///
     pub async fn replicate_chunk(
-        &self,
+        &mut self,
         address: BlobAddress,
         current_holders: BTreeSet<XorName>,
         msg_id: MessageId,
-    ) -> Result<NodeDuty> {
-        info!("Creating new Message for acquiring chunk from current_holders");
+    ) -> Result<NodeDuties> {
+        info!("Fetching chunk from one of current_holders for replication");
         self.chunk_storage
             .replicate_chunk(address, current_holders, msg_id)
             .await
     }
 
+    /// Falls back to the next holder of a chunk being replicated, as `holder` failed to send it.
+    pub async fn replication_fetch_failed(
+        &mut self,
+        msg_id: MessageId,
+        holder: XorName,
+    ) -> Result<NodeDuties> {
+        self.chunk_storage
+            .replication_fetch_failed(msg_id, holder)
+            .await
+    }
+
+    /// Falls back to the next holders of chunks being replicated that didn't arrive in time.
+    pub async fn check_pending_replications(&mut self) -> Result<NodeDuties> {
+        self.chunk_storage.check_pending_replications().await
+    }
+
     ///
     pub async fn get_chunk_for_replication(
         &self,
@@ -97,9 +138,60 @@ impl Chunks {
             .await
     }
 
-    ///
-    pub async fn store_replicated_chunk(&mut self, blob: Blob) -> Result<NodeDuty> {
-        self.chunk_storage.store_for_replication(blob).await
+    /// Answers an elder's challenge to prove the chunk is held.
+    pub async fn answer_challenge(
+        &self,
+        address: BlobAddress,
+        nonce: [u8; 32],
+        correlation_id: MessageId,
+        elder: XorName,
+    ) -> NodeDuty {
+        self.chunk_storage
+            .answer_challenge(address, nonce, correlation_id, elder)
+            .await
+    }
+
+    /// Verifies the next `count` stored chunks.
+    pub async fn scrub(&mut self, count: usize) -> Result<NodeDuties> {
+        self.chunk_storage.scrub(count).await
+    }
+
+    /// Recomputes the used space from the stored chunk files.
+    pub async fn reconcile_used_space(&mut self) -> Result<()> {
+        self.chunk_storage.reconcile_used_space().await
+    }
+
+    /// Stops taking in new chunks, and has the section replicate the stored ones elsewhere.
+    pub async fn decommission(&mut self) -> NodeDuties {
+        info!("Decommissioning, handing off stored chunks");
+        self.chunk_storage.decommission().await
+    }
+
+    /// Asks the section again for the hand-offs it didn't confirm yet, if decommissioning.
+    pub fn retry_hand_off(&self) -> NodeDuties {
+        self.chunk_storage.hand_off_reports()
+    }
+
+    /// Records the section confirming that a chunk has been replicated elsewhere.
+    pub fn complete_hand_off(&mut self, address: &BlobAddress) {
+        self.chunk_storage.complete_hand_off(address)
+    }
+
+    /// Whether decommissioning is done, all stored chunks having been handed off.
+    pub fn handed_off(&self) -> bool {
+        self.chunk_storage.handed_off()
+    }
+
+    /// Stores a chunk `holder` sent for replication, once verified.
+    pub async fn store_replicated_chunk(
+        &mut self,
+        blob: Blob,
+        correlation_id: MessageId,
+        holder: XorName,
+    ) -> Result<NodeDuties> {
+        self.chunk_storage
+            .store_for_replication(blob, correlation_id, holder)
+            .await
     }
 }
 
@@ -108,3 +200,58 @@ impl Display for Chunks {
         write!(formatter, "Chunks")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+    use bls::SecretKey;
+    use sn_data_types::{PublicBlob, PublicKey};
+    use tempdir::TempDir;
+
+    #[tokio::test]
+    async fn check_storage_reports_with_hysteresis() -> Result<()> {
+        let dir = TempDir::new("test").map_err(|e| Error::TempDirCreationFailed(e.to_string()))?;
+        let used_space = UsedSpace::new(u64::MAX);
+        let mut chunks = Chunks::new(
+            XorName::random(),
+            dir.path(),
+            used_space.clone(),
+            &ChunkStoreConfig::default(),
+        )
+        .await?;
+        let blob = Blob::Public(PublicBlob::new(vec![1; 1000]));
+        let owner = PublicKey::from(SecretKey::random().public_key());
+        let _ = chunks
+            .write(
+                &BlobWrite::New(blob),
+                MessageId::new(),
+                EndUser::AllClients(owner),
+            )
+            .await?;
+        let used = used_space.total().await;
+        assert!(used > 0);
+
+        // over the max usage ratio
+        used_space.set_max_capacity(used * 10 / 6).await;
+        assert!(matches!(
+            chunks.check_storage().await?.as_slice(),
+            [NodeDuty::ReachingMaxCapacity]
+        ));
+        // between the two ratios, still full
+        used_space.set_max_capacity(used * 100 / 45).await;
+        assert!(chunks.check_storage().await?.is_empty());
+        // under the available ratio, reported once
+        used_space.set_max_capacity(used * 3).await;
+        assert!(matches!(
+            chunks.check_storage().await?.as_slice(),
+            [NodeDuty::ReachingFreeCapacity]
+        ));
+        assert!(chunks.check_storage().await?.is_empty());
+        // between the two ratios again, not full
+        used_space.set_max_capacity(used * 100 / 45).await;
+        assert!(chunks.check_storage().await?.is_empty());
+
+        Ok(())
+    }
+}
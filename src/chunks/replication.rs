@@ -0,0 +1,222 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use rand::{seq::SliceRandom, thread_rng};
+use sn_data_types::{Blob, BlobAddress};
+use sn_messaging::MessageId;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{Duration, Instant},
+};
+use xor_name::XorName;
+
+/// How long a holder has to send a chunk being replicated, before the next one is asked.
+pub(crate) const REPLICATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What to do after a holder failed to send a chunk being replicated.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Fallback {
+    /// Ask the next holder for it.
+    Fetch {
+        address: BlobAddress,
+        holder: XorName,
+    },
+    /// No holder is left to ask.
+    GiveUp { address: BlobAddress },
+}
+
+struct PendingReplication {
+    address: BlobAddress,
+    /// The holder asked last.
+    holder: XorName,
+    /// The holders not asked yet.
+    remaining_holders: Vec<XorName>,
+    requested_at: Instant,
+}
+
+/// The chunks being fetched from their current holders for replication, keyed by the id of the
+/// cmd asking us to replicate them, which their holders answer to.  They are fetched from one
+/// holder at a time.
+pub(super) struct PendingReplications {
+    pending: BTreeMap<MessageId, PendingReplication>,
+}
+
+impl PendingReplications {
+    pub(super) fn new() -> Self {
+        Self {
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Starts fetching a chunk, returning the holder to ask first.  `None` if the chunk is being
+    /// fetched already, or there are no holders.
+    pub(super) fn start(
+        &mut self,
+        msg_id: MessageId,
+        address: BlobAddress,
+        holders: BTreeSet<XorName>,
+    ) -> Option<XorName> {
+        if self.pending.contains_key(&msg_id) {
+            return None;
+        }
+        // Spread the load of the replication over the holders.
+        let mut remaining_holders: Vec<_> = holders.into_iter().collect();
+        remaining_holders.shuffle(&mut thread_rng());
+        let holder = remaining_holders.pop()?;
+        let _ = self.pending.insert(
+            msg_id,
+            PendingReplication {
+                address,
+                holder,
+                remaining_holders,
+                requested_at: Instant::now(),
+            },
+        );
+        Some(holder)
+    }
+
+    /// The address of the chunk being fetched.
+    pub(super) fn address(&self, msg_id: &MessageId) -> Option<BlobAddress> {
+        self.pending
+            .get(msg_id)
+            .map(|replication| replication.address)
+    }
+
+    /// Records that the chunk arrived, returning whether it was being fetched.
+    pub(super) fn complete(&mut self, msg_id: &MessageId) -> bool {
+        self.pending.remove(msg_id).is_some()
+    }
+
+    /// Records that `holder` failed to send the chunk.  Only falls back if it's the holder asked
+    /// last, as earlier ones were given up on already.
+    pub(super) fn fail(&mut self, msg_id: &MessageId, holder: &XorName) -> Option<Fallback> {
+        if self.pending.get(msg_id)?.holder != *holder {
+            return None;
+        }
+        let replication = self.pending.remove(msg_id)?;
+        Some(self.fall_back(*msg_id, replication, Instant::now()))
+    }
+
+    /// Falls back for each chunk its holder didn't send in time, as of `now`.
+    pub(super) fn timed_out(&mut self, now: Instant) -> Vec<(MessageId, Fallback)> {
+        let timed_out: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, replication)| {
+                now.saturating_duration_since(replication.requested_at) >= REPLICATION_TIMEOUT
+            })
+            .map(|(msg_id, _)| *msg_id)
+            .collect();
+        let mut fallbacks = vec![];
+        for msg_id in timed_out {
+            if let Some(replication) = self.pending.remove(&msg_id) {
+                fallbacks.push((msg_id, self.fall_back(msg_id, replication, now)));
+            }
+        }
+        fallbacks
+    }
+
+    fn fall_back(
+        &mut self,
+        msg_id: MessageId,
+        mut replication: PendingReplication,
+        now: Instant,
+    ) -> Fallback {
+        let address = replication.address;
+        match replication.remaining_holders.pop() {
+            Some(holder) => {
+                replication.holder = holder;
+                replication.requested_at = now;
+                let _ = self.pending.insert(msg_id, replication);
+                Fallback::Fetch { address, holder }
+            }
+            None => Fallback::GiveUp { address },
+        }
+    }
+}
+
+/// The address the contents of a blob hash to, i.e. its value, and its owner if private.
+pub(super) fn content_address(blob: &Blob) -> BlobAddress {
+    match blob {
+        Blob::Public(data) => BlobAddress::Public(XorName::from_content(&[data.value()])),
+        Blob::Private(data) => BlobAddress::Private(XorName::from_content(&[
+            data.value(),
+            &data.owner().to_bytes(),
+        ])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bls::SecretKey;
+    use sn_data_types::{PrivateBlob, PublicBlob, PublicKey};
+
+    #[test]
+    fn falls_back_to_each_holder_once() {
+        let mut replications = PendingReplications::new();
+        let msg_id = MessageId::new();
+        let address = BlobAddress::Public(XorName::random());
+        let holders: BTreeSet<_> = (0..3).map(|_| XorName::random()).collect();
+
+        let first = replications
+            .start(msg_id, address, holders.clone())
+            .unwrap();
+        assert_eq!(replications.start(msg_id, address, holders.clone()), None);
+
+        // the first one fails, the second one times out
+        let second = match replications.fail(&msg_id, &first) {
+            Some(Fallback::Fetch { holder, .. }) => holder,
+            fallback => panic!("Unexpected fallback: {:?}", fallback),
+        };
+        assert!(replications.timed_out(Instant::now()).is_empty());
+        let third = match replications
+            .timed_out(Instant::now() + REPLICATION_TIMEOUT)
+            .as_slice()
+        {
+            [(_, Fallback::Fetch { holder, .. })] => *holder,
+            fallbacks => panic!("Unexpected fallbacks: {:?}", fallbacks),
+        };
+        let asked: BTreeSet<_> = vec![first, second, third].into_iter().collect();
+        assert_eq!(asked, holders);
+
+        // a late failure of an earlier holder changes nothing
+        assert_eq!(replications.fail(&msg_id, &second), None);
+
+        // then there are no holders left
+        assert_eq!(
+            replications.fail(&msg_id, &third),
+            Some(Fallback::GiveUp { address })
+        );
+        assert!(!replications.complete(&msg_id));
+    }
+
+    #[test]
+    fn content_address_matches_blob_address() {
+        let owner = PublicKey::from(SecretKey::random().public_key());
+        let public = Blob::Public(PublicBlob::new(vec![1, 2, 3]));
+        let private = Blob::Private(PrivateBlob::new(vec![1, 2, 3], owner));
+        assert_eq!(content_address(&public), *public.address());
+        assert_eq!(content_address(&private), *private.address());
+        assert_ne!(content_address(&public), *private.address());
+    }
+
+    #[test]
+    fn completes_once() {
+        let mut replications = PendingReplications::new();
+        let msg_id = MessageId::new();
+        let address = BlobAddress::Public(XorName::random());
+        let holders = std::iter::once(XorName::random()).collect();
+
+        let holder = replications.start(msg_id, address, holders).unwrap();
+        assert_eq!(replications.address(&msg_id), Some(address));
+        assert!(replications.complete(&msg_id));
+        assert!(!replications.complete(&msg_id));
+        assert_eq!(replications.fail(&msg_id, &holder), None);
+    }
+}
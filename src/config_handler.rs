@@ -13,8 +13,10 @@ use log::{debug, Level};
 use serde::{Deserialize, Serialize};
 use sn_routing::TransportConfig as NetworkConfig;
 use std::convert::Infallible;
+use std::fmt::{self, Display, Formatter};
 use std::net::AddrParseError;
 use std::num::ParseIntError;
+use std::str::FromStr;
 use std::{
     collections::{HashMap, HashSet},
     fs::{self, File},
@@ -28,6 +30,11 @@ const CONFIG_FILE: &str = "node.config";
 const CONNECTION_INFO_FILE: &str = "node_connection_info.config";
 const DEFAULT_ROOT_DIR_NAME: &str = "root_dir";
 const DEFAULT_MAX_CAPACITY: u64 = 2 * 1024 * 1024 * 1024;
+const DEFAULT_CHUNK_SHARD_DEPTH: u8 = 2;
+const MAX_CHUNK_SHARD_DEPTH: u8 = 4;
+const DEFAULT_CHUNK_SCRUB_RATE: u32 = 0;
+const DEFAULT_CHUNK_CACHE_SIZE: u64 = 0;
+const DEFAULT_BLOB_WRITE_QUORUM: usize = 3;
 
 /// Node configuration
 #[derive(Default, Clone, Debug, Serialize, Deserialize, Eq, PartialEq, StructOpt)]
@@ -39,8 +46,52 @@ pub struct Config {
     #[structopt(short, long, parse(try_from_str))]
     pub wallet_id: Option<String>,
     /// Upper limit in bytes for allowed network storage on this node.
+    /// Less is used if the disk holding the root directory doesn't have room for it.
+    /// A running node picks up a changed value from the config file on SIGHUP.
     #[structopt(short, long)]
     pub max_capacity: Option<u64>,
+    /// Where to keep chunks: "fs" for a file per chunk (the default), "memory" for keeping them
+    /// in memory only, losing them on restart, or "db" for an embedded key-value database.
+    #[structopt(long)]
+    pub chunk_store_backend: Option<ChunkStoreBackend>,
+    /// Number of levels of hex-prefix subdirectories to spread chunk files over (0 to 4).
+    /// Defaults to 2. Existing chunks are moved to the new layout on startup.
+    #[structopt(long)]
+    pub chunk_shard_depth: Option<u8>,
+    /// Number of stored chunks to verify per minute in the background. Corrupt chunks are
+    /// quarantined and re-replicated. Each chunk verified is read in full, so a high rate puts a
+    /// steady read load on the disk. Defaults to 0, i.e. no scrubbing.
+    #[structopt(long)]
+    pub chunk_scrub_rate: Option<u32>,
+    /// Bytes of recently read chunks each chunk store keeps in memory to serve repeated reads.
+    /// Defaults to 0, i.e. no caching.
+    #[structopt(long)]
+    pub chunk_cache_size: Option<u64>,
+    /// Upper limit in bytes for the space blobs may take up, within `--max-capacity`.
+    #[structopt(long)]
+    pub blob_quota: Option<u64>,
+    /// Upper limit in bytes for the space maps may take up, within `--max-capacity`.
+    #[structopt(long)]
+    pub map_quota: Option<u64>,
+    /// Upper limit in bytes for the space sequences may take up, within `--max-capacity`.
+    #[structopt(long)]
+    pub sequence_quota: Option<u64>,
+    /// Number of copies of a new blob that adults must have acknowledged storing before elders
+    /// have its write succeed. Defaults to 3; at most 4 copies are kept.
+    #[structopt(long)]
+    pub blob_write_quorum: Option<usize>,
+    /// Compress chunks before storing them. Chunks already stored are left as they are.
+    #[structopt(long)]
+    pub compress_chunks: bool,
+    /// Encrypt chunks and databases under the root directory with a node-local key, unlocked
+    /// with this passphrase. The same passphrase is needed on every later start. It is never
+    /// written to the config file.
+    #[structopt(long, env = "SN_NODE_ENCRYPTION_PASSPHRASE", hide_env_values = true)]
+    #[serde(skip)]
+    pub encryption_passphrase: Option<String>,
+    /// Like `--encryption-passphrase`, but unlocking the key with the contents of this file.
+    #[structopt(long, parse(from_os_str))]
+    pub encryption_key_file: Option<PathBuf>,
     /// Root directory for ChunkStores and cached state. If not set, it defaults to "root_dir"
     /// within the sn_node project data directory, located at:
     /// Linux: $HOME/.safe/node/root_dir
@@ -123,15 +174,72 @@ pub struct Config {
     pub network_config: NetworkConfig,
 }
 
+/// Where the chunks held by a node are kept.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkStoreBackend {
+    /// A file per chunk, under the root directory.
+    #[default]
+    Fs,
+    /// In memory only, so chunks are lost on restart.
+    Memory,
+    /// An embedded key-value database, under the root directory.
+    Db,
+}
+
+impl FromStr for ChunkStoreBackend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "fs" => Ok(Self::Fs),
+            "memory" => Ok(Self::Memory),
+            "db" => Ok(Self::Db),
+            other => Err(Error::Configuration(format!(
+                "Unknown chunk store backend: {}",
+                other
+            ))),
+        }
+    }
+}
+
+impl Display for ChunkStoreBackend {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Fs => write!(formatter, "fs"),
+            Self::Memory => write!(formatter, "memory"),
+            Self::Db => write!(formatter, "db"),
+        }
+    }
+}
+
 impl Config {
     /// Returns a new `Config` instance.  Tries to read from the default node config file location,
     /// and overrides values with any equivalent command line args.
     pub fn new() -> Result<Self, Error> {
-        let mut config = match Self::read_from_file() {
+        let file_config = match Self::read_from_file() {
             Ok(Some(config)) => config,
             Ok(None) | Err(_) => Default::default(),
         };
+        let config = Self::with_args(file_config)?;
 
+        config.clear_data_from_disk().unwrap_or_else(|_| {
+            log::error!("Error deleting data file from disk");
+        });
+
+        Ok(config)
+    }
+
+    /// Reads the config again, as `new` does, but leaves the node's data on disk alone.  Used by
+    /// a running node to pick up changed settings, so unlike `new` it returns the error if the
+    /// config file can't be read or parsed rather than falling back to the defaults.
+    pub fn reload() -> Result<Self, Error> {
+        let file_config = Self::read_from_file()?.unwrap_or_default();
+        Self::with_args(file_config)
+    }
+
+    // Overrides values of `config` with any equivalent command line args.
+    fn with_args(mut config: Self) -> Result<Self, Error> {
         let mut command_line_args = Config::from_args();
         command_line_args.validate()?;
 
@@ -141,10 +249,6 @@ impl Config {
 
         config.merge(command_line_args);
 
-        config.clear_data_from_disk().unwrap_or_else(|_| {
-            log::error!("Error deleting data file from disk");
-        });
-
         Ok(config)
     }
 
@@ -177,6 +281,46 @@ impl Config {
             self.max_capacity = Some(*max_capacity);
         }
 
+        if let Some(blob_quota) = config.blob_quota {
+            self.blob_quota = Some(blob_quota);
+        }
+
+        if let Some(map_quota) = config.map_quota {
+            self.map_quota = Some(map_quota);
+        }
+
+        if let Some(sequence_quota) = config.sequence_quota {
+            self.sequence_quota = Some(sequence_quota);
+        }
+
+        if let Some(chunk_store_backend) = config.chunk_store_backend {
+            self.chunk_store_backend = Some(chunk_store_backend);
+        }
+
+        if let Some(chunk_shard_depth) = config.chunk_shard_depth {
+            self.chunk_shard_depth = Some(chunk_shard_depth);
+        }
+
+        if let Some(chunk_scrub_rate) = config.chunk_scrub_rate {
+            self.chunk_scrub_rate = Some(chunk_scrub_rate);
+        }
+
+        if let Some(chunk_cache_size) = config.chunk_cache_size {
+            self.chunk_cache_size = Some(chunk_cache_size);
+        }
+
+        if let Some(blob_write_quorum) = config.blob_write_quorum {
+            self.blob_write_quorum = Some(blob_write_quorum);
+        }
+
+        if let Some(encryption_passphrase) = config.encryption_passphrase {
+            self.encryption_passphrase = Some(encryption_passphrase);
+        }
+
+        if let Some(encryption_key_file) = config.encryption_key_file {
+            self.encryption_key_file = Some(encryption_key_file);
+        }
+
         if let Some(root_dir) = &config.root_dir {
             self.root_dir = Some(root_dir.clone());
         }
@@ -196,6 +340,7 @@ impl Config {
         self.update = config.update || self.update;
         self.update_only = config.update_only || self.update_only;
         self.clear_data = config.clear_data || self.clear_data;
+        self.compress_chunks = config.compress_chunks || self.compress_chunks;
 
         if let Some(socket_addr) = config.first {
             self.first = Some(socket_addr);
@@ -256,6 +401,67 @@ impl Config {
         self.max_capacity.unwrap_or(DEFAULT_MAX_CAPACITY)
     }
 
+    /// Upper limit in bytes for the space blobs may take up, if any.
+    pub fn blob_quota(&self) -> Option<u64> {
+        self.blob_quota
+    }
+
+    /// Upper limit in bytes for the space maps may take up, if any.
+    pub fn map_quota(&self) -> Option<u64> {
+        self.map_quota
+    }
+
+    /// Upper limit in bytes for the space sequences may take up, if any.
+    pub fn sequence_quota(&self) -> Option<u64> {
+        self.sequence_quota
+    }
+
+    /// Where to keep chunks.
+    pub fn chunk_store_backend(&self) -> ChunkStoreBackend {
+        self.chunk_store_backend.unwrap_or_default()
+    }
+
+    /// Number of levels of subdirectories chunk files are spread over in each `ChunkStore`.
+    pub fn chunk_shard_depth(&self) -> u8 {
+        self.chunk_shard_depth
+            .unwrap_or(DEFAULT_CHUNK_SHARD_DEPTH)
+            .min(MAX_CHUNK_SHARD_DEPTH)
+    }
+
+    /// Number of stored chunks to verify per minute.
+    pub fn chunk_scrub_rate(&self) -> u32 {
+        self.chunk_scrub_rate.unwrap_or(DEFAULT_CHUNK_SCRUB_RATE)
+    }
+
+    /// Bytes of recently read chunks each chunk store keeps in memory.
+    pub fn chunk_cache_size(&self) -> u64 {
+        self.chunk_cache_size.unwrap_or(DEFAULT_CHUNK_CACHE_SIZE)
+    }
+
+    /// Number of acknowledged copies a blob write succeeds with.
+    pub fn blob_write_quorum(&self) -> usize {
+        self.blob_write_quorum.unwrap_or(DEFAULT_BLOB_WRITE_QUORUM)
+    }
+
+    /// Whether to compress chunks before storing them.
+    pub fn compress_chunks(&self) -> bool {
+        self.compress_chunks
+    }
+
+    /// The secret unlocking the key node-local data is encrypted with, if encryption is enabled:
+    /// the contents of the key file or the passphrase.
+    pub fn encryption_secret(&self) -> Result<Option<Vec<u8>>> {
+        match (&self.encryption_key_file, &self.encryption_passphrase) {
+            (Some(_), Some(_)) => Err(Error::Configuration(
+                "Only one of --encryption-passphrase and --encryption-key-file can be used"
+                    .to_string(),
+            )),
+            (Some(key_file), None) => Ok(Some(fs::read(key_file)?)),
+            (None, Some(passphrase)) => Ok(Some(passphrase.as_bytes().to_vec())),
+            (None, None) => Ok(None),
+        }
+    }
+
     /// Root directory for `ChunkStore`s and cached state. If not set, it defaults to
     /// `DEFAULT_ROOT_DIR_NAME` within the project's data directory (see `Config::root_dir` for the
     /// directories on each platform).
@@ -431,7 +637,7 @@ fn smoke() {
     // NOTE: IF this value is being changed due to a change in the config,
     // the change in config also be handled in Config::merge()
     // and in examples/config_handling.rs
-    let expected_size = 504;
+    let expected_size = 632;
 
     assert_eq!(std::mem::size_of::<Config>(), expected_size);
 }
@@ -0,0 +1,167 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Encryption of the data a node keeps on its own disk.
+//!
+//! Chunks and databases are sealed with a random node-local key.  That key is itself stored on
+//! disk, sealed with a key derived from a secret the operator unlocks the node with (see
+//! `state_db`).
+
+use crate::{Error, Result};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    pbkdf2,
+    rand::{SecureRandom, SystemRandom},
+};
+use std::{
+    fmt::{self, Debug, Formatter},
+    num::NonZeroU32,
+    sync::Arc,
+};
+
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const PBKDF2_ITERATIONS: u32 = 100_000;
+
+/// Symmetric key used to encrypt node-local data.
+#[derive(Clone)]
+pub(crate) struct EncryptionKey(Arc<[u8; KEY_LEN]>);
+
+impl EncryptionKey {
+    /// Generates a new random key.
+    pub fn generate() -> Result<Self> {
+        let mut key = [0; KEY_LEN];
+        random_fill(&mut key)?;
+        Ok(Self(Arc::new(key)))
+    }
+
+    /// Encrypts `plaintext`, authenticating `aad` along with it.  The random nonce used is
+    /// prepended to the returned ciphertext.
+    pub fn seal(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0; NONCE_LEN];
+        random_fill(&mut nonce)?;
+        let mut in_out = plaintext.to_vec();
+        self.aead_key()?
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(aad),
+                &mut in_out,
+            )
+            .map_err(|_| Error::Encryption("Failed to encrypt".to_string()))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(in_out);
+        Ok(sealed)
+    }
+
+    /// Decrypts the output of `seal` in place, failing if it, or `aad`, was tampered with or if it
+    /// was sealed with a different key.
+    pub fn open(&self, mut sealed: Vec<u8>, aad: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < NONCE_LEN {
+            return Err(Error::Encryption("Truncated ciphertext".to_string()));
+        }
+        let mut nonce = [0; NONCE_LEN];
+        nonce.copy_from_slice(&sealed[..NONCE_LEN]);
+        let len = self
+            .aead_key()?
+            .open_within(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(aad),
+                &mut sealed,
+                NONCE_LEN..,
+            )
+            .map_err(|_| Error::Encryption("Failed to decrypt".to_string()))?
+            .len();
+        sealed.truncate(len);
+        Ok(sealed)
+    }
+
+    /// Seals this key with one derived from `secret`, for storing it.
+    pub fn wrap(&self, secret: &[u8]) -> Result<Vec<u8>> {
+        let mut salt = [0; SALT_LEN];
+        random_fill(&mut salt)?;
+        let mut wrapped = salt.to_vec();
+        wrapped.extend(derive(secret, &salt).seal(&self.0[..], &[])?);
+        Ok(wrapped)
+    }
+
+    /// Recovers a key sealed by `wrap`, failing if `secret` isn't the one it was sealed with.
+    pub fn unwrap(wrapped: &[u8], secret: &[u8]) -> Result<Self> {
+        if wrapped.len() < SALT_LEN {
+            return Err(Error::Encryption("Truncated key".to_string()));
+        }
+        let (salt, sealed) = wrapped.split_at(SALT_LEN);
+        let opened = derive(secret, salt)
+            .open(sealed.to_vec(), &[])
+            .map_err(|_| {
+                Error::Encryption("Wrong passphrase or key file for the stored key".to_string())
+            })?;
+        if opened.len() != KEY_LEN {
+            return Err(Error::Encryption("Invalid key length".to_string()));
+        }
+        let mut key = [0; KEY_LEN];
+        key.copy_from_slice(&opened);
+        Ok(Self(Arc::new(key)))
+    }
+
+    fn aead_key(&self) -> Result<LessSafeKey> {
+        let key = UnboundKey::new(&CHACHA20_POLY1305, &self.0[..])
+            .map_err(|_| Error::Encryption("Invalid key".to_string()))?;
+        Ok(LessSafeKey::new(key))
+    }
+}
+
+impl Debug for EncryptionKey {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "EncryptionKey(..)")
+    }
+}
+
+/// Derives a key from a passphrase or key file, stretching it to slow down guessing.
+fn derive(secret: &[u8], salt: &[u8]) -> EncryptionKey {
+    let mut key = [0; KEY_LEN];
+    let iterations = NonZeroU32::new(PBKDF2_ITERATIONS).expect("Iteration count is non-zero");
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        salt,
+        secret,
+        &mut key,
+    );
+    EncryptionKey(Arc::new(key))
+}
+
+fn random_fill(bytes: &mut [u8]) -> Result<()> {
+    SystemRandom::new()
+        .fill(bytes)
+        .map_err(|_| Error::Encryption("Failed to generate random bytes".to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn seal_and_open() -> Result<()> {
+        let key = EncryptionKey::generate()?;
+        let sealed = key.seal(b"chunk", b"aad")?;
+        assert_eq!(key.open(sealed.clone(), b"aad")?, b"chunk");
+        assert!(key.open(sealed.clone(), b"other aad").is_err());
+        assert!(EncryptionKey::generate()?.open(sealed, b"aad").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn wrap_and_unwrap() -> Result<()> {
+        let key = EncryptionKey::generate()?;
+        let wrapped = key.wrap(b"passphrase")?;
+        let unwrapped = EncryptionKey::unwrap(&wrapped, b"passphrase")?;
+        assert_eq!(unwrapped.0, key.0);
+        assert!(EncryptionKey::unwrap(&wrapped, b"wrong").is_err());
+        Ok(())
+    }
+}
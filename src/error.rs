@@ -29,6 +29,10 @@ pub enum Error {
     /// Not enough space in `ChunkStore` to perform `put`.
     #[error("Not enough space")]
     NotEnoughSpace,
+    /// Not enough space left within the quota of the named `ChunkStore` to perform `put`, though
+    /// there may be in others.
+    #[error("Not enough space within the {0} store's quota")]
+    QuotaExceeded(String),
     /// Not Section PublicKey.
     #[error("Not section public key returned from routing")]
     NoSectionPublicKey,
@@ -68,6 +72,12 @@ pub enum Error {
     /// Creating temp directory failed.
     #[error("Could not create temp store: {0}")]
     TempDirCreationFailed(String),
+    /// Stored chunk is in a format this node can't read.
+    #[error("Invalid chunk format: {0}")]
+    ChunkFormat(String),
+    /// Node-local data could not be encrypted or decrypted.
+    #[error("Encryption error: {0}")]
+    Encryption(String),
     /// Chunk Store Id could not be found
     #[error("Could not fetch StoreId")]
     NoStoreId,
@@ -95,6 +105,9 @@ pub enum Error {
     /// PickleDb error.
     #[error("PickleDb error:: {0}")]
     PickleDb(#[from] pickledb::error::Error),
+    /// Sled error.
+    #[error("Sled error:: {0}")]
+    Sled(#[from] sled::Error),
     /// NetworkData error.
     #[error("Network data error:: {0}")]
     NetworkData(#[from] sn_data_types::Error),
@@ -143,7 +156,7 @@ pub(crate) fn convert_to_error_message(error: Error) -> Result<sn_messaging::cli
         Error::InvalidSignedTransfer(_) => Ok(ErrorMessage::InvalidSignature),
         Error::TransferAlreadyRegistered => Ok(ErrorMessage::TransactionIdExists),
         Error::NoSuchChunk => Ok(ErrorMessage::NoSuchData),
-        Error::NotEnoughSpace => Ok(ErrorMessage::NotEnoughSpace),
+        Error::NotEnoughSpace | Error::QuotaExceeded(_) => Ok(ErrorMessage::NotEnoughSpace),
         Error::BalanceExists => Ok(ErrorMessage::BalanceExists),
         Error::TempDirCreationFailed(_) => Ok(ErrorMessage::FailedToWriteFile),
         Error::DataExists => Ok(ErrorMessage::DataExists),
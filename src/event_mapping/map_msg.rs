@@ -10,9 +10,11 @@ use std::thread::current;
 
 use super::{LazyError, Mapping, MsgContext};
 use crate::{
+    node_msg::NodeMsg,
     node_ops::{NodeDuties, NodeDuty},
     Error, Result,
 };
+use bytes::Bytes;
 use log::debug;
 use sn_messaging::{
     client::{
@@ -22,6 +24,7 @@ use sn_messaging::{
     },
     DstLocation, EndUser, SrcLocation,
 };
+use xor_name::XorName;
 
 pub fn match_user_sent_msg(msg: Message, dst: DstLocation, origin: EndUser) -> Mapping {
     match msg.to_owned() {
@@ -161,6 +164,125 @@ pub fn map_node_msg(msg: Message, src: SrcLocation, dst: DstLocation) -> Mapping
     }
 }
 
+/// Maps the messages between nodes that sn_messaging has no variants for yet.
+pub fn map_own_node_msg(msg: NodeMsg, src: SrcLocation, content: Bytes) -> Mapping {
+    match match_own_node_msg(&msg, src) {
+        NodeDuty::NoOp => Mapping::Error(LazyError {
+            error: Error::InvalidMessage(msg.id(), format!("Unexpected node msg: {:?}", msg)),
+            msg: MsgContext::Bytes { msg: content, src },
+        }),
+        op => Mapping::Ok {
+            op,
+            ctx: Some(MsgContext::Bytes { msg: content, src }),
+        },
+    }
+}
+
+fn match_own_node_msg(msg: &NodeMsg, origin: SrcLocation) -> NodeDuty {
+    let sender = match origin {
+        SrcLocation::Node(name) => name,
+        // i.e. aggregated from a majority of the section's elders
+        SrcLocation::Section(_) => {
+            return match msg {
+                NodeMsg::HandOffCompleted { address, .. } => {
+                    NodeDuty::CompleteHandOff { address: *address }
+                }
+                _ => NodeDuty::NoOp,
+            }
+        }
+        _ => return NodeDuty::NoOp,
+    };
+    match msg {
+        // ------ system cmd ------
+        NodeMsg::StorageAvailable { node_id, .. } if XorName::from(*node_id) == sender => {
+            NodeDuty::DecrementFullNodeCount { node_id: *node_id }
+        }
+        NodeMsg::StorageReport {
+            node_id,
+            used_space_ratio,
+            ..
+        } if XorName::from(*node_id) == sender => NodeDuty::RecordUsedSpaceRatio {
+            node_id: *node_id,
+            used_space_ratio: *used_space_ratio,
+        },
+        // ------ storage challenges ------
+        NodeMsg::StorageChallenge { address, nonce, id } => NodeDuty::AnswerStorageChallenge {
+            address: *address,
+            nonce: *nonce,
+            elder: sender,
+            correlation_id: *id,
+        },
+        NodeMsg::ChallengeAnswer {
+            proof,
+            correlation_id,
+            ..
+        } => NodeDuty::ProcessStorageProof {
+            holder: sender,
+            proof: *proof,
+            correlation_id: *correlation_id,
+        },
+        // ------ chunk reads ------
+        NodeMsg::ChunkServed {
+            address,
+            correlation_id,
+            ..
+        } => NodeDuty::ProcessChunkServed {
+            address: *address,
+            holder: sender,
+            correlation_id: *correlation_id,
+        },
+        NodeMsg::ChunkReadFailed {
+            address,
+            error,
+            correlation_id,
+            ..
+        } => NodeDuty::ProcessChunkReadError {
+            address: *address,
+            holder: sender,
+            error: error.clone(),
+            correlation_id: *correlation_id,
+        },
+        // ------ chunk writes ------
+        NodeMsg::ChunkStored {
+            address,
+            correlation_id,
+            ..
+        } => NodeDuty::ProcessChunkStored {
+            address: *address,
+            holder: sender,
+            correlation_id: *correlation_id,
+        },
+        NodeMsg::ChunkWriteFailed {
+            address,
+            error,
+            correlation_id,
+            ..
+        } => NodeDuty::ProcessChunkWriteError {
+            address: *address,
+            holder: sender,
+            error: error.clone(),
+            correlation_id: *correlation_id,
+        },
+        // ------ chunk replication ------
+        NodeMsg::ChunkLost { address, .. } => NodeDuty::ProcessLostChunk {
+            address: *address,
+            holder: sender,
+        },
+        NodeMsg::CorruptCopy {
+            address, holder, ..
+        } => NodeDuty::ProcessCorruptCopy {
+            address: *address,
+            reporter: sender,
+            holder: *holder,
+        },
+        NodeMsg::HandOffChunk { address, .. } => NodeDuty::HandOffChunk {
+            address: *address,
+            holder: sender,
+        },
+        _ => NodeDuty::NoOp,
+    }
+}
+
 fn match_or_err(msg: Message, src: SrcLocation) -> Mapping {
     match match_section_msg(msg.clone(), src) {
         NodeDuty::NoOp => match match_node_msg(msg.clone(), src) {
@@ -325,7 +447,16 @@ fn match_node_msg(msg: Message, origin: SrcLocation) -> NodeDuty {
             cmd: NodeCmd::System(NodeSystemCmd::StorageFull { node_id, .. }),
             ..
         } => NodeDuty::IncrementFullNodeCount { node_id: *node_id },
-        // ------ chunk replication ------
+        Message::NodeEvent {
+            event: NodeEvent::ReplicationCompleted { chunk, .. },
+            ..
+        } => match origin {
+            SrcLocation::Node(node) => NodeDuty::ProcessReplicationCompleted {
+                address: *chunk,
+                node,
+            },
+            _ => NodeDuty::NoOp,
+        },
         // query response from adult cannot be accumulated
         Message::NodeQueryResponse {
             response: NodeQueryResponse::Data(NodeDataQueryResponse::GetChunk(result)),
@@ -333,14 +464,22 @@ fn match_node_msg(msg: Message, origin: SrcLocation) -> NodeDuty {
             ..
         } => {
             log::info!("Verifying GetChunk NodeQueryResponse!");
+            let holder = match origin {
+                SrcLocation::Node(holder) => holder,
+                _ => return NodeDuty::NoOp,
+            };
             if let Ok(data) = result {
                 NodeDuty::StoreChunkForReplication {
                     data: data.clone(),
                     correlation_id: *correlation_id,
+                    holder,
                 }
             } else {
                 log::warn!("Got error when reading chunk for replication: {:?}", result);
-                NodeDuty::NoOp
+                NodeDuty::ReplicationFetchFailed {
+                    correlation_id: *correlation_id,
+                    holder,
+                }
             }
         }
         //
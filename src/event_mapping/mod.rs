@@ -9,10 +9,10 @@
 mod map_msg;
 
 use super::node_ops::{NodeDuties, NodeDuty};
-use crate::{Network, Result};
+use crate::{node_msg::NodeMsg, Network, Result};
 use hex_fmt::HexFmt;
 use log::{debug, info, trace};
-use map_msg::{map_node_msg, match_user_sent_msg};
+use map_msg::{map_node_msg, map_own_node_msg, match_user_sent_msg};
 use sn_data_types::PublicKey;
 use sn_messaging::{client::Message, DstLocation, SrcLocation};
 use sn_routing::{Event as RoutingEvent, EventStream, NodeElderChange, MIN_AGE};
@@ -51,10 +51,13 @@ pub async fn map_routing_event(event: RoutingEvent, network_api: &Network) -> Ma
             let msg = match Message::from(content.clone()) {
                 Ok(msg) => msg,
                 Err(error) => {
+                    if let Ok(msg) = NodeMsg::from(content.clone()) {
+                        return map_own_node_msg(msg, src, content);
+                    }
                     return Mapping::Error(LazyError {
                         msg: MsgContext::Bytes { msg: content, src },
                         error: crate::Error::Message(error),
-                    })
+                    });
                 }
             };
 
@@ -35,11 +35,13 @@ mod chaos;
 mod chunk_store;
 mod chunks;
 mod config_handler;
+mod encryption;
 mod error;
 mod event_mapping;
 mod metadata;
 mod network;
 mod node;
+mod node_msg;
 mod section_funds;
 mod to_db_key;
 mod transfers;
@@ -53,7 +55,7 @@ pub mod utils;
 pub(crate) use to_db_key::ToDbKey;
 
 pub use crate::{
-    config_handler::{add_connection_info, set_connection_info, Config},
+    config_handler::{add_connection_info, set_connection_info, ChunkStoreBackend, Config},
     error::{Error, Result},
     network::Network,
     node::Node,
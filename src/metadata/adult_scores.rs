@@ -0,0 +1,139 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use log::{info, warn};
+use std::{collections::BTreeMap, time::Duration};
+use xor_name::XorName;
+
+/// Weight of the latest outcome in the moving averages of a score.
+const LATEST_OUTCOME_WEIGHT: f64 = 0.2;
+/// Number of outcomes recorded for an adult before it can be considered failing.
+const MIN_OUTCOMES: u64 = 5;
+/// Success rate under which an adult is considered failing.
+const FAILING_SUCCESS_RATE: f64 = 0.5;
+
+/// How an adult has been handling the chunk operations routed to it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdultScore {
+    pub successes: u64,
+    pub failures: u64,
+    /// Moving average of the outcomes, counting successes as 1 and failures as 0.
+    pub success_rate: f64,
+    /// Moving average of the latency of successful operations.
+    pub latency: Option<Duration>,
+}
+
+impl Default for AdultScore {
+    fn default() -> Self {
+        Self {
+            successes: 0,
+            failures: 0,
+            success_rate: 1.0,
+            latency: None,
+        }
+    }
+}
+
+impl AdultScore {
+    /// Whether the adult persistently fails operations.
+    pub fn is_failing(&self) -> bool {
+        self.successes + self.failures >= MIN_OUTCOMES && self.success_rate < FAILING_SUCCESS_RATE
+    }
+
+    fn record(&mut self, success: bool) {
+        let outcome = if success { 1.0 } else { 0.0 };
+        self.success_rate += LATEST_OUTCOME_WEIGHT * (outcome - self.success_rate);
+    }
+}
+
+/// Scores of the adults of our section, from the outcomes of the chunk operations routed to them.
+pub(super) struct AdultScores {
+    scores: BTreeMap<XorName, AdultScore>,
+}
+
+impl AdultScores {
+    pub(super) fn new() -> Self {
+        Self {
+            scores: BTreeMap::new(),
+        }
+    }
+
+    pub(super) fn record_success(&mut self, adult: XorName, latency: Duration) {
+        let score = self.scores.entry(adult).or_default();
+        let was_failing = score.is_failing();
+        score.successes += 1;
+        score.record(true);
+        score.latency = Some(match score.latency {
+            Some(average) => {
+                average.mul_f64(1.0 - LATEST_OUTCOME_WEIGHT)
+                    + latency.mul_f64(LATEST_OUTCOME_WEIGHT)
+            }
+            None => latency,
+        });
+        if was_failing && !score.is_failing() {
+            info!("Adult {:?} recovered: {:?}", adult, score);
+        }
+    }
+
+    pub(super) fn record_failure(&mut self, adult: XorName) {
+        let score = self.scores.entry(adult).or_default();
+        let was_failing = score.is_failing();
+        score.failures += 1;
+        score.record(false);
+        if !was_failing && score.is_failing() {
+            warn!("Adult {:?} is failing chunk operations: {:?}", adult, score);
+        }
+    }
+
+    pub(super) fn is_failing(&self, adult: &XorName) -> bool {
+        self.scores
+            .get(adult)
+            .map(AdultScore::is_failing)
+            .unwrap_or(false)
+    }
+
+    pub(super) fn scores(&self) -> &BTreeMap<XorName, AdultScore> {
+        &self.scores
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn persistently_failing_adults_are_flagged() {
+        let mut scores = AdultScores::new();
+        let adult = XorName::random();
+        let latency = Duration::from_millis(100);
+
+        // a failure now and then is fine
+        for _ in 0..4 {
+            scores.record_success(adult, latency);
+        }
+        scores.record_failure(adult);
+        assert!(!scores.is_failing(&adult));
+
+        for _ in 0..3 {
+            scores.record_failure(adult);
+        }
+        assert!(scores.is_failing(&adult));
+        assert!(!scores.is_failing(&XorName::random()));
+
+        for _ in 0..3 {
+            scores.record_success(adult, latency * 3);
+        }
+        assert!(!scores.is_failing(&adult));
+
+        let score = scores.scores()[&adult];
+        assert_eq!(score.successes, 7);
+        assert_eq!(score.failures, 4);
+        let average = score.latency.unwrap();
+        assert!(average > latency && average < latency * 3);
+    }
+}
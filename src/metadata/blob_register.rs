@@ -7,32 +7,49 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
-    capacity::ChunkHolderDbs,
+    capacity::{Capacity, ChunkHolderDbs, DbBatch, Table},
     error::convert_to_error_message,
-    node_ops::{NodeDuties, NodeDuty, OutgoingMsg},
+    node_msg::NodeMsg,
+    node_ops::{NodeDuties, NodeDuty, OutgoingMsg, OutgoingNodeMsg},
+    to_db_key::from_db_key,
     Error, Network, Result, ToDbKey,
 };
 use log::{info, trace, warn};
+use rand::{seq::IteratorRandom, thread_rng, Rng};
 use serde::{Deserialize, Serialize};
 use sn_data_types::{Blob, BlobAddress, Error as DtError, PublicKey, Result as NdResult};
 use sn_messaging::{
     client::{
         BlobRead, BlobWrite, CmdError, Error as ErrorMessage, Message, NodeCmd, NodeQuery,
-        NodeSystemCmd, QueryResponse,
+        NodeSystemCmd, NodeSystemQuery, QueryResponse,
     },
     Aggregation, DstLocation, EndUser, MessageId, SrcLocation,
 };
 
 use std::{
+    cmp::Ordering,
     collections::{BTreeMap, BTreeSet},
     fmt::{self, Display, Formatter},
 };
 use xor_name::XorName;
 
-use super::adult_reader::AdultReader;
+use super::{
+    adult_reader::AdultReader,
+    adult_scores::{AdultScore, AdultScores},
+    hand_offs::HandOffs,
+    pending_reads::{PendingReads, ReadFallback},
+    pending_writes::{Ack, PendingWrites, WriteOutcome},
+    storage_challenges::{StorageChallenges, Verdict},
+};
 
 // The number of separate copies of a blob chunk which should be maintained.
 const CHUNK_COPY_COUNT: usize = 4;
+// The number of closest adults to a chunk that its holders are chosen from.
+const HOLDER_CANDIDATE_COUNT: usize = 2 * CHUNK_COPY_COUNT;
+// The number of chunks whose holders an audit round checks.
+const AUDIT_BATCH_SIZE: usize = 500;
+// The most chunks an audit round has replicated, so that catching up doesn't flood the adults.
+const AUDIT_REPLICATION_LIMIT: usize = 50;
 
 #[derive(Default, Debug, Serialize, Deserialize)]
 struct ChunkMetadata {
@@ -48,12 +65,38 @@ struct HolderMetadata {
 /// Operations over the data type Blob.
 pub(super) struct BlobRegister {
     dbs: ChunkHolderDbs,
+    capacity: Capacity,
     reader: AdultReader,
+    challenges: StorageChallenges,
+    scores: AdultScores,
+    hand_offs: HandOffs,
+    reads: PendingReads,
+    writes: PendingWrites,
+    // The number of copies of a new chunk to be stored before its write succeeds.
+    write_quorum: usize,
+    // The metadata key of the last chunk audited, the next audit round starting after it.
+    audit_cursor: Option<String>,
 }
 
 impl BlobRegister {
-    pub(super) fn new(dbs: ChunkHolderDbs, reader: AdultReader) -> Self {
-        Self { dbs, reader }
+    pub(super) fn new(
+        dbs: ChunkHolderDbs,
+        capacity: Capacity,
+        reader: AdultReader,
+        write_quorum: usize,
+    ) -> Self {
+        Self {
+            dbs,
+            capacity,
+            reader,
+            challenges: StorageChallenges::new(),
+            scores: AdultScores::new(),
+            hand_offs: HandOffs::new(),
+            reads: PendingReads::new(),
+            writes: PendingWrites::new(),
+            write_quorum: write_quorum.clamp(1, CHUNK_COPY_COUNT),
+            audit_cursor: None,
+        }
     }
 
     pub(super) async fn write(
@@ -72,51 +115,64 @@ impl BlobRegister {
     async fn store(&mut self, data: Blob, msg_id: MessageId, origin: EndUser) -> Result<NodeDuty> {
         // If the data already exist, check the existing no of copies.
         // If no of copies are less then required, then continue with the put request.
-        let target_holders = if let Ok(metadata) = self.get_metadata_for(*data.address()).await {
-            if metadata.holders.len() < CHUNK_COPY_COUNT {
-                self.get_new_holders_for_chunk(data.address()).await
-            } else if data.is_public() {
-                trace!("{}: All good, {:?}, chunk already exists.", self, data);
-                return Ok(NodeDuty::NoOp);
+        let (existing_copies, target_holders) =
+            if let Ok(metadata) = self.get_metadata_for(*data.address()).await {
+                if metadata.holders.len() < CHUNK_COPY_COUNT {
+                    let new_holders = self
+                        .get_new_holders_for_chunk(data.address(), &BTreeSet::new())
+                        .await;
+                    (metadata.holders.len(), new_holders)
+                } else if data.is_public() {
+                    trace!("{}: All good, {:?}, chunk already exists.", self, data);
+                    return Ok(NodeDuty::NoOp);
+                } else {
+                    return Ok(NodeDuty::Send(OutgoingMsg {
+                        msg: Message::CmdError {
+                            error: CmdError::Data(ErrorMessage::DataExists),
+                            id: MessageId::in_response_to(&msg_id),
+                            correlation_id: msg_id,
+                            target_section_pk: None,
+                        },
+                        section_source: false, // strictly this is not correct, but we don't expect responses to an error..
+                        dst: DstLocation::EndUser(origin),
+                        aggregation: Aggregation::AtDestination,
+                    }));
+                }
             } else {
-                return Ok(NodeDuty::Send(OutgoingMsg {
-                    msg: Message::CmdError {
-                        error: CmdError::Data(ErrorMessage::DataExists),
-                        id: MessageId::in_response_to(&msg_id),
-                        correlation_id: msg_id,
-                        target_section_pk: None,
-                    },
-                    section_source: false, // strictly this is not correct, but we don't expect responses to an error..
-                    dst: DstLocation::EndUser(origin),
-                    aggregation: Aggregation::AtDestination,
-                }));
-            }
-        } else {
-            self.get_holders_for_chunk(data.name())
-                .await
-                .iter()
-                .cloned()
-                .collect::<BTreeSet<_>>()
-        };
+                let holders = self.get_holders_for_chunk(data.name()).await;
+                (0, holders.into_iter().collect::<BTreeSet<_>>())
+            };
 
         info!("Storing {} copies of the data", target_holders.len());
 
-        for holder in &target_holders {
-            // TODO: This error needs to be handled in some way.
-            if let Err(e) = self
-                .set_chunk_holder(*data.address(), *holder, origin)
-                .await
-            {
-                warn!(
-                    "Error ({:?}) setting chunk holder ({:?}) of {:?}, sent by origin: {:?}",
-                    e,
-                    *holder,
-                    *data.address(),
-                    origin
-                )
-            }
+        if target_holders.is_empty() {
+            warn!(
+                "{}: No adults with space available to store {:?}",
+                self,
+                data.address()
+            );
+            return self
+                .send_blob_cmd_error(Error::NotEnoughSpace, msg_id, origin)
+                .await;
+        }
+        if target_holders.len() < CHUNK_COPY_COUNT {
+            warn!(
+                "{}: Only {} adults with space available to store {:?}",
+                self,
+                target_holders.len(),
+                data.address()
+            );
         }
 
+        // Holders are recorded as they acknowledge storing it.
+        let quorum = self
+            .write_quorum
+            .saturating_sub(existing_copies)
+            .max(1)
+            .min(target_holders.len());
+        self.writes
+            .start(msg_id, data.clone(), origin, quorum, &target_holders);
+
         Ok(NodeDuty::SendToNodes {
             targets: target_holders,
             msg: Message::NodeCmd {
@@ -201,13 +257,9 @@ impl BlobRegister {
         holder: XorName,
         origin: EndUser,
     ) -> Result<()> {
-        // TODO -
-        // - if Err, we need to flag this sender as "full" (i.e. add to self.full_adults, try on
-        //   next closest non-full adult, or elder if none.  Also update the metadata for this
-        //   chunk.  Not known yet where we'll get the chunk from to do that.
+        // Only called once the holder acknowledged storing the chunk, see `chunk_stored`.
         info!("Setting chunk holder");
 
-        let db_key = blob_address.to_db_key()?;
         let mut metadata = self
             .get_metadata_for(blob_address)
             .await
@@ -215,27 +267,18 @@ impl BlobRegister {
         if blob_address.is_private() {
             metadata.owner = Some(*origin.id());
         }
-
         let _ = metadata.holders.insert(holder);
 
-        if let Err(error) = self.dbs.metadata.lock().await.set(&db_key, &metadata) {
-            warn!("{}: Failed to write metadata to DB: {:?}", self, error);
-            return Err(error.into());
-        }
-
         // We're acting as data handler, received request from client handlers
         let mut holders_metadata = self.get_holder(holder).await.unwrap_or_default();
         let _ = holders_metadata.chunks.insert(blob_address);
 
-        if let Err(error) = self
-            .dbs
-            .holders
-            .lock()
-            .await
-            .set(&holder.to_db_key()?, &holders_metadata)
-        {
+        let mut batch = DbBatch::new();
+        batch.set(Table::Metadata, &blob_address.to_db_key()?, &metadata)?;
+        batch.set(Table::Holders, &holder.to_db_key()?, &holders_metadata)?;
+        if let Err(error) = self.dbs.commit(batch).await {
             warn!("{}: Failed to write metadata to DB: {:?}", self, error);
-            return Err(error.into());
+            return Err(error);
         }
         Ok(())
     }
@@ -245,79 +288,487 @@ impl BlobRegister {
         blob_address: BlobAddress,
         holder_name: XorName,
     ) -> Result<()> {
-        let db_key = blob_address.to_db_key()?;
-        let metadata = self.get_metadata_for(blob_address).await;
-        if let Ok(mut metadata) = metadata {
-            let holder = self.get_holder(holder_name).await;
-
-            // Remove the chunk from the holder metadata
-            if let Ok(mut holder) = holder {
-                let _ = holder.chunks.remove(&blob_address);
-                if holder.chunks.is_empty() {
-                    if let Err(error) = self.dbs.holders.lock().await.rem(&holder_name.to_db_key()?)
-                    {
-                        warn!(
-                            "{}: Failed to delete holder metadata from DB: {:?}",
-                            self, error
-                        );
-                    }
-                } else if let Err(error) = self
-                    .dbs
-                    .holders
-                    .lock()
-                    .await
-                    .set(&holder_name.to_db_key()?, &holder)
-                {
-                    warn!(
-                        "{}: Failed to write holder metadata to DB: {:?}",
-                        self, error
-                    );
-                }
-            }
+        let mut metadata = match self.get_metadata_for(blob_address).await {
+            Ok(metadata) => metadata,
+            Err(_) => return Ok(()),
+        };
+        let mut batch = DbBatch::new();
 
-            // Remove the holder from the chunk metadata
-            let _ = metadata.holders.remove(&holder_name);
-            if metadata.holders.is_empty() {
-                if let Err(error) = self.dbs.metadata.lock().await.rem(&db_key) {
-                    warn!(
-                        "{}: Failed to delete chunk metadata from DB: {:?}",
-                        self, error
-                    );
-                }
-            } else if let Err(error) = self.dbs.metadata.lock().await.set(&db_key, &metadata) {
-                warn!(
-                    "{}: Failed to write chunk metadata to DB: {:?}",
-                    self, error
-                );
+        // Remove the chunk from the holder metadata
+        if let Ok(mut holder) = self.get_holder(holder_name).await {
+            let _ = holder.chunks.remove(&blob_address);
+            if holder.chunks.is_empty() {
+                batch.remove(Table::Holders, &holder_name.to_db_key()?);
+            } else {
+                batch.set(Table::Holders, &holder_name.to_db_key()?, &holder)?;
             }
         }
+
+        // Remove the holder from the chunk metadata
+        let db_key = blob_address.to_db_key()?;
+        let _ = metadata.holders.remove(&holder_name);
+        if metadata.holders.is_empty() {
+            batch.remove(Table::Metadata, &db_key);
+        } else {
+            batch.set(Table::Metadata, &db_key, &metadata)?;
+        }
+
+        if let Err(error) = self.dbs.commit(batch).await {
+            warn!(
+                "{}: Failed to write chunk metadata to DB: {:?}",
+                self, error
+            );
+        }
         Ok(())
     }
 
     pub(super) async fn replicate_chunks(&mut self, holder: XorName) -> Result<NodeDuties> {
         info!("Replicating chunks");
+        self.hand_offs.forget(&holder);
         let chunks_stored = match self.remove_holder(holder).await {
             Ok(chunks) => chunks,
             _ => return Ok(vec![]),
         };
         let mut cmds = Vec::new();
         for (address, holders) in chunks_stored {
-            cmds.extend(self.get_replication_msgs(address, holders).await?);
+            cmds.extend(
+                self.get_replication_msgs(address, holders, &BTreeSet::new())
+                    .await?,
+            );
         }
         Ok(cmds)
     }
 
+    // Checks the holders of the next batch of chunks against our current adults, dropping the
+    // ones that left, and has the chunks short of copies replicated, up to a limit per round.
+    // Catches the chunks that lost copies without us replicating them, e.g. in a churn event
+    // missed while restarting.  Chunks being written or handed off are left to those.
+    pub(super) async fn audit_replication(&mut self) -> Result<NodeDuties> {
+        let adults: BTreeSet<_> = self.reader.our_adults().await.into_iter().collect();
+        if adults.is_empty() {
+            return Ok(vec![]);
+        }
+        let keys = self.dbs.keys(
+            Table::Metadata,
+            self.audit_cursor.as_deref(),
+            AUDIT_BATCH_SIZE,
+        );
+        let batch_size = keys.len();
+        let mut duties = vec![];
+        let (mut audited, mut replicated) = (0, 0);
+        for key in keys {
+            if replicated == AUDIT_REPLICATION_LIMIT {
+                break;
+            }
+            audited += 1;
+            self.audit_cursor = Some(key.clone());
+            let address: BlobAddress = match from_db_key(&key) {
+                Ok(address) => address,
+                Err(error) => {
+                    warn!("{}: Invalid chunk metadata key {}: {}", self, key, error);
+                    continue;
+                }
+            };
+            if self.writes.is_writing(&address) || !self.hand_offs.departing(&address).is_empty() {
+                continue;
+            }
+            let holders = match self.get_metadata_for(address).await {
+                Ok(metadata) => metadata.holders,
+                Err(_) => continue,
+            };
+            let (current, departed): (BTreeSet<_>, BTreeSet<_>) = holders
+                .into_iter()
+                .partition(|holder| adults.contains(holder));
+            if current.is_empty() {
+                warn!("{}: No remaining copies of chunk {:?}", self, address);
+                continue;
+            }
+            for holder in departed {
+                self.remove_chunk_holder(address, holder).await?;
+            }
+            if current.len() < CHUNK_COPY_COUNT {
+                duties.extend(
+                    self.get_replication_msgs(address, current, &BTreeSet::new())
+                        .await?,
+                );
+                replicated += 1;
+            }
+        }
+        if audited == batch_size && batch_size < AUDIT_BATCH_SIZE {
+            // the last chunk was audited, so the next round starts over
+            self.audit_cursor = None;
+        }
+        info!(
+            "{}: Audited {} chunks, {} of them short of copies",
+            self, audited, replicated
+        );
+        Ok(duties)
+    }
+
+    pub(super) async fn replicate_lost_chunk(
+        &mut self,
+        address: BlobAddress,
+        holder: XorName,
+    ) -> Result<NodeDuties> {
+        info!("Replicating chunk {:?} lost by {:?}", address, holder);
+        let mut holders = match self.get_metadata_for(address).await {
+            Ok(metadata) => metadata.holders,
+            Err(_) => return Ok(vec![]),
+        };
+        if !holders.remove(&holder) {
+            return Ok(vec![]);
+        }
+        self.scores.record_failure(holder);
+        if holders.is_empty() {
+            warn!("{}: No remaining copies of chunk {:?}", self, address);
+            return Ok(vec![]);
+        }
+        self.remove_chunk_holder(address, holder).await?;
+        // The holder might have been asked to replicate it and failed, so another is chosen.
+        let excluded = std::iter::once(holder).collect();
+        self.get_replication_msgs(address, holders, &excluded).await
+    }
+
+    // Whether `msg_id` is the id of a pending write of the chunk.
+    fn is_pending_write(&self, address: &BlobAddress, msg_id: &MessageId) -> bool {
+        matches!(self.writes.get(msg_id), Some(write) if write.data.address() == address)
+    }
+
+    // Called when an adult acknowledges storing a chunk a client wrote: it is recorded as holder,
+    // also when acknowledging late, and the client answered once enough copies are stored.
+    pub(super) async fn chunk_stored(
+        &mut self,
+        address: BlobAddress,
+        msg_id: MessageId,
+        holder: XorName,
+    ) -> Result<NodeDuties> {
+        if !self.is_pending_write(&address, &msg_id) {
+            return Ok(vec![]);
+        }
+        match self.writes.stored(&msg_id, holder) {
+            Some(Ack::InTime(latency)) => self.scores.record_success(holder, latency),
+            // already scored as failing to answer in time, but still a copy
+            Some(Ack::Late) => info!("{}: Adult {:?} stored {:?} late", self, holder, address),
+            None => return Ok(vec![]),
+        }
+        if let Some(write) = self.writes.get(&msg_id) {
+            let origin = write.origin;
+            self.set_chunk_holder(address, holder, origin).await?;
+        }
+        Ok(self.write_outcome(msg_id).into_iter().collect())
+    }
+
+    // Called when an adult fails to store a chunk a client wrote.  One lacking space is recorded
+    // as full, unless it is being decommissioned, and another adult is asked in its place, as for
+    // other failures of the adult rather than the write itself.
+    pub(super) async fn chunk_write_failed(
+        &mut self,
+        address: BlobAddress,
+        msg_id: MessageId,
+        holder: XorName,
+        error: ErrorMessage,
+    ) -> Result<NodeDuties> {
+        if !self.is_pending_write(&address, &msg_id) {
+            return Ok(vec![]);
+        }
+        let mut duties = vec![];
+        if error == ErrorMessage::NotEnoughSpace
+            && !self.hand_offs.is_leaving(&holder)
+            && !self.capacity.is_full(&holder).await
+        {
+            info!("{}: Adult {:?} ran out of space", self, holder);
+            self.capacity.mark_full(holder).await?;
+            // Accept a new node in place for the full node.
+            duties.push(NodeDuty::SetNodeJoinsAllowed(true));
+        }
+        let replace = matches!(
+            error,
+            ErrorMessage::NotEnoughSpace | ErrorMessage::FailedToWriteFile
+        );
+        if !self.writes.failed(&msg_id, holder, error) {
+            return Ok(duties);
+        }
+        if replace {
+            duties.extend(self.ask_next_holder(msg_id).await);
+        }
+        duties.extend(self.write_outcome(msg_id));
+        Ok(duties)
+    }
+
+    // Asks another adult for the writes that adults didn't acknowledge in time.
+    pub(super) async fn check_pending_writes(&mut self) -> Result<NodeDuties> {
+        let mut duties = vec![];
+        for (msg_id, holder) in self.writes.expire() {
+            warn!(
+                "{}: Adult {:?} didn't acknowledge write {:?} in time",
+                self, holder, msg_id
+            );
+            self.scores.record_failure(holder);
+            duties.extend(self.ask_next_holder(msg_id).await);
+            duties.extend(self.write_outcome(msg_id));
+        }
+        Ok(duties)
+    }
+
+    // Sends a pending write to the closest adult with space that wasn't asked for it yet.
+    async fn ask_next_holder(&mut self, msg_id: MessageId) -> Option<NodeDuty> {
+        let write = self.writes.get(&msg_id)?;
+        let (data, origin, asked) = (write.data.clone(), write.origin, write.asked.clone());
+        let holders = match self.get_metadata_for(*data.address()).await {
+            Ok(metadata) => metadata.holders,
+            Err(_) => BTreeSet::new(),
+        };
+        let next = self
+            .get_holder_candidates(data.name())
+            .await
+            .into_iter()
+            .find(|adult| !asked.contains(adult) && !holders.contains(adult))?;
+        info!(
+            "{}: Asking {:?} to store {:?} instead",
+            self,
+            next,
+            data.address()
+        );
+        self.writes.ask(msg_id, next);
+        Some(NodeDuty::SendToNodes {
+            targets: std::iter::once(next).collect(),
+            msg: Message::NodeCmd {
+                cmd: NodeCmd::Chunks {
+                    cmd: BlobWrite::New(data),
+                    origin,
+                },
+                id: msg_id,
+                target_section_pk: None,
+            },
+        })
+    }
+
+    // Answers the client once the outcome of its write is known.
+    fn write_outcome(&mut self, msg_id: MessageId) -> Option<NodeDuty> {
+        match self.writes.outcome(&msg_id)? {
+            WriteOutcome::Stored { address, copies } => {
+                info!("{}: Stored {} copies of {:?}", self, copies, address);
+                // TODO: answer the client, once sn_messaging has a response for successful cmds.
+                None
+            }
+            WriteOutcome::Failed {
+                address,
+                origin,
+                error,
+            } => {
+                warn!("{}: Failed to store {:?}: {:?}", self, address, error);
+                Some(NodeDuty::Send(OutgoingMsg {
+                    msg: Message::CmdError {
+                        error: CmdError::Data(error),
+                        id: MessageId::in_response_to(&msg_id),
+                        correlation_id: msg_id,
+                        target_section_pk: None,
+                    },
+                    section_source: false, // strictly this is not correct, but we don't expect responses to an error..
+                    dst: DstLocation::EndUser(origin),
+                    aggregation: Aggregation::AtDestination,
+                }))
+            }
+        }
+    }
+
+    // Has a chunk replicated away from a holder being decommissioned, which keeps serving it
+    // meanwhile: new holders are chosen so that CHUNK_COPY_COUNT remain without the departing
+    // ones.  Repeated requests replace the new holders that didn't confirm the chunk since.
+    pub(super) async fn hand_off_chunk(
+        &mut self,
+        address: BlobAddress,
+        departing: XorName,
+    ) -> Result<NodeDuties> {
+        info!(
+            "{}: Handing off chunk {:?} from {:?}",
+            self, address, departing
+        );
+        let mut holders = match self.get_metadata_for(address).await {
+            Ok(metadata) if !metadata.holders.contains(&departing) => {
+                // already handed off
+                return Ok(vec![NodeDuty::ConfirmHandOff {
+                    address,
+                    holder: departing,
+                }]);
+            }
+            Ok(metadata) => metadata.holders,
+            Err(_) => std::iter::once(departing).collect(),
+        };
+        for unconfirmed in self.hand_offs.start(address, departing) {
+            warn!(
+                "{}: New holder {:?} didn't confirm chunk {:?}",
+                self, unconfirmed, address
+            );
+            self.remove_chunk_holder(address, unconfirmed).await?;
+            let _ = holders.remove(&unconfirmed);
+        }
+        let leaving = self.hand_offs.departing(&address);
+        let remaining = holders.difference(&leaving).count();
+        let new_holders: BTreeSet<_> = self
+            .get_holder_candidates(address.name())
+            .await
+            .into_iter()
+            .filter(|adult| !holders.contains(adult))
+            .take(CHUNK_COPY_COUNT.saturating_sub(remaining))
+            .collect();
+        if new_holders.is_empty() && !self.hand_offs.is_awaiting(&address) {
+            return self.complete_hand_off(address).await;
+        }
+        for holder in &new_holders {
+            self.update_holders(address, *holder).await?;
+        }
+        self.hand_offs.await_holders(address, &new_holders);
+        Ok(self.replication_msgs(address, new_holders, holders))
+    }
+
+    // Called when a new holder reports having replicated a chunk, completing the hand-off the
+    // replication was for, if any.
+    pub(super) async fn confirm_replication(
+        &mut self,
+        address: BlobAddress,
+        holder: XorName,
+    ) -> Result<NodeDuties> {
+        if self.hand_offs.confirm(&address, &holder) {
+            return self.complete_hand_off(address).await;
+        }
+        Ok(vec![])
+    }
+
+    // Removes the departing holders of a chunk, confirming to each that it was handed off.
+    async fn complete_hand_off(&mut self, address: BlobAddress) -> Result<NodeDuties> {
+        let mut duties = vec![];
+        for holder in self.hand_offs.complete(&address) {
+            self.remove_chunk_holder(address, holder).await?;
+            duties.push(NodeDuty::ConfirmHandOff { address, holder });
+        }
+        Ok(duties)
+    }
+
+    // Challenges each adult with one of the chunks recorded for it, picked at random, along with
+    // the other holders of the chunk, all with the same nonce, unless it has too few of them to
+    // check their proofs against each other.  Challenges from the last round still unanswered
+    // fail, and each holder failing one has that chunk replicated elsewhere.
+    pub(super) async fn challenge_holders(&mut self) -> Result<NodeDuties> {
+        let mut duties = vec![];
+        for verdict in self.challenges.expire() {
+            duties.extend(self.judge_holder(verdict).await?);
+        }
+        let adults: BTreeSet<_> = self.reader.our_adults().await.into_iter().collect();
+        for adult in &adults {
+            let address = match self.get_holder(*adult).await {
+                Ok(metadata) => match metadata.chunks.into_iter().choose(&mut thread_rng()) {
+                    Some(address) => address,
+                    None => continue,
+                },
+                Err(_) => continue,
+            };
+            if self.challenges.is_challenged(&address) {
+                continue;
+            }
+            let holders: BTreeSet<_> = match self.get_metadata_for(address).await {
+                Ok(metadata) => metadata.holders.intersection(&adults).copied().collect(),
+                Err(_) => continue,
+            };
+            let msg_id = MessageId::new();
+            let nonce = thread_rng().gen();
+            if !self.challenges.start(msg_id, address, holders.clone()) {
+                continue;
+            }
+            for holder in holders {
+                duties.push(NodeDuty::SendNodeMsg(OutgoingNodeMsg {
+                    msg: NodeMsg::StorageChallenge {
+                        address,
+                        nonce,
+                        id: msg_id,
+                    },
+                    section_source: false,
+                    dst: DstLocation::Node(holder),
+                    aggregation: Aggregation::None,
+                }));
+            }
+        }
+        Ok(duties)
+    }
+
+    pub(super) async fn process_storage_proof(
+        &mut self,
+        correlation_id: MessageId,
+        holder: XorName,
+        proof: Option<XorName>,
+    ) -> Result<NodeDuties> {
+        let mut duties = vec![];
+        for verdict in self.challenges.answer(&correlation_id, holder, proof) {
+            duties.extend(self.judge_holder(verdict).await?);
+        }
+        Ok(duties)
+    }
+
+    async fn judge_holder(&mut self, verdict: Verdict) -> Result<NodeDuties> {
+        info!(
+            "{}: Storage challenge results of {:?}: {:?}",
+            self,
+            verdict.holder,
+            self.challenges.results(&verdict.holder)
+        );
+        if verdict.passed {
+            if let Some(latency) = verdict.latency {
+                self.scores.record_success(verdict.holder, latency);
+            }
+            return Ok(vec![]);
+        }
+        // Only the failed chunk, as the holder might still hold the others.
+        self.replicate_lost_chunk(verdict.address, verdict.holder)
+            .await
+    }
+
+    // Only reports from holders of the chunk, about another holder of it, are acted on.  New
+    // holders are recorded as such when asked to replicate it.
+    pub(super) async fn replace_corrupt_copy(
+        &mut self,
+        address: BlobAddress,
+        reporter: XorName,
+        holder: XorName,
+    ) -> Result<NodeDuties> {
+        let holders = match self.get_metadata_for(address).await {
+            Ok(metadata) => metadata.holders,
+            Err(_) => return Ok(vec![]),
+        };
+        if !holders.contains(&reporter) || !holders.contains(&holder) || reporter == holder {
+            warn!(
+                "{}: Ignoring report on chunk {:?} held by {:?} from {:?}",
+                self, address, holder, reporter
+            );
+            return Ok(vec![]);
+        }
+        warn!(
+            "{}: Holder {:?} sent a corrupt copy of chunk {:?} to {:?}",
+            self, holder, address, reporter
+        );
+        self.replicate_lost_chunk(address, holder).await
+    }
+
     async fn get_replication_msgs(
         &mut self,
         address: BlobAddress,
         current_holders: BTreeSet<XorName>,
+        excluded: &BTreeSet<XorName>,
     ) -> Result<NodeDuties> {
-        use NodeCmd::*;
-        let mut node_ops = Vec::new();
-        let new_holders = self.get_new_holders_for_chunk(&address).await;
+        let new_holders = self.get_new_holders_for_chunk(&address, excluded).await;
         for holder in &new_holders {
             self.update_holders(address, *holder).await?;
         }
+        Ok(self.replication_msgs(address, new_holders, current_holders))
+    }
+
+    fn replication_msgs(
+        &self,
+        address: BlobAddress,
+        new_holders: BTreeSet<XorName>,
+        current_holders: BTreeSet<XorName>,
+    ) -> NodeDuties {
+        use NodeCmd::*;
+        let mut node_ops = Vec::new();
         let messages = new_holders
             .into_iter()
             .map(|new_holder| {
@@ -344,11 +795,11 @@ impl BlobRegister {
                 aggregation: Aggregation::AtDestination,
             }));
         }
-        Ok(node_ops)
+        node_ops
     }
 
     pub(super) async fn read(
-        &self,
+        &mut self,
         read: &BlobRead,
         msg_id: MessageId,
         origin: EndUser,
@@ -360,7 +811,7 @@ impl BlobRegister {
     }
 
     async fn get(
-        &self,
+        &mut self,
         address: BlobAddress,
         msg_id: MessageId,
         origin: EndUser,
@@ -391,45 +842,130 @@ impl BlobRegister {
                 return query_error(Error::NetworkData(DtError::AccessDenied(*origin.id()))).await;
             }
         };
-        let msg = Message::NodeQuery {
-            query: NodeQuery::Chunks {
-                query: BlobRead::Get(address),
-                origin,
+        // Holders are asked one at a time, the healthiest and closest first.
+        let mut holders: Vec<_> = metadata.holders.into_iter().collect();
+        holders.sort_by(|lhs, rhs| {
+            self.scores
+                .is_failing(lhs)
+                .cmp(&self.scores.is_failing(rhs))
+                .then_with(|| address.name().cmp_distance(lhs, rhs))
+        });
+        match self.reads.start(msg_id, address, origin, holders) {
+            Some(holder) => Ok(Self::chunk_query(address, holder, msg_id, origin)),
+            None => query_error(Error::NoHoldersOfChunk).await,
+        }
+    }
+
+    fn chunk_query(
+        address: BlobAddress,
+        holder: XorName,
+        msg_id: MessageId,
+        origin: EndUser,
+    ) -> NodeDuty {
+        NodeDuty::SendToNodes {
+            targets: std::iter::once(holder).collect(),
+            msg: Message::NodeQuery {
+                query: NodeQuery::Chunks {
+                    query: BlobRead::Get(address),
+                    origin,
+                },
+                id: msg_id,
+                target_section_pk: None,
             },
-            id: msg_id,
-            target_section_pk: None,
-        };
-        Ok(NodeDuty::SendToNodes {
-            targets: metadata.holders,
-            msg,
-        })
+        }
+    }
+
+    // Called when the holder asked to serve a read fails to.  One that no longer holds the chunk
+    // is replaced as holder, and the read goes on to the next holder.
+    pub(super) async fn chunk_read_failed(
+        &mut self,
+        address: BlobAddress,
+        msg_id: MessageId,
+        holder: XorName,
+        error: ErrorMessage,
+    ) -> Result<NodeDuties> {
+        if !self.is_pending_read(&address, &msg_id, &holder) {
+            return Ok(vec![]);
+        }
+        let mut duties = vec![];
+        if error == ErrorMessage::NoSuchData {
+            duties.extend(self.replicate_lost_chunk(address, holder).await?);
+        } else {
+            self.scores.record_failure(holder);
+        }
+        duties.extend(self.next_read(msg_id, holder));
+        Ok(duties)
+    }
+
+    // Whether `holder` was asked to serve the pending read of the chunk with `msg_id`.
+    fn is_pending_read(&self, address: &BlobAddress, msg_id: &MessageId, holder: &XorName) -> bool {
+        matches!(self.reads.asked(msg_id, holder), Some(read) if &read.address == address)
+    }
+
+    // Called when a holder serves a chunk for the client's read with `msg_id`.
+    pub(super) fn chunk_served(
+        &mut self,
+        address: BlobAddress,
+        msg_id: MessageId,
+        holder: XorName,
+    ) {
+        if !self.is_pending_read(&address, &msg_id, &holder) {
+            return;
+        }
+        if let Some(latency) = self.reads.served(&msg_id, &holder) {
+            self.scores.record_success(holder, latency);
+        }
+    }
+
+    // Asks the next holder for the reads that weren't served in time.
+    pub(super) fn check_pending_reads(&mut self) -> NodeDuties {
+        let mut duties = vec![];
+        for (msg_id, holder) in self.reads.expired() {
+            warn!(
+                "{}: Holder {:?} didn't serve read {:?} in time",
+                self, holder, msg_id
+            );
+            self.scores.record_failure(holder);
+            duties.extend(self.next_read(msg_id, holder));
+        }
+        duties
+    }
+
+    fn next_read(&mut self, msg_id: MessageId, failed: XorName) -> Option<NodeDuty> {
+        match self.reads.fail(&msg_id, &failed)? {
+            ReadFallback::Next {
+                address,
+                origin,
+                holder,
+            } => Some(Self::chunk_query(address, holder, msg_id, origin)),
+            ReadFallback::Exhausted { address, origin } => {
+                warn!("{}: No holder served {:?}", self, address);
+                Some(NodeDuty::Send(OutgoingMsg {
+                    msg: Message::QueryResponse {
+                        response: QueryResponse::GetBlob(Err(ErrorMessage::NoSuchData)),
+                        id: MessageId::in_response_to(&msg_id),
+                        correlation_id: msg_id,
+                        target_section_pk: None,
+                    },
+                    section_source: false, // strictly this is not correct, but we don't expect responses to an error..
+                    dst: DstLocation::EndUser(origin),
+                    aggregation: Aggregation::AtDestination,
+                }))
+            }
+        }
     }
 
     async fn update_holders(&mut self, address: BlobAddress, holder: XorName) -> Result<()> {
         let mut chunk_metadata = self.get_metadata_for(address).await.unwrap_or_default();
         let _ = chunk_metadata.holders.insert(holder);
-        if let Err(error) = self
-            .dbs
-            .metadata
-            .lock()
-            .await
-            .set(&address.to_db_key()?, &chunk_metadata)
-        {
-            warn!("{}: Failed to write metadata to DB: {:?}", self, error);
-        }
         let mut holders_metadata = self.get_holder(holder).await.unwrap_or_default();
         let _ = holders_metadata.chunks.insert(address);
-        if let Err(error) = self
-            .dbs
-            .holders
-            .lock()
-            .await
-            .set(&holder.to_db_key()?, &holders_metadata)
-        {
-            warn!(
-                "{}: Failed to write holder metadata to DB: {:?}",
-                self, error
-            );
+
+        let mut batch = DbBatch::new();
+        batch.set(Table::Metadata, &address.to_db_key()?, &chunk_metadata)?;
+        batch.set(Table::Holders, &holder.to_db_key()?, &holders_metadata)?;
+        if let Err(error) = self.dbs.commit(batch).await {
+            warn!("{}: Failed to write metadata to DB: {:?}", self, error);
         }
         info!(
             "Requested replication of chunk {:?} to new holder {:?}",
@@ -445,6 +981,7 @@ impl BlobRegister {
         node: XorName,
     ) -> Result<BTreeMap<BlobAddress, BTreeSet<XorName>>> {
         let mut blob_addresses: BTreeMap<BlobAddress, BTreeSet<XorName>> = BTreeMap::new();
+        let mut batch = DbBatch::new();
         let chunk_holder = self.get_holder(node).await;
 
         if let Ok(holder) = chunk_holder {
@@ -460,22 +997,20 @@ impl BlobRegister {
                     let _ = blob_addresses.insert(chunk_address, metadata.holders.clone());
 
                     if metadata.holders.is_empty() {
-                        if let Err(error) = self.dbs.metadata.lock().await.rem(&db_key) {
-                            warn!("{}: Failed to write metadata to DB: {:?}", self, error);
-                        }
-                    } else if let Err(error) =
-                        self.dbs.metadata.lock().await.set(&db_key, &metadata)
-                    {
-                        warn!("{}: Failed to write metadata to DB: {:?}", self, error);
+                        batch.remove(Table::Metadata, &db_key);
+                    } else {
+                        batch.set(Table::Metadata, &db_key, &metadata)?;
                     }
                 }
             }
         }
 
         // Since the node has left the section, remove it from the holders DB
-        if let Err(error) = self.dbs.holders.lock().await.rem(&node.to_db_key()?) {
-            warn!("{}: Failed to delete metadata from DB: {:?}", self, error);
-        };
+        batch.remove(Table::Holders, &node.to_db_key()?);
+        if let Err(error) = self.dbs.commit(batch).await {
+            warn!("{}: Failed to write metadata to DB: {:?}", self, error);
+            return Err(error);
+        }
 
         Ok(blob_addresses)
     }
@@ -483,10 +1018,7 @@ impl BlobRegister {
     async fn get_holder(&self, holder: XorName) -> Result<HolderMetadata> {
         match self
             .dbs
-            .holders
-            .lock()
-            .await
-            .get::<HolderMetadata>(&holder.to_db_key()?)
+            .get::<HolderMetadata>(Table::Holders, &holder.to_db_key()?)
         {
             Some(metadata) => {
                 if metadata.chunks.is_empty() {
@@ -506,10 +1038,7 @@ impl BlobRegister {
     async fn get_metadata_for(&self, address: BlobAddress) -> Result<ChunkMetadata> {
         match self
             .dbs
-            .metadata
-            .lock()
-            .await
-            .get::<ChunkMetadata>(&address.to_db_key()?)
+            .get::<ChunkMetadata>(Table::Metadata, &address.to_db_key()?)
         {
             Some(metadata) => {
                 if metadata.holders.is_empty() {
@@ -532,27 +1061,66 @@ impl BlobRegister {
     // Returns `XorName`s of the target holders for an Blob chunk.
     // Used to fetch the list of holders for a new chunk.
     async fn get_holders_for_chunk(&self, target: &XorName) -> Vec<XorName> {
-        self.reader
-            .our_adults_sorted_by_distance_to(&target, CHUNK_COPY_COUNT)
+        let mut candidates = self.get_holder_candidates(target).await;
+        candidates.truncate(CHUNK_COPY_COUNT);
+        candidates
+    }
+
+    // Of the closest adults to the target, the least used ones come first, the closer ones first
+    // when equally used.  Adults persistently failing chunk operations come last, and ones being
+    // decommissioned or recorded as full are left out, the next closest ones taking their place.
+    async fn get_holder_candidates(&self, target: &XorName) -> Vec<XorName> {
+        let adult_count = self.reader.our_adults().await.len();
+        let mut candidates = vec![];
+        for adult in self
+            .reader
+            .our_adults_sorted_by_distance_to(&target, adult_count)
             .await
+        {
+            if candidates.len() == HOLDER_CANDIDATE_COUNT {
+                break;
+            }
+            if self.hand_offs.is_leaving(&adult) || self.capacity.is_full(&adult).await {
+                continue;
+            }
+            let failing = self.scores.is_failing(&adult);
+            candidates.push((failing, self.capacity.used_space_ratio(&adult).await, adult));
+        }
+        candidates.sort_by(|(failing, ratio, _), (other_failing, other_ratio, _)| {
+            failing
+                .cmp(other_failing)
+                .then_with(|| ratio.partial_cmp(other_ratio).unwrap_or(Ordering::Equal))
+        });
+        candidates.into_iter().map(|(_, _, adult)| adult).collect()
+    }
+
+    pub(super) async fn flush(&self) -> Result<()> {
+        self.dbs.flush().await
+    }
+
+    /// The scores of the adults chunk operations were routed to.
+    pub(super) fn adult_scores(&self) -> &BTreeMap<XorName, AdultScore> {
+        self.scores.scores()
     }
 
     // Returns `XorName`s of the new target holders for an Blob chunk.
-    // Used to fetch the additional list of holders for existing chunks.
-    async fn get_new_holders_for_chunk(&self, target: &BlobAddress) -> BTreeSet<XorName> {
-        let closest_holders = self
-            .get_holders_for_chunk(target.name())
+    // Used to fetch the additional list of holders for existing chunks,
+    // leaving out `excluded` adults, e.g. ones that failed to replicate it.
+    async fn get_new_holders_for_chunk(
+        &self,
+        target: &BlobAddress,
+        excluded: &BTreeSet<XorName>,
+    ) -> BTreeSet<XorName> {
+        let holders = match self.get_metadata_for(*target).await {
+            Ok(metadata) => metadata.holders,
+            Err(_) => BTreeSet::new(),
+        };
+        self.get_holder_candidates(target.name())
             .await
-            .iter()
-            .cloned()
-            .collect::<BTreeSet<_>>();
-        if let Ok(metadata) = self.get_metadata_for(*target).await {
-            return closest_holders
-                .difference(&metadata.holders)
-                .cloned()
-                .collect();
-        }
-        closest_holders
+            .into_iter()
+            .filter(|adult| !holders.contains(adult) && !excluded.contains(adult))
+            .take(CHUNK_COPY_COUNT.saturating_sub(holders.len()))
+            .collect()
     }
 }
 
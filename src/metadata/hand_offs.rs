@@ -0,0 +1,154 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use sn_data_types::BlobAddress;
+use std::collections::{BTreeMap, BTreeSet};
+use xor_name::XorName;
+
+/// A chunk being replicated away from holders that are leaving.
+#[derive(Default)]
+struct HandOff {
+    /// The holders leaving.
+    departing: BTreeSet<XorName>,
+    /// The new holders asked to replicate the chunk, that didn't confirm it yet.
+    awaiting: BTreeSet<XorName>,
+}
+
+/// Tracks the adults being decommissioned, and the hand-off of their chunks.
+pub(super) struct HandOffs {
+    leaving: BTreeSet<XorName>,
+    pending: BTreeMap<BlobAddress, HandOff>,
+}
+
+impl HandOffs {
+    pub(super) fn new() -> Self {
+        Self {
+            leaving: BTreeSet::new(),
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Starts the hand-off of a chunk from a departing holder.  If that holder already asked for
+    /// it, the new holders that didn't confirm the chunk since are returned, to be replaced.
+    pub(super) fn start(&mut self, address: BlobAddress, departing: XorName) -> BTreeSet<XorName> {
+        let _ = self.leaving.insert(departing);
+        let hand_off = self.pending.entry(address).or_default();
+        if hand_off.departing.insert(departing) {
+            BTreeSet::new()
+        } else {
+            std::mem::take(&mut hand_off.awaiting)
+        }
+    }
+
+    /// The departing holders of a chunk being handed off.
+    pub(super) fn departing(&self, address: &BlobAddress) -> BTreeSet<XorName> {
+        self.pending
+            .get(address)
+            .map(|hand_off| hand_off.departing.clone())
+            .unwrap_or_default()
+    }
+
+    /// Whether new holders were asked to replicate the chunk, that didn't confirm it yet.
+    pub(super) fn is_awaiting(&self, address: &BlobAddress) -> bool {
+        self.pending
+            .get(address)
+            .map(|hand_off| !hand_off.awaiting.is_empty())
+            .unwrap_or(false)
+    }
+
+    pub(super) fn await_holders(&mut self, address: BlobAddress, new_holders: &BTreeSet<XorName>) {
+        if let Some(hand_off) = self.pending.get_mut(&address) {
+            hand_off.awaiting.extend(new_holders);
+        }
+    }
+
+    /// Records a new holder confirming it replicated the chunk.  Returns whether the hand-off is
+    /// complete, with no other new holder to wait for.
+    pub(super) fn confirm(&mut self, address: &BlobAddress, holder: &XorName) -> bool {
+        match self.pending.get_mut(address) {
+            Some(hand_off) => hand_off.awaiting.remove(holder) && hand_off.awaiting.is_empty(),
+            None => false,
+        }
+    }
+
+    /// Ends the hand-off of a chunk, returning its departing holders.
+    pub(super) fn complete(&mut self, address: &BlobAddress) -> BTreeSet<XorName> {
+        self.pending
+            .remove(address)
+            .map(|hand_off| hand_off.departing)
+            .unwrap_or_default()
+    }
+
+    /// Whether the adult is being decommissioned, so is not to be given chunks.
+    pub(super) fn is_leaving(&self, adult: &XorName) -> bool {
+        self.leaving.contains(adult)
+    }
+
+    /// Forgets an adult that left.
+    pub(super) fn forget(&mut self, adult: &XorName) {
+        let _ = self.leaving.remove(adult);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hand_off_completes_once_new_holders_confirm() {
+        let mut hand_offs = HandOffs::new();
+        let address = BlobAddress::Public(XorName::random());
+        let departing = XorName::random();
+        let new_holders: BTreeSet<_> = (0..2).map(|_| XorName::random()).collect();
+
+        assert!(hand_offs.start(address, departing).is_empty());
+        assert!(hand_offs.is_leaving(&departing));
+        hand_offs.await_holders(address, &new_holders);
+        assert!(hand_offs.is_awaiting(&address));
+
+        let mut confirming = new_holders.iter();
+        let first = confirming.next().unwrap();
+        assert!(!hand_offs.confirm(&address, first));
+        // confirmations are only counted once, and from new holders
+        assert!(!hand_offs.confirm(&address, first));
+        assert!(!hand_offs.confirm(&address, &XorName::random()));
+        assert!(hand_offs.confirm(&address, confirming.next().unwrap()));
+
+        assert_eq!(
+            hand_offs.complete(&address),
+            std::iter::once(departing).collect()
+        );
+        assert!(hand_offs.departing(&address).is_empty());
+        hand_offs.forget(&departing);
+        assert!(!hand_offs.is_leaving(&departing));
+    }
+
+    #[test]
+    fn retried_hand_off_replaces_unconfirmed_holders() {
+        let mut hand_offs = HandOffs::new();
+        let address = BlobAddress::Public(XorName::random());
+        let departing = XorName::random();
+        let new_holders: BTreeSet<_> = (0..2).map(|_| XorName::random()).collect();
+
+        assert!(hand_offs.start(address, departing).is_empty());
+        hand_offs.await_holders(address, &new_holders);
+        let confirmed = *new_holders.iter().next().unwrap();
+        assert!(!hand_offs.confirm(&address, &confirmed));
+
+        // another holder leaving doesn't make a retry
+        assert!(hand_offs.start(address, XorName::random()).is_empty());
+        assert_eq!(hand_offs.departing(&address).len(), 2);
+
+        let unconfirmed: BTreeSet<_> = new_holders
+            .into_iter()
+            .filter(|holder| *holder != confirmed)
+            .collect();
+        assert_eq!(hand_offs.start(address, departing), unconfirmed);
+        assert!(!hand_offs.is_awaiting(&address));
+    }
+}
@@ -7,12 +7,12 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
-    chunk_store::{MapChunkStore, UsedSpace},
+    chunk_store::{ChunkStoreConfig, MapChunkStore, UsedSpace},
     error::convert_to_error_message,
     node_ops::{NodeDuty, OutgoingMsg},
     Error, Network, Result,
 };
-use log::info;
+use log::{info, warn};
 use sn_data_types::{
     Error as DtError, Map, MapAction, MapAddress, MapEntryActions, MapPermissionSet, MapValue,
     PublicKey, Result as NdResult,
@@ -33,11 +33,27 @@ pub(super) struct MapStorage {
 }
 
 impl MapStorage {
-    pub(super) async fn new(path: &Path, used_space: UsedSpace) -> Result<Self> {
-        let chunks = MapChunkStore::new(path, used_space).await?;
+    pub(super) async fn new(
+        path: &Path,
+        used_space: UsedSpace,
+        store_config: &ChunkStoreConfig,
+    ) -> Result<Self> {
+        let chunks = MapChunkStore::new(path, used_space, store_config).await?;
         Ok(Self { chunks })
     }
 
+    /// Verifies the next `count` stored chunks, quarantining corrupt ones.
+    pub(super) async fn scrub(&mut self, count: usize) -> Result<()> {
+        for address in self.chunks.scrub(count).await? {
+            warn!("{}: Quarantined corrupt chunk {:?}", self, address);
+        }
+        Ok(())
+    }
+
+    pub(super) async fn reconcile_used_space(&mut self) -> Result<()> {
+        self.chunks.reconcile_used_space().await
+    }
+
     pub(super) async fn read(
         &self,
         read: &MapRead,
@@ -96,8 +112,13 @@ impl MapStorage {
     /// Returns `Some(Result<..>)` if the flow should be continued, returns
     /// `None` if there was a logic error encountered and the flow should be
     /// terminated.
-    fn get_chunk(&self, address: &MapAddress, origin: EndUser, action: MapAction) -> Result<Map> {
-        self.chunks.get(&address).and_then(move |map| {
+    async fn get_chunk(
+        &self,
+        address: &MapAddress,
+        origin: EndUser,
+        action: MapAction,
+    ) -> Result<Map> {
+        self.chunks.get(&address).await.and_then(move |map| {
             map.check_permissions(action, origin.id())
                 .map(move |_| map)
                 .map_err(|error| error.into())
@@ -115,7 +136,7 @@ impl MapStorage {
     where
         F: FnOnce(Map) -> NdResult<Map>,
     {
-        let result = match self.chunks.get(address) {
+        let result = match self.chunks.get(address).await {
             Ok(data) => match mutation_fn(data) {
                 Ok(map) => self.chunks.put(&map).await,
                 Err(error) => Err(error.into()),
@@ -128,7 +149,7 @@ impl MapStorage {
 
     /// Put Map.
     async fn create(&mut self, data: &Map, msg_id: MessageId, origin: EndUser) -> Result<NodeDuty> {
-        let result = if self.chunks.has(data.address()) {
+        let result = if self.chunks.has(data.address()).await {
             Err(Error::DataExists)
         } else {
             self.chunks.put(&data).await
@@ -142,7 +163,7 @@ impl MapStorage {
         msg_id: MessageId,
         origin: EndUser,
     ) -> Result<NodeDuty> {
-        let result = match self.chunks.get(&address) {
+        let result = match self.chunks.get(&address).await {
             Ok(map) => match map.check_is_owner(origin.id()) {
                 Ok(()) => {
                     info!("Deleting Map");
@@ -216,7 +237,7 @@ impl MapStorage {
         msg_id: MessageId,
         origin: EndUser,
     ) -> Result<NodeDuty> {
-        let result = match self.get_chunk(&address, origin, MapAction::Read) {
+        let result = match self.get_chunk(&address, origin, MapAction::Read).await {
             Ok(res) => Ok(res),
             Err(error) => Err(convert_to_error_message(error)?),
         };
@@ -243,6 +264,7 @@ impl MapStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(&address, origin, MapAction::Read)
+            .await
             .map(|data| data.shell())
         {
             Ok(res) => Ok(res),
@@ -271,6 +293,7 @@ impl MapStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(&address, origin, MapAction::Read)
+            .await
             .map(|data| data.version())
         {
             Ok(res) => Ok(res),
@@ -298,7 +321,7 @@ impl MapStorage {
         msg_id: MessageId,
         origin: EndUser,
     ) -> Result<NodeDuty> {
-        let res = self.get_chunk(&address, origin, MapAction::Read);
+        let res = self.get_chunk(&address, origin, MapAction::Read).await;
         let result = match res.and_then(|data| match data {
             Map::Seq(map) => map
                 .get(key)
@@ -337,6 +360,7 @@ impl MapStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(&address, origin, MapAction::Read)
+            .await
             .map(|data| data.keys())
         {
             Ok(res) => Ok(res),
@@ -363,7 +387,7 @@ impl MapStorage {
         msg_id: MessageId,
         origin: EndUser,
     ) -> Result<NodeDuty> {
-        let res = self.get_chunk(&address, origin, MapAction::Read);
+        let res = self.get_chunk(&address, origin, MapAction::Read).await;
         let result = match res.map(|data| match data {
             Map::Seq(map) => map.values().into(),
             Map::Unseq(map) => map.values().into(),
@@ -392,7 +416,7 @@ impl MapStorage {
         msg_id: MessageId,
         origin: EndUser,
     ) -> Result<NodeDuty> {
-        let res = self.get_chunk(&address, origin, MapAction::Read);
+        let res = self.get_chunk(&address, origin, MapAction::Read).await;
         let result = match res.map(|data| match data {
             Map::Seq(map) => map.entries().clone().into(),
             Map::Unseq(map) => map.entries().clone().into(),
@@ -423,6 +447,7 @@ impl MapStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(&address, origin, MapAction::Read)
+            .await
             .map(|data| data.permissions())
         {
             Ok(res) => Ok(res),
@@ -452,6 +477,7 @@ impl MapStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(&address, origin, MapAction::Read)
+            .await
             .and_then(|data| {
                 data.user_permissions(&user)
                     .map_err(|e| e.into())
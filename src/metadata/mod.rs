@@ -7,32 +7,51 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 pub mod adult_reader;
+mod adult_scores;
 mod blob_register;
 mod elder_stores;
+mod hand_offs;
 mod map_storage;
+mod pending_reads;
+mod pending_writes;
 mod reading;
 mod sequence_storage;
+mod storage_challenges;
 mod writing;
 
 use self::adult_reader::AdultReader;
+pub use self::adult_scores::AdultScore;
 use super::node_ops::NodeDuty;
 use crate::{
-    capacity::ChunkHolderDbs, chunk_store::UsedSpace, node_ops::NodeDuties, Network, Result,
+    capacity::{Capacity, ChunkHolderDbs},
+    chunk_store::{ChunkStoreConfig, UsedSpace},
+    node_ops::NodeDuties,
+    Network, Result,
 };
 use blob_register::BlobRegister;
 use elder_stores::ElderStores;
 use map_storage::MapStorage;
 use sequence_storage::SequenceStorage;
+use sn_data_types::{Blob, BlobAddress};
 use sn_messaging::{
-    client::{DataCmd, DataQuery},
+    client::{DataCmd, DataQuery, Error as ErrorMessage},
     EndUser, MessageId,
 };
 use std::{
+    collections::BTreeMap,
     fmt::{self, Display, Formatter},
     path::Path,
 };
 use xor_name::XorName;
 
+/// Settings of how elders handle the data of their section.
+#[derive(Clone, Debug)]
+pub(crate) struct MetadataConfig {
+    /// Number of copies of a new blob adults must acknowledge before an elder has its write
+    /// succeed.
+    pub write_quorum: usize,
+}
+
 /// This module is called `Metadata`
 /// as a preparation for the responsibilities
 /// it will have eventually, after `Data Hierarchy Refinement`
@@ -47,18 +66,26 @@ impl Metadata {
     pub async fn new(
         path: &Path,
         used_space: &UsedSpace,
+        store_config: &ChunkStoreConfig,
+        config: &MetadataConfig,
         dbs: ChunkHolderDbs,
+        capacity: Capacity,
         reader: AdultReader,
     ) -> Result<Self> {
-        let blob_register = BlobRegister::new(dbs, reader);
-        let map_storage = MapStorage::new(path, used_space.clone()).await?;
-        let sequence_storage = SequenceStorage::new(path, used_space.clone()).await?;
+        let blob_register = BlobRegister::new(dbs, capacity, reader, config.write_quorum);
+        let map_storage = MapStorage::new(path, used_space.clone(), store_config).await?;
+        let sequence_storage = SequenceStorage::new(path, used_space.clone(), store_config).await?;
         let elder_stores = ElderStores::new(blob_register, map_storage, sequence_storage);
         Ok(Self { elder_stores })
     }
 
-    pub async fn read(&self, query: DataQuery, id: MessageId, origin: EndUser) -> Result<NodeDuty> {
-        reading::get_result(query, id, origin, &self.elder_stores).await
+    pub async fn read(
+        &mut self,
+        query: DataQuery,
+        id: MessageId,
+        origin: EndUser,
+    ) -> Result<NodeDuty> {
+        reading::get_result(query, id, origin, &mut self.elder_stores).await
     }
 
     pub async fn write(
@@ -70,6 +97,183 @@ impl Metadata {
         writing::get_result(cmd, id, origin, &mut self.elder_stores).await
     }
 
+    /// Verifies the next `count` chunks of each elder store.
+    pub async fn scrub(&mut self, count: usize) -> Result<NodeDuties> {
+        self.elder_stores.map_storage_mut().scrub(count).await?;
+        self.elder_stores
+            .sequence_storage_mut()
+            .scrub(count)
+            .await?;
+        Ok(vec![])
+    }
+
+    /// Recomputes the used space of each elder store from its chunk files.
+    pub async fn reconcile_used_space(&mut self) -> Result<()> {
+        self.elder_stores
+            .map_storage_mut()
+            .reconcile_used_space()
+            .await?;
+        self.elder_stores
+            .sequence_storage_mut()
+            .reconcile_used_space()
+            .await
+    }
+
+    // Challenges each adult to prove it still holds a chunk recorded for it.
+    pub async fn challenge_holders(&mut self) -> Result<NodeDuties> {
+        self.elder_stores
+            .blob_register_mut()
+            .challenge_holders()
+            .await
+    }
+
+    // Checks the proof a holder answered a storage challenge with, `None` if it has no copy.
+    pub async fn process_storage_proof(
+        &mut self,
+        correlation_id: MessageId,
+        holder: XorName,
+        proof: Option<XorName>,
+    ) -> Result<NodeDuties> {
+        self.elder_stores
+            .blob_register_mut()
+            .process_storage_proof(correlation_id, holder, proof)
+            .await
+    }
+
+    // Called when a new holder of a chunk reports that the holder it fetched the chunk from sent
+    // a corrupt copy.
+    pub async fn replace_corrupt_copy(
+        &mut self,
+        address: BlobAddress,
+        reporter: XorName,
+        holder: XorName,
+    ) -> Result<NodeDuties> {
+        self.elder_stores
+            .blob_register_mut()
+            .replace_corrupt_copy(address, reporter, holder)
+            .await
+    }
+
+    // Called when an adult reports a copy of the chunk it held as lost. Removes it as a holder
+    // and has the chunk replicated again from the remaining ones.
+    pub async fn replicate_lost_chunk(
+        &mut self,
+        address: BlobAddress,
+        holder: XorName,
+    ) -> Result<NodeDuties> {
+        self.elder_stores
+            .blob_register_mut()
+            .replicate_lost_chunk(address, holder)
+            .await
+    }
+
+    // Called when an adult stores a chunk a client wrote, with the id of the write.  Records it
+    // as holder, and answers the client once enough copies are stored.
+    pub async fn chunk_stored(
+        &mut self,
+        address: BlobAddress,
+        msg_id: MessageId,
+        holder: XorName,
+    ) -> Result<NodeDuties> {
+        self.elder_stores
+            .blob_register_mut()
+            .chunk_stored(address, msg_id, holder)
+            .await
+    }
+
+    // Called when an adult fails to store a chunk a client wrote, with the id of the write.
+    // Asks another adult in its place where it makes sense, and answers the client once the
+    // write can't succeed.
+    pub async fn chunk_write_failed(
+        &mut self,
+        address: BlobAddress,
+        msg_id: MessageId,
+        holder: XorName,
+        error: ErrorMessage,
+    ) -> Result<NodeDuties> {
+        self.elder_stores
+            .blob_register_mut()
+            .chunk_write_failed(address, msg_id, holder, error)
+            .await
+    }
+
+    // Called periodically to have the chunks short of copies replicated, a batch at a time.
+    pub async fn audit_replication(&mut self) -> Result<NodeDuties> {
+        self.elder_stores
+            .blob_register_mut()
+            .audit_replication()
+            .await
+    }
+
+    // Called periodically to replace the adults that didn't acknowledge writes in time.
+    pub async fn check_pending_writes(&mut self) -> Result<NodeDuties> {
+        self.elder_stores
+            .blob_register_mut()
+            .check_pending_writes()
+            .await
+    }
+
+    // Called when the holder asked to serve a read does, with the id of the read.
+    pub fn chunk_served(&mut self, address: BlobAddress, msg_id: MessageId, holder: XorName) {
+        self.elder_stores
+            .blob_register_mut()
+            .chunk_served(address, msg_id, holder)
+    }
+
+    // Called when the holder asked to serve a read fails to, with the id of the read.  Asks
+    // the next holder, and answers the client once none is left.
+    pub async fn chunk_read_failed(
+        &mut self,
+        address: BlobAddress,
+        msg_id: MessageId,
+        holder: XorName,
+        error: ErrorMessage,
+    ) -> Result<NodeDuties> {
+        self.elder_stores
+            .blob_register_mut()
+            .chunk_read_failed(address, msg_id, holder, error)
+            .await
+    }
+
+    // Called periodically to ask the next holder for reads not served in time.
+    pub fn check_pending_reads(&mut self) -> NodeDuties {
+        self.elder_stores.blob_register_mut().check_pending_reads()
+    }
+
+    // Called when a holder being decommissioned asks for a chunk to be replicated elsewhere.
+    pub async fn hand_off_chunk(
+        &mut self,
+        address: BlobAddress,
+        departing: XorName,
+    ) -> Result<NodeDuties> {
+        self.elder_stores
+            .blob_register_mut()
+            .hand_off_chunk(address, departing)
+            .await
+    }
+
+    // Called when an adult reports having stored a chunk, as a new holder replicating it.
+    pub async fn confirm_replication(
+        &mut self,
+        address: BlobAddress,
+        holder: XorName,
+    ) -> Result<NodeDuties> {
+        self.elder_stores
+            .blob_register_mut()
+            .confirm_replication(address, holder)
+            .await
+    }
+
+    /// Writes the chunk holder dbs to disk.
+    pub async fn flush(&self) -> Result<()> {
+        self.elder_stores.blob_register().flush().await
+    }
+
+    /// The scores of the adults chunk operations were routed to, from their outcomes.
+    pub fn adult_scores(&self) -> &BTreeMap<XorName, AdultScore> {
+        self.elder_stores.blob_register().adult_scores()
+    }
+
     // This should be called whenever a node leaves the section. It fetches the list of data that was
     // previously held by the node and requests the other holders to store an additional copy.
     // The list of holders is also updated by removing the node that left.
@@ -0,0 +1,166 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use sn_data_types::BlobAddress;
+use sn_messaging::{EndUser, MessageId};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::{Duration, Instant},
+};
+use xor_name::XorName;
+
+/// How long a holder has to serve a read before the next one is asked instead.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A blob read sent to one holder at a time, until one serves it.
+pub(super) struct PendingRead {
+    pub(super) address: BlobAddress,
+    origin: EndUser,
+    /// The holder asked, with when it was asked.
+    holder: XorName,
+    asked_at: Instant,
+    /// The holders to fall back on, best first.
+    fallbacks: VecDeque<XorName>,
+}
+
+/// Where a read goes after its holder failed to serve it.
+#[derive(Debug, PartialEq)]
+pub(super) enum ReadFallback {
+    /// The next holder to ask.
+    Next {
+        address: BlobAddress,
+        origin: EndUser,
+        holder: XorName,
+    },
+    /// No holder is left to ask, so the read failed.
+    Exhausted {
+        address: BlobAddress,
+        origin: EndUser,
+    },
+}
+
+/// Tracks the blob reads sent to holders, by the id of the client's query.
+pub(super) struct PendingReads {
+    reads: BTreeMap<MessageId, PendingRead>,
+}
+
+impl PendingReads {
+    pub(super) fn new() -> Self {
+        Self {
+            reads: BTreeMap::new(),
+        }
+    }
+
+    /// Starts tracking a read of the given holders, best first, returning the one to ask.
+    pub(super) fn start(
+        &mut self,
+        msg_id: MessageId,
+        address: BlobAddress,
+        origin: EndUser,
+        holders: Vec<XorName>,
+    ) -> Option<XorName> {
+        let mut fallbacks: VecDeque<_> = holders.into();
+        let holder = fallbacks.pop_front()?;
+        let read = PendingRead {
+            address,
+            origin,
+            holder,
+            asked_at: Instant::now(),
+            fallbacks,
+        };
+        let _ = self.reads.insert(msg_id, read);
+        Some(holder)
+    }
+
+    /// The read the holder was asked to serve, if any.
+    pub(super) fn asked(&self, msg_id: &MessageId, holder: &XorName) -> Option<&PendingRead> {
+        self.reads.get(msg_id).filter(|read| &read.holder == holder)
+    }
+
+    /// Records the holder asked serving the read, returning how long it took.
+    pub(super) fn served(&mut self, msg_id: &MessageId, holder: &XorName) -> Option<Duration> {
+        let asked_at = self.asked(msg_id, holder)?.asked_at;
+        let _ = self.reads.remove(msg_id);
+        Some(asked_at.elapsed())
+    }
+
+    /// Moves a read on from the holder asked, which failed to serve it.  The read is forgotten
+    /// once no holder is left.
+    pub(super) fn fail(&mut self, msg_id: &MessageId, holder: &XorName) -> Option<ReadFallback> {
+        let _ = self.asked(msg_id, holder)?;
+        let read = self.reads.get_mut(msg_id)?;
+        match read.fallbacks.pop_front() {
+            Some(next) => {
+                read.holder = next;
+                read.asked_at = Instant::now();
+                Some(ReadFallback::Next {
+                    address: read.address,
+                    origin: read.origin,
+                    holder: next,
+                })
+            }
+            None => {
+                let read = self.reads.remove(msg_id)?;
+                Some(ReadFallback::Exhausted {
+                    address: read.address,
+                    origin: read.origin,
+                })
+            }
+        }
+    }
+
+    /// The reads whose holder didn't serve them in time, with that holder.
+    pub(super) fn expired(&self) -> Vec<(MessageId, XorName)> {
+        self.reads
+            .iter()
+            .filter(|(_, read)| read.asked_at.elapsed() >= READ_TIMEOUT)
+            .map(|(msg_id, read)| (*msg_id, read.holder))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bls::SecretKey;
+    use sn_data_types::{Blob, PublicBlob, PublicKey};
+
+    #[test]
+    fn read_falls_back_on_the_next_holder() {
+        let mut reads = PendingReads::new();
+        let msg_id = MessageId::new();
+        let origin = EndUser::AllClients(PublicKey::from(SecretKey::random().public_key()));
+        let address = *Blob::Public(PublicBlob::new(b"read".to_vec())).address();
+        let holders: Vec<_> = (0..2).map(|_| XorName::random()).collect();
+
+        assert_eq!(
+            reads.start(msg_id, address, origin, holders.clone()),
+            Some(holders[0])
+        );
+        assert!(reads.fail(&msg_id, &holders[1]).is_none());
+        assert_eq!(
+            reads.fail(&msg_id, &holders[0]),
+            Some(ReadFallback::Next {
+                address,
+                origin,
+                holder: holders[1]
+            })
+        );
+        assert!(reads.served(&msg_id, &holders[0]).is_none());
+        assert_eq!(
+            reads.fail(&msg_id, &holders[1]),
+            Some(ReadFallback::Exhausted { address, origin })
+        );
+        assert!(reads.asked(&msg_id, &holders[1]).is_none());
+
+        let _ = reads.start(msg_id, address, origin, holders.clone());
+        assert!(reads.served(&msg_id, &holders[0]).is_some());
+        assert!(reads.fail(&msg_id, &holders[0]).is_none());
+        assert!(reads.expired().is_empty());
+    }
+}
@@ -0,0 +1,329 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use sn_data_types::{Blob, BlobAddress};
+use sn_messaging::{client::Error as ErrorMessage, EndUser, MessageId};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{Duration, Instant},
+};
+use xor_name::XorName;
+
+/// How long an adult has to acknowledge storing a chunk before another one is asked instead.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long an adult's acknowledgement is still recorded after its time ran out, so that the
+/// copies stored late are recorded as such.
+const LATE_ACK_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// A blob being stored, until every adult asked to hold it answered.
+pub(super) struct PendingWrite {
+    /// The blob, kept to ask other adults should some fail to store it.
+    pub(super) data: Blob,
+    pub(super) origin: EndUser,
+    /// The number of acknowledged copies the write succeeds with.
+    quorum: usize,
+    /// The adults asked to store it that didn't answer yet, with when they were asked.
+    awaiting: BTreeMap<XorName, Instant>,
+    /// The adults that didn't answer in time, but might still, with when they were asked.
+    late: BTreeMap<XorName, Instant>,
+    /// The adults that acknowledged storing it.
+    stored: BTreeSet<XorName>,
+    /// Every adult asked to store it, so that none is asked twice.
+    pub(super) asked: BTreeSet<XorName>,
+    /// The last error an adult answered with.
+    error: Option<ErrorMessage>,
+    /// Whether the client was answered.
+    answered: bool,
+}
+
+/// An adult's acknowledgement of storing a blob.
+#[derive(Debug, PartialEq)]
+pub(super) enum Ack {
+    /// Within its time, after the given latency.
+    InTime(Duration),
+    /// After its time ran out, when another adult may have been asked in its place.
+    Late,
+}
+
+/// How a write turned out, known once a quorum of copies is stored, or once it can't be.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, PartialEq)]
+pub(super) enum WriteOutcome {
+    Stored {
+        address: BlobAddress,
+        copies: usize,
+    },
+    Failed {
+        address: BlobAddress,
+        origin: EndUser,
+        error: ErrorMessage,
+    },
+}
+
+/// Tracks the blob writes sent to adults, by the id of the client's cmd, until they answer.
+pub(super) struct PendingWrites {
+    writes: BTreeMap<MessageId, PendingWrite>,
+}
+
+impl PendingWrites {
+    pub(super) fn new() -> Self {
+        Self {
+            writes: BTreeMap::new(),
+        }
+    }
+
+    /// Starts tracking a write sent to the given holders, succeeding once `quorum` of them
+    /// acknowledged it.
+    pub(super) fn start(
+        &mut self,
+        msg_id: MessageId,
+        data: Blob,
+        origin: EndUser,
+        quorum: usize,
+        holders: &BTreeSet<XorName>,
+    ) {
+        let now = Instant::now();
+        let write = PendingWrite {
+            data,
+            origin,
+            quorum,
+            awaiting: holders.iter().map(|holder| (*holder, now)).collect(),
+            late: BTreeMap::new(),
+            stored: BTreeSet::new(),
+            asked: holders.clone(),
+            error: None,
+            answered: false,
+        };
+        let _ = self.writes.insert(msg_id, write);
+    }
+
+    pub(super) fn get(&self, msg_id: &MessageId) -> Option<&PendingWrite> {
+        self.writes.get(msg_id)
+    }
+
+    /// Whether adults are still awaited to store the blob.
+    pub(super) fn is_writing(&self, address: &BlobAddress) -> bool {
+        self.writes
+            .values()
+            .any(|write| write.data.address() == address)
+    }
+
+    /// Records another adult asked to store the blob, in place of one that failed to.
+    pub(super) fn ask(&mut self, msg_id: MessageId, holder: XorName) {
+        if let Some(write) = self.writes.get_mut(&msg_id) {
+            let _ = write.asked.insert(holder);
+            let _ = write.awaiting.insert(holder, Instant::now());
+        }
+    }
+
+    /// Records an adult acknowledging it stored the blob, unless it wasn't awaited.  Copies
+    /// acknowledged after their time ran out still count, but without a latency.
+    pub(super) fn stored(&mut self, msg_id: &MessageId, holder: XorName) -> Option<Ack> {
+        let write = self.writes.get_mut(msg_id)?;
+        let ack = if let Some(asked_at) = write.awaiting.remove(&holder) {
+            Ack::InTime(asked_at.elapsed())
+        } else if write.late.remove(&holder).is_some() {
+            Ack::Late
+        } else {
+            return None;
+        };
+        let _ = write.stored.insert(holder);
+        Some(ack)
+    }
+
+    /// Records an adult failing to store the blob.  Returns whether it was awaited.
+    pub(super) fn failed(
+        &mut self,
+        msg_id: &MessageId,
+        holder: XorName,
+        error: ErrorMessage,
+    ) -> bool {
+        let write = match self.writes.get_mut(msg_id) {
+            Some(write) => write,
+            None => return false,
+        };
+        if write.awaiting.remove(&holder).is_some() {
+            write.error = Some(error);
+            return true;
+        }
+        let _ = write.late.remove(&holder);
+        false
+    }
+
+    /// Stops waiting for the adults that didn't answer in time, returning them.  Their late
+    /// acknowledgements are recorded for a while longer, then the writes answered are forgotten.
+    pub(super) fn expire(&mut self) -> Vec<(MessageId, XorName)> {
+        let mut expired = vec![];
+        for (msg_id, write) in &mut self.writes {
+            let timed_out: Vec<_> = write
+                .awaiting
+                .iter()
+                .filter(|(_, asked_at)| asked_at.elapsed() >= WRITE_TIMEOUT)
+                .map(|(holder, asked_at)| (*holder, *asked_at))
+                .collect();
+            for (holder, asked_at) in timed_out {
+                let _ = write.awaiting.remove(&holder);
+                let _ = write.late.insert(holder, asked_at);
+                expired.push((*msg_id, holder));
+            }
+            write
+                .late
+                .retain(|_, asked_at| asked_at.elapsed() < LATE_ACK_TIMEOUT);
+        }
+        self.writes.retain(|_, write| !write.is_done());
+        expired
+    }
+
+    /// The outcome of a write the client wasn't answered about yet, if known.  The write is
+    /// forgotten once no more adults are awaited, also late.
+    pub(super) fn outcome(&mut self, msg_id: &MessageId) -> Option<WriteOutcome> {
+        let write = self.writes.get_mut(msg_id)?;
+        let address = *write.data.address();
+        let outcome = if write.answered {
+            None
+        } else if write.stored.len() >= write.quorum {
+            write.answered = true;
+            Some(WriteOutcome::Stored {
+                address,
+                copies: write.stored.len(),
+            })
+        } else if write.awaiting.is_empty() {
+            write.answered = true;
+            Some(WriteOutcome::Failed {
+                address,
+                origin: write.origin,
+                error: write
+                    .error
+                    .take()
+                    .unwrap_or(ErrorMessage::FailedToWriteFile),
+            })
+        } else {
+            None
+        };
+        if write.is_done() {
+            let _ = self.writes.remove(msg_id);
+        }
+        outcome
+    }
+}
+
+impl PendingWrite {
+    // Whether the client was answered, and no adult's acknowledgement is expected anymore.
+    fn is_done(&self) -> bool {
+        self.answered && self.awaiting.is_empty() && self.late.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bls::SecretKey;
+    use sn_data_types::{PublicBlob, PublicKey};
+
+    fn origin() -> EndUser {
+        EndUser::AllClients(PublicKey::from(SecretKey::random().public_key()))
+    }
+
+    #[test]
+    fn write_succeeds_once_a_quorum_of_copies_is_stored() {
+        let mut writes = PendingWrites::new();
+        let msg_id = MessageId::new();
+        let blob = Blob::Public(PublicBlob::new(b"written".to_vec()));
+        let holders: BTreeSet<_> = (0..3).map(|_| XorName::random()).collect();
+        let mut holder = holders.iter().copied();
+        writes.start(msg_id, blob.clone(), origin(), 2, &holders);
+
+        assert!(writes.stored(&msg_id, XorName::random()).is_none());
+        assert!(writes.stored(&msg_id, holder.next().unwrap()).is_some());
+        assert_eq!(writes.outcome(&msg_id), None);
+        assert!(writes.failed(
+            &msg_id,
+            holder.next().unwrap(),
+            ErrorMessage::NotEnoughSpace
+        ));
+        assert_eq!(writes.outcome(&msg_id), None);
+
+        let replacement = XorName::random();
+        writes.ask(msg_id, replacement);
+        assert!(writes.stored(&msg_id, replacement).is_some());
+        assert_eq!(
+            writes.outcome(&msg_id),
+            Some(WriteOutcome::Stored {
+                address: *blob.address(),
+                copies: 2
+            })
+        );
+
+        // the client is answered once, and the last copy still awaited
+        assert!(writes.stored(&msg_id, holder.next().unwrap()).is_some());
+        assert_eq!(writes.outcome(&msg_id), None);
+        assert!(writes.get(&msg_id).is_none());
+    }
+
+    #[test]
+    fn write_fails_once_no_quorum_can_be_reached() {
+        let mut writes = PendingWrites::new();
+        let msg_id = MessageId::new();
+        let origin = origin();
+        let blob = Blob::Public(PublicBlob::new(b"rejected".to_vec()));
+        let holders: BTreeSet<_> = (0..2).map(|_| XorName::random()).collect();
+        writes.start(msg_id, blob.clone(), origin, 2, &holders);
+
+        for holder in &holders {
+            assert!(writes.failed(&msg_id, *holder, ErrorMessage::NotEnoughSpace));
+        }
+        assert_eq!(
+            writes.outcome(&msg_id),
+            Some(WriteOutcome::Failed {
+                address: *blob.address(),
+                origin,
+                error: ErrorMessage::NotEnoughSpace,
+            })
+        );
+        assert!(writes.get(&msg_id).is_none());
+        assert!(writes.expire().is_empty());
+    }
+
+    #[test]
+    fn late_copies_are_recorded_without_a_latency() {
+        let mut writes = PendingWrites::new();
+        let msg_id = MessageId::new();
+        let origin = origin();
+        let blob = Blob::Public(PublicBlob::new(b"late".to_vec()));
+        let holder = XorName::random();
+        writes.start(
+            msg_id,
+            blob.clone(),
+            origin,
+            1,
+            &std::iter::once(holder).collect(),
+        );
+        let asked_at = Instant::now() - WRITE_TIMEOUT;
+        let _ = writes
+            .writes
+            .get_mut(&msg_id)
+            .and_then(|write| write.awaiting.insert(holder, asked_at));
+
+        assert_eq!(writes.expire(), vec![(msg_id, holder)]);
+        assert_eq!(
+            writes.outcome(&msg_id),
+            Some(WriteOutcome::Failed {
+                address: *blob.address(),
+                origin,
+                error: ErrorMessage::FailedToWriteFile,
+            })
+        );
+        // still recorded once the client was answered
+        assert!(writes.get(&msg_id).is_some());
+        assert_eq!(writes.stored(&msg_id, XorName::random()), None);
+        assert_eq!(writes.stored(&msg_id, holder), Some(Ack::Late));
+        assert_eq!(writes.stored(&msg_id, holder), None);
+        assert_eq!(writes.outcome(&msg_id), None);
+        assert!(writes.get(&msg_id).is_none());
+    }
+}
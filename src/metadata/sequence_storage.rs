@@ -7,12 +7,12 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
-    chunk_store::{SequenceChunkStore, UsedSpace},
+    chunk_store::{ChunkStoreConfig, SequenceChunkStore, UsedSpace},
     error::convert_to_error_message,
     node_ops::{NodeDuty, OutgoingMsg},
     Error, Network, Result,
 };
-use log::info;
+use log::{info, warn};
 use sn_data_types::{
     Error as DtError, Sequence, SequenceAction, SequenceAddress, SequenceEntry, SequenceIndex,
     SequenceOp, SequenceUser,
@@ -33,11 +33,27 @@ pub(super) struct SequenceStorage {
 }
 
 impl SequenceStorage {
-    pub(super) async fn new(path: &Path, used_space: UsedSpace) -> Result<Self> {
-        let chunks = SequenceChunkStore::new(path, used_space).await?;
+    pub(super) async fn new(
+        path: &Path,
+        used_space: UsedSpace,
+        store_config: &ChunkStoreConfig,
+    ) -> Result<Self> {
+        let chunks = SequenceChunkStore::new(path, used_space, store_config).await?;
         Ok(Self { chunks })
     }
 
+    /// Verifies the next `count` stored chunks, quarantining corrupt ones.
+    pub(super) async fn scrub(&mut self, count: usize) -> Result<()> {
+        for address in self.chunks.scrub(count).await? {
+            warn!("{}: Quarantined corrupt chunk {:?}", self, address);
+        }
+        Ok(())
+    }
+
+    pub(super) async fn reconcile_used_space(&mut self) -> Result<()> {
+        self.chunks.reconcile_used_space().await
+    }
+
     pub(super) async fn read(
         &self,
         read: &SequenceRead,
@@ -83,7 +99,7 @@ impl SequenceStorage {
         msg_id: MessageId,
         origin: EndUser,
     ) -> Result<NodeDuty> {
-        let result = if self.chunks.has(data.address()) {
+        let result = if self.chunks.has(data.address()).await {
             Err(Error::DataExists)
         } else {
             self.chunks.put(&data).await
@@ -97,7 +113,7 @@ impl SequenceStorage {
         msg_id: MessageId,
         origin: EndUser,
     ) -> Result<NodeDuty> {
-        let result = match self.get_chunk(address, SequenceAction::Read, origin) {
+        let result = match self.get_chunk(address, SequenceAction::Read, origin).await {
             Ok(res) => Ok(res),
             Err(error) => Err(convert_to_error_message(error)?),
         };
@@ -114,13 +130,13 @@ impl SequenceStorage {
         }))
     }
 
-    fn get_chunk(
+    async fn get_chunk(
         &self,
         address: SequenceAddress,
         action: SequenceAction,
         origin: EndUser,
     ) -> Result<Sequence> {
-        let data = self.chunks.get(&address)?;
+        let data = self.chunks.get(&address).await?;
         data.check_permission(action, Some(*origin.id()))?;
         Ok(data)
     }
@@ -131,7 +147,7 @@ impl SequenceStorage {
         msg_id: MessageId,
         origin: EndUser,
     ) -> Result<NodeDuty> {
-        let result = match self.chunks.get(&address).and_then(|sequence| {
+        let result = match self.chunks.get(&address).await.and_then(|sequence| {
             // TODO - Sequence::check_permission() doesn't support Delete yet in safe-nd
             if sequence.address().is_public() {
                 return Err(Error::InvalidMessage(
@@ -165,6 +181,7 @@ impl SequenceStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(address, SequenceAction::Read, origin)
+            .await
             .and_then(|sequence| {
                 sequence
                     .in_range(range.0, range.1, Some(*origin.id()))?
@@ -194,6 +211,7 @@ impl SequenceStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(address, SequenceAction::Read, origin)
+            .await
             .and_then(|sequence| match sequence.last_entry(Some(*origin.id()))? {
                 Some(entry) => Ok((sequence.len(Some(*origin.id()))? - 1, entry.to_vec())),
                 None => Err(Error::NetworkData(DtError::NoSuchEntry)),
@@ -222,6 +240,7 @@ impl SequenceStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(address, SequenceAction::Read, origin)
+            .await
             .and_then(|sequence| {
                 if sequence.is_public() {
                     let policy = sequence.public_policy()?;
@@ -256,6 +275,7 @@ impl SequenceStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(address, SequenceAction::Read, origin)
+            .await
             .and_then(|sequence| {
                 sequence
                     .permissions(user, Some(*origin.id()))
@@ -285,6 +305,7 @@ impl SequenceStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(address, SequenceAction::Read, origin)
+            .await
             .and_then(|sequence| {
                 let res = if sequence.is_public() {
                     let policy = sequence.public_policy()?;
@@ -318,6 +339,7 @@ impl SequenceStorage {
     ) -> Result<NodeDuty> {
         let result = match self
             .get_chunk(address, SequenceAction::Read, origin)
+            .await
             .and_then(|sequence| {
                 let res = if !sequence.is_public() {
                     let policy = sequence.private_policy(Some(*origin.id()))?;
@@ -381,7 +403,7 @@ impl SequenceStorage {
         F: FnOnce(Sequence) -> Result<Sequence>,
     {
         info!("Getting Sequence chunk for Edit");
-        let result = self.get_chunk(address, action, origin)?;
+        let result = self.get_chunk(address, action, origin).await?;
         let sequence = write_fn(result)?;
         info!("Edited Sequence chunk successfully");
         self.chunks.put(&sequence).await
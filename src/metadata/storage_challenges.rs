@@ -0,0 +1,304 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use log::warn;
+use sn_data_types::BlobAddress;
+use sn_messaging::MessageId;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{Duration, Instant},
+};
+use xor_name::XorName;
+
+/// Fewest holders a chunk is challenged with, so that a majority of them agreeing on a proof takes
+/// more than one holder.  Chunks with fewer copies are left to be replicated by the audit.
+const MIN_CHALLENGED_HOLDERS: usize = 3;
+
+/// The results of the storage challenges of a holder.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChallengeResults {
+    /// Challenges answered with the proof the other holders of the chunk agreed on.
+    pub passed: u64,
+    /// Challenges answered with another proof or none, or not answered in time.
+    pub failed: u64,
+}
+
+/// The outcome of a challenge for one of the holders challenged.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct Verdict {
+    pub(super) holder: XorName,
+    pub(super) address: BlobAddress,
+    pub(super) passed: bool,
+    /// Time taken by the holder to answer, if it did.
+    pub(super) latency: Option<Duration>,
+}
+
+// The holders of a chunk challenged with the same nonce.
+struct Challenge {
+    address: BlobAddress,
+    sent_at: Instant,
+    holders: BTreeSet<XorName>,
+    /// The proof each holder answered with, if any, and when, by holder.
+    proofs: BTreeMap<XorName, (Option<XorName>, Instant)>,
+}
+
+/// Challenges that have holders prove they still hold a chunk recorded for them, by sending a
+/// hash of a nonce and the chunk's bytes.  As elders don't keep the chunks, the holders of a chunk
+/// are challenged with the same nonce, and the proof sent by a majority of them is the right one.
+pub(super) struct StorageChallenges {
+    pending: BTreeMap<MessageId, Challenge>,
+    results: BTreeMap<XorName, ChallengeResults>,
+}
+
+impl StorageChallenges {
+    pub(super) fn new() -> Self {
+        Self {
+            pending: BTreeMap::new(),
+            results: BTreeMap::new(),
+        }
+    }
+
+    /// Starts a challenge of the holders of a chunk, unless they are too few for their proofs to
+    /// be checked against each other.  Returns whether it was started.
+    pub(super) fn start(
+        &mut self,
+        msg_id: MessageId,
+        address: BlobAddress,
+        holders: BTreeSet<XorName>,
+    ) -> bool {
+        if holders.len() < MIN_CHALLENGED_HOLDERS {
+            return false;
+        }
+        let _ = self.pending.insert(
+            msg_id,
+            Challenge {
+                address,
+                sent_at: Instant::now(),
+                holders,
+                proofs: BTreeMap::new(),
+            },
+        );
+        true
+    }
+
+    /// Whether a challenge for the chunk is pending.
+    pub(super) fn is_challenged(&self, address: &BlobAddress) -> bool {
+        self.pending
+            .values()
+            .any(|challenge| &challenge.address == address)
+    }
+
+    /// Records the proof `holder` answered a challenge with, `None` if it doesn't hold the chunk.
+    /// Once all the holders challenged answered, returns the verdicts for them.
+    pub(super) fn answer(
+        &mut self,
+        msg_id: &MessageId,
+        holder: XorName,
+        proof: Option<XorName>,
+    ) -> Vec<Verdict> {
+        let challenge = match self.pending.get_mut(msg_id) {
+            Some(challenge) if challenge.holders.contains(&holder) => challenge,
+            _ => return vec![],
+        };
+        let _ = challenge
+            .proofs
+            .entry(holder)
+            .or_insert((proof, Instant::now()));
+        if challenge.proofs.len() < challenge.holders.len() {
+            return vec![];
+        }
+        match self.pending.remove(msg_id) {
+            Some(challenge) => self.judge(challenge),
+            None => vec![],
+        }
+    }
+
+    /// Ends the challenges still pending, the holders that didn't answer them failing.
+    pub(super) fn expire(&mut self) -> Vec<Verdict> {
+        let expired = std::mem::take(&mut self.pending);
+        expired
+            .into_values()
+            .flat_map(|challenge| self.judge(challenge))
+            .collect()
+    }
+
+    // Holders that answered with the proof a majority of the holders sent pass, and the others
+    // fail.  Without a majority the answers can't be told apart, so only missing ones fail.
+    fn judge(&mut self, challenge: Challenge) -> Vec<Verdict> {
+        let mut counts = BTreeMap::new();
+        for (proof, _) in challenge.proofs.values() {
+            if let Some(proof) = proof {
+                *counts.entry(*proof).or_insert(0) += 1;
+            }
+        }
+        let majority = counts
+            .into_iter()
+            .find(|(_, count)| 2 * count > challenge.holders.len())
+            .map(|(proof, _)| proof);
+        if majority.is_none() && !challenge.proofs.is_empty() {
+            warn!(
+                "No majority of the holders of {:?} agreed on a proof",
+                challenge.address
+            );
+        }
+        let mut verdicts = vec![];
+        for holder in &challenge.holders {
+            let (passed, latency) = match challenge.proofs.get(holder) {
+                Some((proof, answered_at)) => {
+                    let latency = answered_at.duration_since(challenge.sent_at);
+                    match majority {
+                        Some(majority) => (*proof == Some(majority), Some(latency)),
+                        None => continue,
+                    }
+                }
+                None => {
+                    warn!(
+                        "Holder {:?} didn't answer challenge for {:?}",
+                        holder, challenge.address
+                    );
+                    (false, None)
+                }
+            };
+            if !passed && latency.is_some() {
+                warn!(
+                    "Holder {:?} failed challenge for {:?}",
+                    holder, challenge.address
+                );
+            }
+            let results = self.results.entry(*holder).or_default();
+            if passed {
+                results.passed += 1;
+            } else {
+                results.failed += 1;
+            }
+            verdicts.push(Verdict {
+                holder: *holder,
+                address: challenge.address,
+                passed,
+                latency,
+            });
+        }
+        verdicts
+    }
+
+    /// The results of the challenges of a holder.
+    pub(super) fn results(&self, holder: &XorName) -> ChallengeResults {
+        self.results.get(holder).copied().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holders(count: usize) -> Vec<XorName> {
+        let mut holders: Vec<_> = (0..count).map(|_| XorName::random()).collect();
+        holders.sort();
+        holders
+    }
+
+    #[test]
+    fn proofs_are_checked_against_the_other_holders() {
+        let mut challenges = StorageChallenges::new();
+        let address = BlobAddress::Public(XorName::random());
+        let holders = holders(5);
+        let (proof, other) = (XorName::random(), XorName::random());
+        let msg_id = MessageId::new();
+        assert!(challenges.start(msg_id, address, holders.iter().copied().collect()));
+        assert!(challenges.is_challenged(&address));
+
+        assert!(challenges
+            .answer(&msg_id, holders[0], Some(proof))
+            .is_empty());
+        assert!(challenges
+            .answer(&msg_id, holders[1], Some(other))
+            .is_empty());
+        // answers from others don't count
+        assert!(challenges
+            .answer(&msg_id, XorName::random(), Some(proof))
+            .is_empty());
+        assert!(challenges.answer(&msg_id, holders[2], None).is_empty());
+        assert!(challenges
+            .answer(&msg_id, holders[3], Some(proof))
+            .is_empty());
+        let verdicts = challenges.answer(&msg_id, holders[4], Some(proof));
+        assert!(!challenges.is_challenged(&address));
+
+        let passed: Vec<_> = verdicts
+            .iter()
+            .map(|verdict| (verdict.holder, verdict.passed))
+            .collect();
+        assert_eq!(
+            passed,
+            vec![
+                (holders[0], true),
+                (holders[1], false),
+                (holders[2], false),
+                (holders[3], true),
+                (holders[4], true)
+            ]
+        );
+        assert_eq!(challenges.results(&holders[0]).passed, 1);
+        assert_eq!(challenges.results(&holders[1]).failed, 1);
+    }
+
+    #[test]
+    fn unanswered_challenges_fail() {
+        let mut challenges = StorageChallenges::new();
+        let address = BlobAddress::Public(XorName::random());
+        let holders = holders(3);
+        let msg_id = MessageId::new();
+        assert!(challenges.start(msg_id, address, holders.iter().copied().collect()));
+        assert!(challenges
+            .answer(&msg_id, holders[0], Some(XorName::random()))
+            .is_empty());
+
+        // a lone answer is no majority, so only the missing ones fail
+        let verdicts = challenges.expire();
+        assert_eq!(
+            verdicts,
+            vec![
+                Verdict {
+                    holder: holders[1],
+                    address,
+                    passed: false,
+                    latency: None,
+                },
+                Verdict {
+                    holder: holders[2],
+                    address,
+                    passed: false,
+                    latency: None,
+                }
+            ]
+        );
+        assert!(challenges
+            .answer(&msg_id, holders[1], Some(XorName::random()))
+            .is_empty());
+        assert_eq!(challenges.results(&holders[0]), ChallengeResults::default());
+        assert_eq!(challenges.results(&holders[1]).failed, 1);
+    }
+
+    #[test]
+    fn chunks_with_too_few_holders_are_not_challenged() {
+        let mut challenges = StorageChallenges::new();
+        let address = BlobAddress::Public(XorName::random());
+        let msg_id = MessageId::new();
+
+        // a single holder's proof would be its own majority
+        let holder = XorName::random();
+        assert!(!challenges.start(msg_id, address, std::iter::once(holder).collect()));
+        assert!(!challenges.is_challenged(&address));
+        assert!(challenges
+            .answer(&msg_id, holder, Some(XorName::random()))
+            .is_empty());
+        assert!(!challenges.start(msg_id, address, holders(2).into_iter().collect()));
+        assert!(challenges.expire().is_empty());
+        assert_eq!(challenges.results(&holder), ChallengeResults::default());
+    }
+}
@@ -6,7 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::messaging::{send, send_to_nodes};
+use super::messaging::{send, send_node_msg, send_to_nodes};
 use crate::{
     chunks::Chunks,
     metadata::Metadata,
@@ -22,7 +22,7 @@ use crate::{
     Error, Node, Result,
 };
 use dashmap::DashMap;
-use log::{debug, info};
+use log::{debug, info, warn};
 use sn_data_types::{CreditAgreementProof, CreditId, PublicKey, SectionElders, WalletHistory};
 use sn_messaging::{
     client::{Message, NodeCmd, NodeQuery, Query},
@@ -151,10 +151,7 @@ impl Node {
 
                 let elder = self.role.as_elder_mut()?;
                 elder.section_funds.remove_node_wallet(name);
-                elder
-                    .transfers
-                    .decrease_full_node_count_if_present(name)
-                    .await?;
+                elder.transfers.remove_node(name).await?;
 
                 ops.extend(elder.meta_data.trigger_chunk_replication(name).await?);
 
@@ -173,6 +170,7 @@ impl Node {
                         self.node_info.node_name,
                         self.node_info.root_dir.as_path(),
                         self.used_space.clone(),
+                        &self.store_config,
                     )
                     .await?,
                 });
@@ -262,9 +260,8 @@ impl Node {
                     .matches(&&data_section_addr)
                 {
                     let adult = self.role.as_adult_mut()?;
-                    let read = adult.chunks.read(&read, msg_id, origin).await?;
-                    let mut ops = adult.chunks.check_storage().await?;
-                    ops.insert(0, read);
+                    let mut ops = adult.chunks.read(&read, msg_id, origin).await?;
+                    ops.extend(adult.chunks.check_storage().await?);
                     Ok(ops)
                 } else {
                     Ok(vec![NodeDuty::Send(OutgoingMsg {
@@ -291,19 +288,67 @@ impl Node {
                 let adult = self.role.as_adult_mut()?;
                 Ok(vec![adult.chunks.write(&write, msg_id, origin).await?])
             }
+            NodeDuty::CheckStorage => {
+                let (mut duties, used_space_ratio) = match &mut self.role {
+                    Role::Adult(adult) => (
+                        adult.chunks.check_storage().await?,
+                        adult.chunks.used_space_ratio().await,
+                    ),
+                    Role::Elder(_) => return Ok(vec![]),
+                };
+                duties.push(self.report_storage(used_space_ratio).await?);
+                Ok(duties)
+            }
             NodeDuty::ReachingMaxCapacity => Ok(vec![self.notify_section_of_our_storage().await?]),
+            NodeDuty::ReachingFreeCapacity => {
+                info!("Storage usage is back under the available storage ratio");
+                Ok(vec![self.notify_section_of_available_storage().await?])
+            }
             //
             // ------- Misc ------------
             NodeDuty::IncrementFullNodeCount { node_id } => {
                 let elder = self.role.as_elder_mut()?;
                 elder.transfers.increase_full_node_count(node_id).await?;
+                info!(
+                    "Section free capacity: {:.0}%",
+                    100.0 * elder.transfers.free_capacity().await
+                );
                 // Accept a new node in place for the full node.
                 Ok(vec![NodeDuty::SetNodeJoinsAllowed(true)])
             }
+            NodeDuty::DecrementFullNodeCount { node_id } => {
+                let elder = self.role.as_elder_mut()?;
+                elder.transfers.storage_available(node_id.into()).await?;
+                info!(
+                    "Section free capacity: {:.0}%",
+                    100.0 * elder.transfers.free_capacity().await
+                );
+                Ok(vec![])
+            }
+            NodeDuty::RecordUsedSpaceRatio {
+                node_id,
+                used_space_ratio,
+            } => {
+                let elder = self.role.as_elder_mut()?;
+                let now_full = elder
+                    .transfers
+                    .record_used_space_ratio(node_id.into(), used_space_ratio)
+                    .await?;
+                if now_full {
+                    // Accept a new node in place for the full node.
+                    Ok(vec![NodeDuty::SetNodeJoinsAllowed(true)])
+                } else {
+                    Ok(vec![])
+                }
+            }
             NodeDuty::Send(msg) => {
                 send(msg, &self.network_api).await?;
                 Ok(vec![])
             }
+            NodeDuty::SendNodeMsg(msg) => {
+                send_node_msg(msg, &self.network_api).await?;
+                Ok(vec![])
+            }
             NodeDuty::SendToNodes { targets, msg } => {
                 send_to_nodes(targets, &msg, &self.network_api).await?;
                 Ok(vec![])
@@ -324,7 +369,7 @@ impl Node {
                     .await
                     .matches(&data_section_addr)
                 {
-                    let elder = self.role.as_elder()?;
+                    let elder = self.role.as_elder_mut()?;
                     Ok(vec![elder.meta_data.read(query, id, origin).await?])
                 } else {
                     Ok(vec![NodeDuty::Send(OutgoingMsg {
@@ -358,14 +403,30 @@ impl Node {
                 address,
                 id,
             } => {
-                let adult = self.role.as_adult()?;
-                Ok(vec![
-                    adult
-                        .chunks
-                        .replicate_chunk(address, current_holders, id)
-                        .await?,
-                ])
+                let adult = self.role.as_adult_mut()?;
+                adult
+                    .chunks
+                    .replicate_chunk(address, current_holders, id)
+                    .await
+            }
+            NodeDuty::ReplicationFetchFailed {
+                correlation_id,
+                holder,
+            } => {
+                let adult = self.role.as_adult_mut()?;
+                adult
+                    .chunks
+                    .replication_fetch_failed(correlation_id, holder)
+                    .await
             }
+            NodeDuty::CheckPendingReplications => match &mut self.role {
+                Role::Adult(adult) => adult.chunks.check_pending_replications().await,
+                Role::Elder(elder) => {
+                    let mut duties = elder.meta_data.check_pending_writes().await?;
+                    duties.extend(elder.meta_data.check_pending_reads());
+                    Ok(duties)
+                }
+            },
             NodeDuty::GetChunkForReplication {
                 address,
                 new_holder,
@@ -382,20 +443,194 @@ impl Node {
             NodeDuty::StoreChunkForReplication {
                 data,
                 correlation_id,
+                holder,
             } => {
-                // Recreate original MessageId from Section
-                let msg_id = MessageId::combine(vec![
-                    *data.address().name(),
-                    self.network_api.our_name().await,
-                ]);
-                if msg_id == correlation_id {
-                    let adult = self.role.as_adult_mut()?;
-                    Ok(vec![adult.chunks.store_replicated_chunk(data).await?])
-                } else {
-                    log::warn!("Invalid message ID");
+                let adult = self.role.as_adult_mut()?;
+                adult
+                    .chunks
+                    .store_replicated_chunk(data, correlation_id, holder)
+                    .await
+            }
+            NodeDuty::ChallengeHolders => match &mut self.role {
+                Role::Elder(elder) => elder.meta_data.challenge_holders().await,
+                Role::Adult(_) => Ok(vec![]),
+            },
+            NodeDuty::AnswerStorageChallenge {
+                address,
+                nonce,
+                elder,
+                correlation_id,
+            } => {
+                let elders = self.network_api.our_elder_names().await;
+                match &self.role {
+                    Role::Adult(adult) if elders.contains(&elder) => Ok(vec![
+                        adult
+                            .chunks
+                            .answer_challenge(address, nonce, correlation_id, elder)
+                            .await,
+                    ]),
+                    _ => Ok(vec![]),
+                }
+            }
+            NodeDuty::ProcessStorageProof {
+                holder,
+                proof,
+                correlation_id,
+            } => match &mut self.role {
+                Role::Elder(elder) => {
+                    elder
+                        .meta_data
+                        .process_storage_proof(correlation_id, holder, proof)
+                        .await
+                }
+                Role::Adult(_) => Ok(vec![]),
+            },
+            NodeDuty::AuditReplication => match &mut self.role {
+                Role::Elder(elder) => elder.meta_data.audit_replication().await,
+                Role::Adult(_) => Ok(vec![]),
+            },
+            NodeDuty::ScrubChunks => {
+                let count = self.store_config.scrub_rate as usize;
+                match &mut self.role {
+                    Role::Adult(adult) => adult.chunks.scrub(count).await,
+                    Role::Elder(elder) => elder.meta_data.scrub(count).await,
+                }
+            }
+            NodeDuty::ReconcileUsedSpace => {
+                match &mut self.role {
+                    Role::Adult(adult) => adult.chunks.reconcile_used_space().await?,
+                    Role::Elder(elder) => elder.meta_data.reconcile_used_space().await?,
+                }
+                Ok(vec![])
+            }
+            NodeDuty::SetMaxCapacity(max_capacity) => {
+                info!("Setting max capacity to {} bytes", max_capacity);
+                self.used_space.set_max_capacity(max_capacity).await;
+                match &mut self.role {
+                    Role::Adult(adult) => adult.chunks.check_storage().await,
+                    Role::Elder(_) => Ok(vec![]),
+                }
+            }
+            NodeDuty::LogAdultScores => {
+                if let Role::Elder(elder) = &self.role {
+                    for (adult, score) in elder.meta_data.adult_scores() {
+                        if score.is_failing() {
+                            warn!("Adult {:?} is failing chunk operations: {:?}", adult, score);
+                        } else {
+                            info!("Adult {:?}: {:?}", adult, score);
+                        }
+                    }
+                }
+                Ok(vec![])
+            }
+            NodeDuty::ProcessCorruptCopy {
+                address,
+                reporter,
+                holder,
+            } => {
+                let elder = self.role.as_elder_mut()?;
+                elder
+                    .meta_data
+                    .replace_corrupt_copy(address, reporter, holder)
+                    .await
+            }
+            NodeDuty::ProcessLostChunk { address, holder } => {
+                let elder = self.role.as_elder_mut()?;
+                elder.meta_data.replicate_lost_chunk(address, holder).await
+            }
+            NodeDuty::ProcessChunkServed {
+                address,
+                holder,
+                correlation_id,
+            } => {
+                if let Role::Elder(elder) = &mut self.role {
+                    elder
+                        .meta_data
+                        .chunk_served(address, correlation_id, holder);
+                }
+                Ok(vec![])
+            }
+            NodeDuty::ProcessChunkReadError {
+                address,
+                holder,
+                error,
+                correlation_id,
+            } => match &mut self.role {
+                Role::Elder(elder) => {
+                    elder
+                        .meta_data
+                        .chunk_read_failed(address, correlation_id, holder, error)
+                        .await
+                }
+                Role::Adult(_) => Ok(vec![]),
+            },
+            NodeDuty::ProcessChunkStored {
+                address,
+                holder,
+                correlation_id,
+            } => match &mut self.role {
+                Role::Elder(elder) => {
+                    elder
+                        .meta_data
+                        .chunk_stored(address, correlation_id, holder)
+                        .await
+                }
+                Role::Adult(_) => Ok(vec![]),
+            },
+            NodeDuty::ProcessChunkWriteError {
+                address,
+                holder,
+                error,
+                correlation_id,
+            } => match &mut self.role {
+                Role::Elder(elder) => {
+                    elder
+                        .meta_data
+                        .chunk_write_failed(address, correlation_id, holder, error)
+                        .await
+                }
+                Role::Adult(_) => Ok(vec![]),
+            },
+            NodeDuty::Decommission => match &mut self.role {
+                Role::Adult(adult) => {
+                    let duties = adult.chunks.decommission().await;
+                    self.leave_if_handed_off();
+                    Ok(duties)
+                }
+                Role::Elder(_) => {
+                    warn!("Only adults can be decommissioned");
                     Ok(vec![])
                 }
+            },
+            NodeDuty::RetryHandOff => match &self.role {
+                Role::Adult(adult) => Ok(adult.chunks.retry_hand_off()),
+                Role::Elder(_) => Ok(vec![]),
+            },
+            NodeDuty::HandOffChunk { address, holder } => {
+                let elder = self.role.as_elder_mut()?;
+                elder.meta_data.hand_off_chunk(address, holder).await
             }
+            NodeDuty::ConfirmHandOff { address, holder } => {
+                Ok(vec![self.hand_off_completed(address, holder)])
+            }
+            NodeDuty::CompleteHandOff { address } => match &mut self.role {
+                Role::Adult(adult) => {
+                    adult.chunks.complete_hand_off(&address);
+                    self.leave_if_handed_off();
+                    Ok(vec![])
+                }
+                Role::Elder(_) => Ok(vec![]),
+            },
+            NodeDuty::ReportReplicationCompleted {
+                address,
+                correlation_id,
+            } => Ok(vec![
+                self.replication_completed(address, correlation_id).await?,
+            ]),
+            NodeDuty::ProcessReplicationCompleted { address, node } => match &mut self.role {
+                Role::Elder(elder) => elder.meta_data.confirm_replication(address, node).await,
+                Role::Adult(_) => Ok(vec![]),
+            },
             NodeDuty::NoOp => Ok(vec![]),
         }
     }
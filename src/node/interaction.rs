@@ -7,7 +7,8 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
-    node_ops::{NodeDuties, NodeDuty, OutgoingMsg},
+    node_msg::NodeMsg,
+    node_ops::{NodeDuties, NodeDuty, OutgoingMsg, OutgoingNodeMsg},
     section_funds::{self, SectionFunds},
     transfers::{
         replica_signing::ReplicaSigningImpl,
@@ -25,8 +26,8 @@ use section_funds::{
     Credits,
 };
 use sn_data_types::{
-    ActorHistory, CreditAgreementProof, CreditId, NodeAge, PublicKey, SectionElders, Token,
-    WalletHistory,
+    ActorHistory, BlobAddress, CreditAgreementProof, CreditId, NodeAge, PublicKey, SectionElders,
+    Token, WalletHistory,
 };
 use sn_messaging::{
     client::{
@@ -109,6 +110,72 @@ impl Node {
         }))
     }
 
+    /// Tells the section that we have storage available again, after having reported it full.
+    pub(crate) async fn notify_section_of_available_storage(&self) -> Result<NodeDuty> {
+        let node_id = PublicKey::from(self.network_api.public_key().await);
+        Ok(NodeDuty::SendNodeMsg(OutgoingNodeMsg {
+            msg: NodeMsg::StorageAvailable {
+                node_id,
+                id: MessageId::new(),
+            },
+            section_source: false, // sent as single node
+            dst: DstLocation::Section(node_id.into()),
+            aggregation: Aggregation::None,
+        }))
+    }
+
+    /// Reports our used space ratio to the section.
+    pub(crate) async fn report_storage(&self, used_space_ratio: f64) -> Result<NodeDuty> {
+        let node_id = PublicKey::from(self.network_api.public_key().await);
+        Ok(NodeDuty::SendNodeMsg(OutgoingNodeMsg {
+            msg: NodeMsg::StorageReport {
+                node_id,
+                used_space_ratio,
+                id: MessageId::new(),
+            },
+            section_source: false, // sent as single node
+            dst: DstLocation::Section(node_id.into()),
+            aggregation: Aggregation::None,
+        }))
+    }
+
+    /// Reports a chunk as replicated or read at this node to the section.
+    pub(crate) async fn replication_completed(
+        &self,
+        address: BlobAddress,
+        correlation_id: MessageId,
+    ) -> Result<NodeDuty> {
+        let proof = self.network_api.sign_as_node(&address).await?;
+        Ok(NodeDuty::Send(OutgoingMsg {
+            msg: Message::NodeEvent {
+                event: NodeEvent::ReplicationCompleted {
+                    chunk: address,
+                    proof,
+                },
+                id: MessageId::new(),
+                correlation_id,
+                target_section_pk: None,
+            },
+            section_source: false, // sent as single node
+            dst: DstLocation::Section(*address.name()),
+            aggregation: Aggregation::None,
+        }))
+    }
+
+    /// Confirms to a departing holder that a chunk has been handed off.  The message is the same
+    /// from every elder, so that the holder only acts on it once a majority of them sent it.
+    pub(crate) fn hand_off_completed(&self, address: BlobAddress, holder: XorName) -> NodeDuty {
+        NodeDuty::SendNodeMsg(OutgoingNodeMsg {
+            msg: NodeMsg::HandOffCompleted {
+                address,
+                id: MessageId::combine(vec![holder, *address.name()]),
+            },
+            section_source: true,
+            dst: DstLocation::Node(holder),
+            aggregation: Aggregation::AtDestination,
+        })
+    }
+
     ///
     pub(crate) async fn register_wallet(&self) -> OutgoingMsg {
         let address = self.network_api.our_prefix().await.name();
@@ -47,17 +47,31 @@ impl Node {
 
         //
         // start handling metadata
-        let dbs = ChunkHolderDbs::new(self.node_info.path())?;
+        let dbs = ChunkHolderDbs::new(self.node_info.path(), self.store_config.encryption.clone())?;
+        let capacity = Capacity::new(dbs.clone());
         let reader = AdultReader::new(self.network_api.clone());
-        let meta_data =
-            Metadata::new(&self.node_info.path(), &self.used_space, dbs, reader).await?;
+        let meta_data = Metadata::new(
+            &self.node_info.path(),
+            &self.used_space,
+            &self.store_config,
+            &self.metadata_config,
+            dbs,
+            capacity.clone(),
+            reader,
+        )
+        .await?;
 
         //
         // start handling transfers
-        let dbs = ChunkHolderDbs::new(self.node_info.root_dir.as_path())?;
-        let rate_limit = RateLimit::new(self.network_api.clone(), Capacity::new(dbs.clone()));
+        let rate_limit = RateLimit::new(self.network_api.clone(), capacity);
         let user_wallets = BTreeMap::<PublicKey, ActorHistory>::new();
-        let replicas = transfer_replicas(&self.node_info, &self.network_api, user_wallets).await?;
+        let replicas = transfer_replicas(
+            &self.node_info,
+            &self.network_api,
+            user_wallets,
+            self.store_config.encryption.clone(),
+        )
+        .await?;
         let transfers = Transfers::new(replicas, rate_limit);
 
         //
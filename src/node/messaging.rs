@@ -6,32 +6,63 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{node_ops::OutgoingMsg, Error};
+use crate::{
+    node_ops::{OutgoingMsg, OutgoingNodeMsg},
+    Error,
+};
 use crate::{Network, Result};
+use bytes::Bytes;
 use log::{error, trace};
-use sn_messaging::{client::Message, Aggregation, DstLocation, Itinerary, SrcLocation};
+use sn_messaging::{client::Message, Aggregation, DstLocation, Itinerary, MessageId, SrcLocation};
 use sn_routing::XorName;
 use std::collections::BTreeSet;
 
 pub(crate) async fn send(msg: OutgoingMsg, network: &Network) -> Result<()> {
-    let our_prefix = network.our_prefix().await;
-    trace!("{:?}, Sending msg: {:?}", our_prefix, msg);
-    let src = if msg.section_source {
-        SrcLocation::Section(our_prefix.name())
+    trace!("{:?}, Sending msg: {:?}", network.our_prefix().await, msg);
+    let itinerary = itinerary(msg.dst, msg.section_source, msg.aggregation, network).await;
+    send_bytes(itinerary, msg.msg.serialize()?, msg.id(), network).await
+}
+
+pub(crate) async fn send_node_msg(msg: OutgoingNodeMsg, network: &Network) -> Result<()> {
+    trace!(
+        "{:?}, Sending node msg: {:?}",
+        network.our_prefix().await,
+        msg
+    );
+    let itinerary = itinerary(msg.dst, msg.section_source, msg.aggregation, network).await;
+    send_bytes(itinerary, msg.msg.serialize()?, msg.id(), network).await
+}
+
+async fn itinerary(
+    dst: DstLocation,
+    section_source: bool,
+    aggregation: Aggregation,
+    network: &Network,
+) -> Itinerary {
+    let src = if section_source {
+        SrcLocation::Section(network.our_prefix().await.name())
     } else {
         SrcLocation::Node(network.our_name().await)
     };
-    let itinerary = Itinerary {
+    Itinerary {
         src,
-        dst: msg.dst,
-        aggregation: msg.aggregation,
-    };
-    let result = network.send_message(itinerary, msg.msg.serialize()?).await;
+        dst,
+        aggregation,
+    }
+}
+
+async fn send_bytes(
+    itinerary: Itinerary,
+    bytes: Bytes,
+    msg_id: MessageId,
+    network: &Network,
+) -> Result<()> {
+    let result = network.send_message(itinerary, bytes).await;
 
     result.map_or_else(
         |err| {
             error!("Unable to send msg: {:?}", err);
-            Err(Error::Logic(format!("Unable to send msg: {:?}", msg.id())))
+            Err(Error::Logic(format!("Unable to send msg: {:?}", msg_id)))
         },
         |()| Ok(()),
     )
@@ -14,15 +14,16 @@ mod split;
 
 use crate::{
     capacity::{Capacity, ChunkHolderDbs, RateLimit},
-    chunk_store::UsedSpace,
+    chunk_store::{ChunkStoreConfig, UsedSpace},
     chunks::Chunks,
     event_mapping::{map_routing_event, LazyError, Mapping, MsgContext},
-    metadata::{adult_reader::AdultReader, Metadata},
+    metadata::{adult_reader::AdultReader, Metadata, MetadataConfig},
     node_ops::{NodeDuties, NodeDuty},
     section_funds::SectionFunds,
-    state_db::store_new_reward_keypair,
+    state_db::{load_encryption_key, store_new_reward_keypair},
     transfers::get_replicas::transfer_replicas,
     transfers::Transfers,
+    utils::Command,
     Config, Error, Network, Result,
 };
 use bls::SecretKey;
@@ -41,7 +42,30 @@ use std::sync::Arc;
 use std::{
     fmt::{self, Display, Formatter},
     net::SocketAddr,
+    time::Duration,
 };
+use tokio::{
+    sync::mpsc,
+    time::{self, Instant},
+};
+
+/// How often a batch of stored chunks is verified.
+const SCRUB_INTERVAL: Duration = Duration::from_secs(60);
+/// How often the free space on the disk holding the root dir is measured.
+const FREE_SPACE_INTERVAL: Duration = Duration::from_secs(60);
+/// How often adults check for chunks being replicated that didn't arrive in time, and elders
+/// for blob writes and reads that adults didn't answer in time.
+const REPLICATION_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// How often elders challenge adults to prove they still hold their chunks.
+const CHALLENGE_INTERVAL: Duration = Duration::from_secs(600);
+/// How often elders audit a batch of chunks for missing copies.
+const AUDIT_INTERVAL: Duration = Duration::from_secs(60);
+/// How often adults report their storage usage to the section.
+const STORAGE_REPORT_INTERVAL: Duration = Duration::from_secs(300);
+/// How often adults being decommissioned ask again for the hand-off of their remaining chunks.
+const HAND_OFF_RETRY_INTERVAL: Duration = Duration::from_secs(300);
+/// How long a node may take to flush its state to disk when shutting down.
+const SHUTDOWN_DEADLINE: Duration = Duration::from_secs(30);
 
 /// Static info about the node.
 #[derive(Clone)]
@@ -121,6 +145,10 @@ pub struct Node {
     network_events: EventStream,
     node_info: NodeInfo,
     used_space: UsedSpace,
+    store_config: ChunkStoreConfig,
+    metadata_config: MetadataConfig,
+    command_sender: mpsc::UnboundedSender<Command>,
+    commands: mpsc::UnboundedReceiver<Command>,
     prefix: Prefix,
     role: Role,
 }
@@ -153,6 +181,8 @@ impl Node {
         .await;
 
         let reward_key = reward_key_task?;
+        let encryption =
+            load_encryption_key(root_dir, config.encryption_secret()?.as_deref()).await?;
         let (network_api, network_events) = Network::new(config).await?;
 
         let node_info = NodeInfo {
@@ -164,6 +194,27 @@ impl Node {
         };
 
         let used_space = UsedSpace::new(config.max_capacity());
+        if let Err(error) = used_space.refresh_free_space(node_info.path()).await {
+            warn!(
+                "Could not measure free disk space, using the max capacity: {}",
+                error
+            );
+        }
+        let store_config = ChunkStoreConfig {
+            backend: config.chunk_store_backend(),
+            shard_depth: config.chunk_shard_depth(),
+            scrub_rate: config.chunk_scrub_rate(),
+            cache_size: config.chunk_cache_size(),
+            compress: config.compress_chunks(),
+            encryption,
+            blob_quota: config.blob_quota(),
+            map_quota: config.map_quota(),
+            sequence_quota: config.sequence_quota(),
+        };
+        let metadata_config = MetadataConfig {
+            write_quorum: config.blob_write_quorum(),
+        };
+        let (command_sender, commands) = mpsc::unbounded_channel();
 
         let node = Self {
             prefix: network_api.our_prefix().await,
@@ -172,11 +223,16 @@ impl Node {
                     node_info.node_name,
                     node_info.root_dir.as_path(),
                     used_space.clone(),
+                    &store_config,
                 )
                 .await?,
             }),
             node_info,
             used_space,
+            store_config,
+            metadata_config,
+            command_sender,
+            commands,
             network_api,
             network_events,
         };
@@ -201,18 +257,136 @@ impl Node {
         self.network_api.our_prefix().await
     }
 
+    /// Returns a sender of `Command`s to the running node.
+    pub fn command_sender(&self) -> mpsc::UnboundedSender<Command> {
+        self.command_sender.clone()
+    }
+
     /// Starts the node, and runs the main event loop.
     /// Blocks until the node is terminated, which is done
     /// by client sending in a `Command` to free it.
+    /// The node then stops taking in new work, and flushes
+    /// its state to disk within `SHUTDOWN_DEADLINE` before returning.
     pub async fn run(&mut self) -> Result<()> {
-        while let Some(event) = self.network_events.next().await {
-            // tokio spawn should only be needed around intensive tasks, ie sign/verify
-            match map_routing_event(event, &self.network_api).await {
-                Mapping::Ok { op, ctx } => self.process_while_any(op, ctx).await,
-                Mapping::Error(error) => handle_error(error),
+        let mut scrub_timer = time::interval_at(Instant::now() + SCRUB_INTERVAL, SCRUB_INTERVAL);
+        let scrub_enabled = self.store_config.scrub_rate > 0;
+        let mut free_space_timer =
+            time::interval_at(Instant::now() + FREE_SPACE_INTERVAL, FREE_SPACE_INTERVAL);
+        let mut replication_check_timer = time::interval_at(
+            Instant::now() + REPLICATION_CHECK_INTERVAL,
+            REPLICATION_CHECK_INTERVAL,
+        );
+        let mut challenge_timer =
+            time::interval_at(Instant::now() + CHALLENGE_INTERVAL, CHALLENGE_INTERVAL);
+        let mut audit_timer = time::interval_at(Instant::now() + AUDIT_INTERVAL, AUDIT_INTERVAL);
+        let mut storage_report_timer = time::interval_at(
+            Instant::now() + STORAGE_REPORT_INTERVAL,
+            STORAGE_REPORT_INTERVAL,
+        );
+        let mut hand_off_retry_timer = time::interval_at(
+            Instant::now() + HAND_OFF_RETRY_INTERVAL,
+            HAND_OFF_RETRY_INTERVAL,
+        );
+
+        loop {
+            tokio::select! {
+                event = self.network_events.next() => {
+                    let event = match event {
+                        Some(event) => event,
+                        None => break,
+                    };
+                    // tokio spawn should only be needed around intensive tasks, ie sign/verify
+                    match map_routing_event(event, &self.network_api).await {
+                        Mapping::Ok { op, ctx } => self.process_while_any(op, ctx).await,
+                        Mapping::Error(error) => handle_error(error),
+                    }
+                }
+                _ = scrub_timer.tick(), if scrub_enabled => {
+                    self.process_while_any(NodeDuty::ScrubChunks, None).await
+                }
+                _ = free_space_timer.tick() => {
+                    if let Err(error) = self.used_space.refresh_free_space(self.node_info.path()).await {
+                        warn!("Could not measure free disk space: {}", error);
+                    }
+                }
+                _ = replication_check_timer.tick() => {
+                    self.process_while_any(NodeDuty::CheckPendingReplications, None).await
+                }
+                _ = challenge_timer.tick() => {
+                    self.process_while_any(NodeDuty::ChallengeHolders, None).await
+                }
+                _ = audit_timer.tick() => {
+                    self.process_while_any(NodeDuty::AuditReplication, None).await
+                }
+                _ = storage_report_timer.tick() => {
+                    self.process_while_any(NodeDuty::CheckStorage, None).await
+                }
+                _ = hand_off_retry_timer.tick() => {
+                    self.process_while_any(NodeDuty::RetryHandOff, None).await
+                }
+                Some(command) = self.commands.recv() => {
+                    info!("Received command: {:?}", command);
+                    match command {
+                        Command::Shutdown => break,
+                        Command::ReconcileUsedSpace => {
+                            self.process_while_any(NodeDuty::ReconcileUsedSpace, None).await
+                        }
+                        Command::SetMaxCapacity(max_capacity) => {
+                            self.process_while_any(NodeDuty::SetMaxCapacity(max_capacity), None).await
+                        }
+                        Command::ReloadConfig => match Config::reload() {
+                            Ok(config) => {
+                                let max_capacity = config.max_capacity();
+                                self.process_while_any(NodeDuty::SetMaxCapacity(max_capacity), None).await
+                            }
+                            Err(error) => warn!("Could not reload the config: {}", error),
+                        },
+                        Command::LogAdultScores => {
+                            self.process_while_any(NodeDuty::LogAdultScores, None).await
+                        }
+                        Command::Decommission => {
+                            self.process_while_any(NodeDuty::Decommission, None).await
+                        }
+                    }
+                }
             }
         }
 
+        self.shutdown().await
+    }
+
+    // Once decommissioned, with all its chunks handed off, the node shuts down.
+    fn leave_if_handed_off(&self) {
+        if let Role::Adult(adult) = &self.role {
+            if adult.chunks.handed_off() {
+                info!("All chunks handed off, leaving");
+                if self.command_sender.send(Command::Shutdown).is_err() {
+                    warn!("Could not shut down after decommissioning");
+                }
+            }
+        }
+    }
+
+    // Called once the event loop is left, so the batch of duties being processed has been
+    // completed and no new ones are taken in.
+    async fn shutdown(&mut self) -> Result<()> {
+        info!("Shutting down, flushing state to disk");
+        match time::timeout(SHUTDOWN_DEADLINE, self.flush()).await {
+            Ok(result) => result,
+            Err(_) => {
+                error!("Could not flush state within {:?}", SHUTDOWN_DEADLINE);
+                Err(Error::Logic("Shutdown deadline exceeded".to_string()))
+            }
+        }
+    }
+
+    async fn flush(&self) -> Result<()> {
+        self.used_space.flush().await?;
+        if let Role::Elder(elder) = &self.role {
+            elder.meta_data.flush().await?;
+            elder.transfers.flush().await?;
+        }
+        info!("State flushed to disk");
         Ok(())
     }
 
@@ -0,0 +1,155 @@
+// Copyright 2021 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Messages between the nodes of a section that sn_messaging has no variants for yet.  They
+//! travel as the bytes of its `node::NodeMessage`, which is the placeholder it has for node
+//! messages, so that they're never mistaken for the client messages nodes also exchange.
+
+use crate::{utils, Result};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use sn_data_types::{BlobAddress, PublicKey};
+use sn_messaging::{client::Error as ErrorMessage, node::NodeMessage, MessageId};
+use xor_name::XorName;
+
+/// A message between the nodes of a section.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum NodeMsg {
+    /// An adult reported full, with `NodeSystemCmd::StorageFull`, has storage available again.
+    StorageAvailable {
+        /// Node ID of the adult.
+        node_id: PublicKey,
+        id: MessageId,
+    },
+    /// An adult's used space to capacity ratio, reported periodically.
+    StorageReport {
+        /// Node ID of the adult.
+        node_id: PublicKey,
+        used_space_ratio: f64,
+        id: MessageId,
+    },
+    /// Challenges a holder to prove it still holds a chunk, by hashing the nonce with its bytes.
+    StorageChallenge {
+        address: BlobAddress,
+        nonce: [u8; 32],
+        id: MessageId,
+    },
+    /// The answer to a `StorageChallenge`, with the proof `None` if the chunk isn't held.
+    ChallengeAnswer {
+        address: BlobAddress,
+        proof: Option<XorName>,
+        /// The id of the challenge.
+        correlation_id: MessageId,
+        id: MessageId,
+    },
+    /// A holder's copy of a chunk was lost, or a new holder couldn't fetch or store the chunk
+    /// to replicate it, so it has to be replicated elsewhere.
+    ChunkLost { address: BlobAddress, id: MessageId },
+    /// Another holder sent a corrupt copy of a chunk, to the new holder replicating it.
+    CorruptCopy {
+        address: BlobAddress,
+        /// The holder that sent the corrupt copy.
+        holder: XorName,
+        id: MessageId,
+    },
+    /// A departing holder asks for a chunk to be replicated elsewhere.
+    HandOffChunk { address: BlobAddress, id: MessageId },
+    /// The section confirms to a departing holder that a chunk has been replicated elsewhere.
+    /// Sent by each elder and aggregated at the holder, so the id is derived from both.
+    HandOffCompleted { address: BlobAddress, id: MessageId },
+    /// An adult stored a chunk a client wrote, or already held it.
+    ChunkStored {
+        address: BlobAddress,
+        /// The id of the client's write.
+        correlation_id: MessageId,
+        id: MessageId,
+    },
+    /// An adult failed to store a chunk a client wrote.
+    ChunkWriteFailed {
+        address: BlobAddress,
+        error: ErrorMessage,
+        /// The id of the client's write.
+        correlation_id: MessageId,
+        id: MessageId,
+    },
+    /// A holder served a chunk a client read.
+    ChunkServed {
+        address: BlobAddress,
+        /// The id of the client's read.
+        correlation_id: MessageId,
+        id: MessageId,
+    },
+    /// A holder failed to serve a chunk a client read.
+    ChunkReadFailed {
+        address: BlobAddress,
+        error: ErrorMessage,
+        /// The id of the client's read.
+        correlation_id: MessageId,
+        id: MessageId,
+    },
+}
+
+impl NodeMsg {
+    /// The id of the message.
+    pub fn id(&self) -> MessageId {
+        match self {
+            Self::StorageAvailable { id, .. }
+            | Self::StorageReport { id, .. }
+            | Self::StorageChallenge { id, .. }
+            | Self::ChallengeAnswer { id, .. }
+            | Self::ChunkLost { id, .. }
+            | Self::CorruptCopy { id, .. }
+            | Self::HandOffChunk { id, .. }
+            | Self::HandOffCompleted { id, .. }
+            | Self::ChunkStored { id, .. }
+            | Self::ChunkWriteFailed { id, .. }
+            | Self::ChunkServed { id, .. }
+            | Self::ChunkReadFailed { id, .. } => *id,
+        }
+    }
+
+    /// Deserialises a message from bytes received over the wire, failing if they aren't one.
+    pub fn from(bytes: Bytes) -> Result<Self> {
+        let msg = NodeMessage::from(bytes)?;
+        utils::deserialise(&msg.0)
+    }
+
+    /// Serialises the message into bytes ready to be sent over the wire.
+    pub fn serialize(&self) -> Result<Bytes> {
+        Ok(NodeMessage::new(utils::serialise(self)?).serialize()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bls::SecretKey;
+    use sn_messaging::client::Message;
+
+    #[test]
+    fn node_msgs_are_told_apart_from_client_msgs() -> Result<()> {
+        let node_id = PublicKey::from(SecretKey::random().public_key());
+        let msg = NodeMsg::StorageAvailable {
+            node_id,
+            id: MessageId::new(),
+        };
+        let bytes = msg.serialize()?;
+        assert!(Message::from(bytes.clone()).is_err());
+        match NodeMsg::from(bytes)? {
+            NodeMsg::StorageAvailable {
+                node_id: received,
+                id,
+            } => {
+                assert_eq!(received, node_id);
+                assert_eq!(id, msg.id());
+            }
+            other => panic!("Unexpected msg: {:?}", other),
+        }
+        Ok(())
+    }
+}
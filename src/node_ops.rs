@@ -6,6 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use crate::node_msg::NodeMsg;
 use bls::PublicKeySet;
 #[cfg(feature = "simulated-payouts")]
 use sn_data_types::Transfer;
@@ -16,7 +17,7 @@ use sn_data_types::{
     WalletHistory,
 };
 use sn_messaging::{
-    client::{BlobRead, BlobWrite, Message, NodeSystemCmd},
+    client::{BlobRead, BlobWrite, Error as ErrorMessage, Message, NodeSystemCmd},
     Aggregation, DstLocation, EndUser, MessageId, SrcLocation,
 };
 use sn_routing::{NodeElderChange, Prefix};
@@ -160,6 +161,9 @@ pub enum NodeDuty {
         name: XorName,
         age: u8,
     },
+    /// Check the storage usage, and
+    /// report it to the section.
+    CheckStorage,
     /// Storage reaching max capacity.
     ReachingMaxCapacity,
     /// Increment count of full nodes in the network
@@ -167,10 +171,24 @@ pub enum NodeDuty {
         /// Node ID of node that reached max capacity.
         node_id: PublicKey,
     },
+    /// Decrement count of full nodes in the network
+    DecrementFullNodeCount {
+        /// Node ID of node that has storage available again.
+        node_id: PublicKey,
+    },
+    /// Record the used space ratio an adult reported.
+    RecordUsedSpaceRatio {
+        /// Node ID of the adult.
+        node_id: PublicKey,
+        used_space_ratio: f64,
+    },
     /// Sets joining allowed to true or false.
     SetNodeJoinsAllowed(bool),
     /// Send a message to the specified dst.
     Send(OutgoingMsg),
+    /// Send a message sn_messaging has no
+    /// variant for yet to the specified dst.
+    SendNodeMsg(OutgoingNodeMsg),
     /// Send the same request to each individual node.
     SendToNodes {
         targets: BTreeSet<XorName>,
@@ -209,11 +227,150 @@ pub enum NodeDuty {
         new_holder: XorName,
         id: MessageId,
     },
+    /// A holder failed to send a chunk
+    /// being replicated, so ask the next one.
+    ReplicationFetchFailed {
+        correlation_id: MessageId,
+        holder: XorName,
+    },
+    /// Ask the next holders for chunks being
+    /// replicated that didn't arrive in time,
+    /// or, as elder, other adults for writes
+    /// not acknowledged in time.
+    CheckPendingReplications,
     /// Store a chunk that is a result of data replication
     /// on `MemberLeft`
     StoreChunkForReplication {
         data: Blob,
         correlation_id: MessageId,
+        /// The holder that sent it.
+        holder: XorName,
+    },
+    /// Challenge adults to prove they still
+    /// hold the chunks recorded for them.
+    ChallengeHolders,
+    /// Answer an elder's challenge to prove
+    /// a chunk is held.
+    AnswerStorageChallenge {
+        address: BlobAddress,
+        nonce: [u8; 32],
+        /// The elder that sent it.
+        elder: XorName,
+        /// The id of the challenge.
+        correlation_id: MessageId,
+    },
+    /// Check a holder's answer to a storage challenge.
+    ProcessStorageProof {
+        holder: XorName,
+        proof: Option<XorName>,
+        /// The id of the challenge.
+        correlation_id: MessageId,
+    },
+    /// Replicate the next batch of chunks
+    /// short of copies, if any.
+    AuditReplication,
+    /// Verify a batch of locally stored chunks,
+    /// quarantining corrupt ones.
+    ScrubChunks,
+    /// Recompute the used space of the local
+    /// chunk stores from their files.
+    ReconcileUsedSpace,
+    /// Change the max capacity of the local
+    /// storage, and report the resulting usage.
+    SetMaxCapacity(u64),
+    /// Storage usage back under the max
+    /// usage ratio, after being reported full.
+    ReachingFreeCapacity,
+    /// Log the scores of the adults
+    /// chunk operations were routed to.
+    LogAdultScores,
+    /// Process a report of a holder sending a corrupt
+    /// copy of a chunk for replication, by replacing it.
+    ProcessCorruptCopy {
+        address: BlobAddress,
+        /// The holder that reported it.
+        reporter: XorName,
+        /// The holder that sent the corrupt copy.
+        holder: XorName,
+    },
+    /// Process the loss of a holder's copy of a chunk,
+    /// by replicating it from the remaining holders.
+    ProcessLostChunk {
+        address: BlobAddress,
+        holder: XorName,
+    },
+    /// Process a holder serving a
+    /// chunk a client read.
+    ProcessChunkServed {
+        address: BlobAddress,
+        holder: XorName,
+        /// The id of the client's read.
+        correlation_id: MessageId,
+    },
+    /// Process a holder failing to serve
+    /// a chunk a client read.
+    ProcessChunkReadError {
+        address: BlobAddress,
+        holder: XorName,
+        error: ErrorMessage,
+        /// The id of the client's read.
+        correlation_id: MessageId,
+    },
+    /// Process an adult storing a
+    /// chunk a client wrote.
+    ProcessChunkStored {
+        address: BlobAddress,
+        holder: XorName,
+        /// The id of the client's write.
+        correlation_id: MessageId,
+    },
+    /// Process an adult failing to store
+    /// a chunk a client wrote.
+    ProcessChunkWriteError {
+        address: BlobAddress,
+        holder: XorName,
+        error: ErrorMessage,
+        /// The id of the client's write.
+        correlation_id: MessageId,
+    },
+    /// Stop taking in chunks, and have the stored
+    /// ones replicated elsewhere before leaving.
+    Decommission,
+    /// Ask again for the hand-off of the chunks
+    /// not confirmed yet, when decommissioning.
+    RetryHandOff,
+    /// Have a chunk replicated away from
+    /// a holder being decommissioned.
+    HandOffChunk {
+        address: BlobAddress,
+        /// The departing holder.
+        holder: XorName,
+    },
+    /// Confirm to a departing holder that
+    /// a chunk has been replicated elsewhere.
+    ConfirmHandOff {
+        address: BlobAddress,
+        holder: XorName,
+    },
+    /// Drop a chunk from the ones to hand off,
+    /// once the section confirmed it.
+    CompleteHandOff {
+        address: BlobAddress,
+    },
+    /// Report to the section that a
+    /// chunk was replicated here.
+    ReportReplicationCompleted {
+        address: BlobAddress,
+        /// The id of the cmd to
+        /// replicate the chunk.
+        correlation_id: MessageId,
+    },
+    /// Process a report of a chunk having
+    /// been replicated at a new holder.
+    ProcessReplicationCompleted {
+        address: BlobAddress,
+        /// The node that sent the report.
+        node: XorName,
     },
     NoOp,
 }
@@ -255,12 +412,16 @@ impl Debug for NodeDuty {
             Self::GetSectionElders { .. } => write!(f, "GetSectionElders"),
 
             Self::NoOp => write!(f, "No op."),
+            Self::CheckStorage => write!(f, "CheckStorage"),
             Self::ReachingMaxCapacity => write!(f, "ReachingMaxCapacity"),
             Self::ProcessLostMember { .. } => write!(f, "ProcessLostMember"),
             //Self::ProcessRelocatedMember { .. } => write!(f, "ProcessRelocatedMember"),
             Self::IncrementFullNodeCount { .. } => write!(f, "IncrementFullNodeCount"),
+            Self::DecrementFullNodeCount { .. } => write!(f, "DecrementFullNodeCount"),
+            Self::RecordUsedSpaceRatio { .. } => write!(f, "RecordUsedSpaceRatio"),
             Self::SetNodeJoinsAllowed(_) => write!(f, "SetNodeJoinsAllowed"),
             Self::Send(msg) => write!(f, "Send [ msg: {:?} ]", msg),
+            Self::SendNodeMsg(msg) => write!(f, "SendNodeMsg [ msg: {:?} ]", msg),
             Self::SendToNodes { targets, msg } => {
                 write!(f, "SendToNodes [ targets: {:?}, msg: {:?} ]", targets, msg)
             }
@@ -270,6 +431,30 @@ impl Debug for NodeDuty {
             Self::ReplicateChunk { .. } => write!(f, "ReplicateChunk"),
             Self::GetChunkForReplication { .. } => write!(f, "GetChunkForReplication"),
             Self::StoreChunkForReplication { .. } => write!(f, "StoreChunkForReplication"),
+            Self::ReplicationFetchFailed { .. } => write!(f, "ReplicationFetchFailed"),
+            Self::CheckPendingReplications => write!(f, "CheckPendingReplications"),
+            Self::ProcessCorruptCopy { .. } => write!(f, "ProcessCorruptCopy"),
+            Self::ChallengeHolders => write!(f, "ChallengeHolders"),
+            Self::AnswerStorageChallenge { .. } => write!(f, "AnswerStorageChallenge"),
+            Self::ProcessStorageProof { .. } => write!(f, "ProcessStorageProof"),
+            Self::AuditReplication => write!(f, "AuditReplication"),
+            Self::ScrubChunks => write!(f, "ScrubChunks"),
+            Self::ReconcileUsedSpace => write!(f, "ReconcileUsedSpace"),
+            Self::SetMaxCapacity(max_capacity) => write!(f, "SetMaxCapacity({})", max_capacity),
+            Self::ReachingFreeCapacity => write!(f, "ReachingFreeCapacity"),
+            Self::LogAdultScores => write!(f, "LogAdultScores"),
+            Self::ProcessLostChunk { .. } => write!(f, "ProcessLostChunk"),
+            Self::ProcessChunkServed { .. } => write!(f, "ProcessChunkServed"),
+            Self::ProcessChunkReadError { .. } => write!(f, "ProcessChunkReadError"),
+            Self::ProcessChunkStored { .. } => write!(f, "ProcessChunkStored"),
+            Self::ProcessChunkWriteError { .. } => write!(f, "ProcessChunkWriteError"),
+            Self::Decommission => write!(f, "Decommission"),
+            Self::RetryHandOff => write!(f, "RetryHandOff"),
+            Self::HandOffChunk { .. } => write!(f, "HandOffChunk"),
+            Self::ConfirmHandOff { .. } => write!(f, "ConfirmHandOff"),
+            Self::CompleteHandOff { .. } => write!(f, "CompleteHandOff"),
+            Self::ReportReplicationCompleted { .. } => write!(f, "ReportReplicationCompleted"),
+            Self::ProcessReplicationCompleted { .. } => write!(f, "ProcessReplicationCompleted"),
         }
     }
 }
@@ -289,3 +474,17 @@ impl OutgoingMsg {
         self.msg.id()
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct OutgoingNodeMsg {
+    pub msg: NodeMsg,
+    pub dst: DstLocation,
+    pub section_source: bool,
+    pub aggregation: Aggregation,
+}
+
+impl OutgoingNodeMsg {
+    pub fn id(&self) -> MessageId {
+        self.msg.id()
+    }
+}
@@ -6,7 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{utils, Error, Result};
+use crate::{encryption::EncryptionKey, utils, Error, Result};
 use bls::{self, serde_impl::SerdeSecret, PublicKey, SecretKey, PK_SIZE};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
@@ -15,6 +15,7 @@ use tokio::fs;
 const AGE_GROUP_FILENAME: &str = "age_group";
 const REWARD_PUBLIC_KEY_FILENAME: &str = "reward_public_key";
 const REWARD_SECRET_KEY_FILENAME: &str = "reward_secret_key";
+const ENCRYPTION_KEY_FILENAME: &str = "encryption_key";
 
 /// Writes the public and secret key to different locations at disk.
 pub async fn store_new_reward_keypair(
@@ -29,6 +30,35 @@ pub async fn store_new_reward_keypair(
     Ok(())
 }
 
+/// Returns the key the node's local data is encrypted with, unlocked with `secret`.  The key is
+/// generated and stored, sealed with `secret`, the first time one is given.  Without a `secret`
+/// no key is used, which is an error once one has been stored.
+pub(crate) async fn load_encryption_key(
+    root_dir: &Path,
+    secret: Option<&[u8]>,
+) -> Result<Option<EncryptionKey>> {
+    let path = root_dir.join(ENCRYPTION_KEY_FILENAME);
+    let stored = if path.is_file() {
+        Some(fs::read_to_string(&path).await?)
+    } else {
+        None
+    };
+    match (secret, stored) {
+        (None, None) => Ok(None),
+        (None, Some(_)) => Err(Error::Configuration(
+            "Node data is encrypted: a passphrase or key file is needed to unlock it".to_string(),
+        )),
+        (Some(secret), Some(stored)) => {
+            Ok(Some(EncryptionKey::unwrap(&parse_hex(&stored), secret)?))
+        }
+        (Some(secret), None) => {
+            let key = EncryptionKey::generate()?;
+            fs::write(path, vec_to_hex(key.wrap(secret)?)).await?;
+            Ok(Some(key))
+        }
+    }
+}
+
 // /// Writes the info to disk.
 // pub async fn store_age_group(root_dir: &Path, age_group: &AgeGroup) -> Result<()> {
 //     let path = root_dir.join(AGE_GROUP_FILENAME);
@@ -105,6 +135,26 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn encryption_key() -> Result<()> {
+        let root_dir = tempdir::TempDir::new("root")?;
+        let root_dir = root_dir.path();
+        assert!(load_encryption_key(root_dir, None).await?.is_none());
+
+        let key = load_encryption_key(root_dir, Some(b"passphrase"))
+            .await?
+            .ok_or_else(|| Error::Logic("No key".to_string()))?;
+        let sealed = key.seal(b"data", &[])?;
+        let reloaded = load_encryption_key(root_dir, Some(b"passphrase"))
+            .await?
+            .ok_or_else(|| Error::Logic("No key".to_string()))?;
+        assert_eq!(reloaded.open(sealed, &[])?, b"data");
+
+        assert!(load_encryption_key(root_dir, Some(b"wrong")).await.is_err());
+        assert!(load_encryption_key(root_dir, None).await.is_err());
+        Ok(())
+    }
+
     fn gen_key() -> PublicKey {
         SecretKey::random().public_key()
     }
@@ -22,7 +22,6 @@ pub(crate) trait ToDbKey: Serialize {
     }
 }
 
-#[allow(unused)]
 pub fn from_db_key<T: DeserializeOwned>(key: &str) -> Result<T> {
     let decoded = hex::decode(key).map_err(|e| Error::Logic(e.to_string()))?;
     utils::deserialise(&decoded)
@@ -7,7 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{replica_signing::ReplicaSigningImpl, replicas::Replicas, ReplicaInfo};
-use crate::{Error, Network, NodeInfo, Result};
+use crate::{encryption::EncryptionKey, Error, Network, NodeInfo, Result};
 use sn_data_types::{ActorHistory, Credit, CreditAgreementProof, PublicKey, SignedCredit, Token};
 use std::collections::BTreeMap;
 
@@ -15,10 +15,11 @@ pub async fn transfer_replicas(
     node_info: &NodeInfo,
     network: &Network,
     user_wallets: BTreeMap<PublicKey, ActorHistory>,
+    encryption: Option<EncryptionKey>,
 ) -> Result<Replicas<ReplicaSigningImpl>> {
     let root_dir = node_info.root_dir.clone();
     let info = replica_info(node_info, network).await?;
-    Replicas::new(root_dir, info, user_wallets).await
+    Replicas::new(root_dir, encryption, info, user_wallets).await
 }
 
 pub async fn replica_info(
@@ -91,6 +91,11 @@ impl Transfers {
         }
     }
 
+    /// Writes the wallet stores to disk.
+    pub async fn flush(&self) -> Result<()> {
+        self.replicas.flush().await
+    }
+
     /// The total amount in wallets managed
     /// by the replicas in this section.
     pub async fn managed_amount(&self) -> Result<Token> {
@@ -120,13 +125,32 @@ impl Transfers {
         self.rate_limit.increase_full_node_count(node_id).await
     }
 
-    ///
-    pub async fn decrease_full_node_count_if_present(&mut self, node_name: XorName) -> Result<()> {
+    /// Records that an adult has storage available again.
+    pub async fn storage_available(&mut self, node_name: XorName) -> Result<()> {
+        self.rate_limit.storage_available(node_name).await
+    }
+
+    /// Records the used space ratio an adult reported, returning whether it is now full.
+    pub async fn record_used_space_ratio(
+        &mut self,
+        node_name: XorName,
+        used_space_ratio: f64,
+    ) -> Result<bool> {
         self.rate_limit
-            .decrease_full_node_count_if_present(node_name)
+            .record_used_space_ratio(node_name, used_space_ratio)
             .await
     }
 
+    /// Forgets about the capacity of an adult that left the section.
+    pub async fn remove_node(&mut self, node_name: XorName) -> Result<()> {
+        self.rate_limit.remove_node(node_name).await
+    }
+
+    /// The mean unused space ratio of the adults in our section.
+    pub async fn free_capacity(&self) -> f64 {
+        self.rate_limit.free_capacity().await
+    }
+
     /// Get latest StoreCost for the given number of bytes.
     /// Also check for Section storage capacity and report accordingly.
     pub async fn get_store_cost(
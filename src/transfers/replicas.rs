@@ -7,7 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{replica_signing::ReplicaSigning, store::TransferStore};
-use crate::{Error, Result};
+use crate::{encryption::EncryptionKey, Error, Result};
 use bls::PublicKeySet;
 use dashmap::DashMap;
 use futures::lock::Mutex;
@@ -50,6 +50,7 @@ where
     T: ReplicaSigning,
 {
     root_dir: PathBuf,
+    encryption: Option<EncryptionKey>,
     info: ReplicaInfo<T>,
     locks: WalletLocks,
     self_lock: Arc<Mutex<usize>>,
@@ -58,11 +59,13 @@ where
 impl<T: ReplicaSigning> Replicas<T> {
     pub(crate) async fn new(
         root_dir: PathBuf,
+        encryption: Option<EncryptionKey>,
         info: ReplicaInfo<T>,
         user_wallets: BTreeMap<PublicKey, ActorHistory>,
     ) -> Result<Self> {
         let instance = Self {
             root_dir,
+            encryption,
             info,
             locks: DashMap::new(),
             self_lock: Arc::new(Mutex::new(0)),
@@ -71,6 +74,19 @@ impl<T: ReplicaSigning> Replicas<T> {
         Ok(instance)
     }
 
+    /// Writes the stores of the wallets to disk, waiting for the changes in progress.
+    pub async fn flush(&self) -> Result<()> {
+        let stores: Vec<_> = self
+            .locks
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect();
+        for store in stores {
+            store.lock().await.flush()?;
+        }
+        Ok(())
+    }
+
     pub fn merge(&mut self, user_wallets: BTreeMap<PublicKey, ActorHistory>) {
         self.setup(user_wallets); // TODO: fix this!!!! (this duplciates entries in db)
     }
@@ -144,7 +160,9 @@ impl<T: ReplicaSigning> Replicas<T> {
             .locks
             .iter()
             .map(|r| *r.key())
-            .filter_map(|id| TransferStore::new(id.into(), &self.root_dir).ok())
+            .filter_map(|id| {
+                TransferStore::new(id.into(), &self.root_dir, self.encryption.clone()).ok()
+            })
             .map(|store| store.get_all())
             .flatten()
             .collect();
@@ -153,7 +171,7 @@ impl<T: ReplicaSigning> Replicas<T> {
 
     /// History of actor
     pub fn history(&self, id: PublicKey) -> Result<ActorHistory> {
-        let store = TransferStore::new(id.into(), &self.root_dir);
+        let store = TransferStore::new(id.into(), &self.root_dir, self.encryption.clone());
 
         if let Err(error) = store {
             // hmm.. can we handle this in a better way?
@@ -216,7 +234,7 @@ impl<T: ReplicaSigning> Replicas<T> {
     ///
     pub async fn balance(&self, id: PublicKey) -> Result<Token> {
         debug!("Replica: Getting balance of: {:?}", id);
-        let store = match TransferStore::new(id.into(), &self.root_dir) {
+        let store = match TransferStore::new(id.into(), &self.root_dir, self.encryption.clone()) {
             Ok(store) => store,
             // store load failed, so we return 0 balance
             Err(_) => return Ok(Token::from_nano(0)),
@@ -373,7 +391,8 @@ impl<T: ReplicaSigning> Replicas<T> {
                     Ok(store) => store,
                     Err(_) => {
                         // no key lock (hence no store), so we create one
-                        let store = TransferStore::new(id.into(), &self.root_dir)?;
+                        let store =
+                            TransferStore::new(id.into(), &self.root_dir, self.encryption.clone())?;
                         let locked_store = Arc::new(Mutex::new(store));
                         let _ = self.locks.insert(id, locked_store.clone());
                         let _ = self_lock.overflowing_add(0); // resolve: is a usage at end of block necessary to actually engage the lock?
@@ -448,11 +467,14 @@ impl<T: ReplicaSigning> Replicas<T> {
         let key_lock = match self.load_key_lock(id).await {
             Ok(lock) => lock,
             Err(_) => {
-                let store = match TransferStore::new(id.into(), &self.root_dir) {
-                    Ok(store) => store,
-                    // no key lock, so we create one for this payout...
-                    Err(_e) => TransferStore::new(id.into(), &self.root_dir)?,
-                };
+                let store =
+                    match TransferStore::new(id.into(), &self.root_dir, self.encryption.clone()) {
+                        Ok(store) => store,
+                        // no key lock, so we create one for this payout...
+                        Err(_e) => {
+                            TransferStore::new(id.into(), &self.root_dir, self.encryption.clone())?
+                        }
+                    };
                 let locked_store = Arc::new(Mutex::new(store));
                 let _ = self.locks.insert(id, locked_store.clone());
                 locked_store
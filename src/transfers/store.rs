@@ -6,9 +6,8 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{utils, Error, Result, ToDbKey};
+use crate::{encryption::EncryptionKey, utils, utils::AutoDumpDb, Error, Result, ToDbKey};
 use log::{debug, trace};
-use pickledb::PickleDb;
 use serde::{de::DeserializeOwned, Serialize};
 use std::{
     fmt::Debug,
@@ -23,7 +22,7 @@ const DB_EXTENSION: &str = ".db";
 /// Disk storage for transfers.
 pub struct TransferStore<TEvent: Debug + Serialize + DeserializeOwned> {
     id: XorName,
-    db: PickleDb,
+    db: AutoDumpDb,
     _phantom: PhantomData<TEvent>,
 }
 
@@ -31,12 +30,12 @@ impl<'a, TEvent: Debug + Serialize + DeserializeOwned> TransferStore<TEvent>
 where
     TEvent: 'a,
 {
-    pub fn new(id: XorName, root_dir: &Path) -> Result<Self> {
+    pub fn new(id: XorName, root_dir: &Path, encryption: Option<EncryptionKey>) -> Result<Self> {
         let db_dir = root_dir.join(Path::new(TRANSFERS_DIR_NAME));
         let db_name = format!("{}{}", id.to_db_key()?, DB_EXTENSION);
         Ok(Self {
             id,
-            db: utils::new_auto_dump_db(db_dir.as_path(), db_name)?,
+            db: utils::new_auto_dump_db(db_dir.as_path(), db_name, encryption)?,
             _phantom: PhantomData::default(),
         })
     }
@@ -81,7 +80,12 @@ where
                 key, event
             )));
         }
-        self.db.set(key, &event).map_err(Error::PickleDb)
+        self.db.set(key, &event)
+    }
+
+    /// Writes the store to disk.
+    pub fn flush(&mut self) -> Result<()> {
+        self.db.dump()
     }
 }
 
@@ -103,7 +107,7 @@ mod test {
         let id = xor_name::XorName::random();
         let tmp_dir = TempDir::new("root")?;
         let root_dir = tmp_dir.into_path();
-        let mut store = TransferStore::new(id, &root_dir)?;
+        let mut store = TransferStore::new(id, &root_dir, None)?;
         let wallet_id = get_random_pk();
         let mut rng = rand::thread_rng();
         let bls_secret_key = SecretKeySet::random(0, &mut rng);
@@ -8,27 +8,38 @@
 
 //! Utilities
 
-use crate::{config_handler::Config, Error, Result};
+use crate::{config_handler::Config, encryption::EncryptionKey, Error, Result};
 use bytes::Bytes;
 use flexi_logger::{DeferredNow, Logger};
-use log::debug;
+use log::{debug, error};
 use log::{Log, Metadata, Record};
 use pickledb::{PickleDb, PickleDbDumpPolicy};
 use rand::{distributions::Standard, CryptoRng, Rng};
 use serde::{de::DeserializeOwned, Serialize};
 use std::io::Write;
-use std::{fs, path::Path};
+use std::{collections::HashMap, fs, path::Path};
 
 const NODE_MODULE_NAME: &str = "sn_node";
 
+/// The contents of a `PickleDb` file: serialised values and lists by key.
+type DbContents = (HashMap<String, Vec<u8>>, HashMap<String, Vec<Vec<u8>>>);
+
+/// Key recording that the values of a database are encrypted.
+const ENCRYPTED_DB_MARKER: &str = "__encrypted";
+
+/// Loads the database `db_name` in `db_dir`, creating it if it doesn't exist yet.
+///
+/// Given an `encryption` key, values are encrypted with it.  The values of a database previously
+/// stored unencrypted are encrypted on loading it; going back is an error.
 pub(crate) fn new_auto_dump_db<D: AsRef<Path>, N: AsRef<Path>>(
     db_dir: D,
     db_name: N,
-) -> Result<PickleDb> {
+    encryption: Option<EncryptionKey>,
+) -> Result<AutoDumpDb> {
     let db_path = db_dir.as_ref().join(db_name);
     //debug!("Trying to load Database at {}", db_path.display(),);
-    match PickleDb::load_bin(db_path.clone(), PickleDbDumpPolicy::AutoDump) {
-        Ok(db) => Ok(db),
+    let mut db = match PickleDb::load_bin(db_path.clone(), PickleDbDumpPolicy::AutoDump) {
+        Ok(db) => db,
         Err(_) => {
             //debug!("Database not found, creating it..");
             fs::create_dir_all(db_dir)?;
@@ -36,8 +47,150 @@ pub(crate) fn new_auto_dump_db<D: AsRef<Path>, N: AsRef<Path>>(
             // dump is needed to actually write the db to disk.
             _db.dump()?;
             //debug!("Created database");
-            PickleDb::load_bin(db_path, PickleDbDumpPolicy::AutoDump).map_err(Error::PickleDb)
+            PickleDb::load_bin(db_path.clone(), PickleDbDumpPolicy::AutoDump)
+                .map_err(Error::PickleDb)?
+        }
+    };
+    match &encryption {
+        Some(key) if !db.exists(ENCRYPTED_DB_MARKER) => {
+            encrypt_db_values(&mut db, &db_path, key)?;
+            db.set(ENCRYPTED_DB_MARKER, &true)?;
+        }
+        None if db.exists(ENCRYPTED_DB_MARKER) => {
+            return Err(Error::Encryption(format!(
+                "Database at {} is encrypted, but no encryption key is set",
+                db_path.display()
+            )))
         }
+        _ => (),
+    }
+    Ok(AutoDumpDb { db, encryption })
+}
+
+/// Encrypts the values of a `db` stored unencrypted.  `PickleDb` only hands out deserialised
+/// values, so the serialised ones are read from its file.
+fn encrypt_db_values(db: &mut PickleDb, db_path: &Path, key: &EncryptionKey) -> Result<()> {
+    if db.total_keys() == 0 {
+        return Ok(());
+    }
+    debug!("Encrypting database at {}", db_path.display());
+    let (values, lists): DbContents = deserialise(&fs::read(db_path)?)?;
+    // Lists become values, as they would otherwise be kept unencrypted.
+    let lists = lists
+        .into_iter()
+        .map(|(name, items)| (name, list_value(items)));
+    for (name, value) in values.into_iter().chain(lists) {
+        db.set(&name, &key.seal(&value, name.as_bytes())?)?;
+    }
+    Ok(())
+}
+
+/// Serialises the items of a `PickleDb` list as one value, which deserialises to a `Vec` of
+/// them, as bincode encodes one as its length followed by its items.
+fn list_value(items: Vec<Vec<u8>>) -> Vec<u8> {
+    let mut value = (items.len() as u64).to_le_bytes().to_vec();
+    value.extend(items.into_iter().flatten());
+    value
+}
+
+/// Reads the values of the database at `db_path`, serialised and decrypted, e.g. to move them to
+/// another store.  Lists are read as values holding a `Vec` of their items.
+pub(crate) fn read_db_values(
+    db_path: &Path,
+    encryption: Option<&EncryptionKey>,
+) -> Result<Vec<(String, Vec<u8>)>> {
+    let (mut values, lists): DbContents = deserialise(&fs::read(db_path)?)?;
+    let mut entries: Vec<_> = if values.remove(ENCRYPTED_DB_MARKER).is_none() {
+        values.into_iter().collect()
+    } else {
+        let key = encryption.ok_or_else(|| {
+            Error::Encryption(format!(
+                "Database at {} is encrypted, but no encryption key is set",
+                db_path.display()
+            ))
+        })?;
+        values
+            .into_iter()
+            .map(|(name, value)| {
+                let sealed: Vec<u8> = deserialise(&value)?;
+                let value = key.open(sealed, name.as_bytes())?;
+                Ok((name, value))
+            })
+            .collect::<Result<_>>()?
+    };
+    // Lists are kept unencrypted in databases encrypted before they were turned into values.
+    entries.extend(
+        lists
+            .into_iter()
+            .map(|(name, items)| (name, list_value(items))),
+    );
+    Ok(entries)
+}
+
+/// A `PickleDb` that is dumped to disk on every change, with its values encrypted if the node
+/// has an encryption key.
+pub(crate) struct AutoDumpDb {
+    db: PickleDb,
+    encryption: Option<EncryptionKey>,
+}
+
+impl AutoDumpDb {
+    /// Returns the value of `key`, if present and readable.
+    pub fn get<V: DeserializeOwned>(&self, key: &str) -> Option<V> {
+        match &self.encryption {
+            Some(encryption) => {
+                let sealed = self.db.get::<Vec<u8>>(key)?;
+                match encryption.open(sealed, key.as_bytes()) {
+                    Ok(value) => deserialise(&value).ok(),
+                    Err(error) => {
+                        error!("Could not decrypt the value of {}: {}", key, error);
+                        None
+                    }
+                }
+            }
+            None => self.db.get(key),
+        }
+    }
+
+    /// Sets the value of `key`.
+    pub fn set<V: Serialize>(&mut self, key: &str, value: &V) -> Result<()> {
+        match &self.encryption {
+            Some(encryption) => {
+                let sealed = encryption.seal(&serialise(value)?, key.as_bytes())?;
+                self.db.set(key, &sealed)?
+            }
+            None => self.db.set(key, value)?,
+        }
+        Ok(())
+    }
+
+    /// Removes `key`, returning whether it was present.
+    pub fn rem(&mut self, key: &str) -> Result<bool> {
+        Ok(self.db.rem(key)?)
+    }
+
+    /// Whether `key` is present.
+    pub fn exists(&self, key: &str) -> bool {
+        key != ENCRYPTED_DB_MARKER && self.db.exists(key)
+    }
+
+    /// All the keys present.
+    pub fn get_all(&self) -> Vec<String> {
+        self.db
+            .get_all()
+            .into_iter()
+            .filter(|key| key != ENCRYPTED_DB_MARKER)
+            .collect()
+    }
+
+    /// Number of keys present.
+    pub fn total_keys(&self) -> usize {
+        self.get_all().len()
+    }
+
+    /// Writes the database to disk.
+    pub fn dump(&mut self) -> Result<()> {
+        Ok(self.db.dump()?)
     }
 }
 
@@ -51,7 +204,6 @@ pub(crate) fn serialise<T: Serialize>(data: &T) -> Result<Bytes> {
     Ok(Bytes::copy_from_slice(serialised_data.as_slice()))
 }
 
-#[allow(unused)]
 pub(crate) fn deserialise<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
     bincode::deserialize(bytes).map_err(Error::Bincode)
 }
@@ -117,4 +269,80 @@ impl Log for LoggerWrapper {
 pub enum Command {
     /// Shutdown the node
     Shutdown,
+    /// Recompute the used space of the node's chunk stores from their files
+    ReconcileUsedSpace,
+    /// Change the maximum capacity of the node's storage, in bytes
+    SetMaxCapacity(u64),
+    /// Read the config again and apply the settings that can change at runtime
+    ReloadConfig,
+    /// Log the scores of the adults chunk operations were routed to, when an elder
+    LogAdultScores,
+    /// Hand off the stored chunks to other adults, then leave, when an adult
+    Decommission,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn auto_dump_db_encryption() -> Result<()> {
+        let dir = TempDir::new("db")?;
+        let key = EncryptionKey::generate()?;
+
+        let mut db = new_auto_dump_db(dir.path(), "plain.db", None)?;
+        db.set("a", &"private contents".to_string())?;
+        drop(db);
+
+        // Values stored unencrypted are encrypted once a key is set.
+        let db = new_auto_dump_db(dir.path(), "plain.db", Some(key.clone()))?;
+        assert_eq!(db.get::<String>("a"), Some("private contents".to_string()));
+        assert_eq!(db.get_all(), vec!["a".to_string()]);
+        assert_eq!(db.total_keys(), 1);
+        let contents = fs::read(dir.path().join("plain.db"))?;
+        assert!(!contents
+            .windows("private".len())
+            .any(|window| window == b"private"));
+        drop(db);
+
+        // But not the other way round.
+        assert!(new_auto_dump_db(dir.path(), "plain.db", None).is_err());
+        let db = new_auto_dump_db(dir.path(), "plain.db", Some(EncryptionKey::generate()?))?;
+        assert_eq!(db.get::<String>("a"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn auto_dump_db_encryption_keeps_lists() -> Result<()> {
+        let dir = TempDir::new("db")?;
+        let key = EncryptionKey::generate()?;
+        let mut db = PickleDb::new_bin(dir.path().join("lists.db"), PickleDbDumpPolicy::AutoDump);
+        let _ = db.lcreate("adult")?.ladd(&"Node Full");
+        db.set("a", &1u8)?;
+        drop(db);
+
+        let db = new_auto_dump_db(dir.path(), "lists.db", Some(key.clone()))?;
+        assert_eq!(
+            db.get::<Vec<String>>("adult"),
+            Some(vec!["Node Full".to_string()])
+        );
+        assert_eq!(db.get::<u8>("a"), Some(1));
+        let contents = fs::read(dir.path().join("lists.db"))?;
+        assert!(!contents
+            .windows("Node".len())
+            .any(|window| window == b"Node"));
+        drop(db);
+
+        let mut values = read_db_values(&dir.path().join("lists.db"), Some(&key))?;
+        values.sort();
+        assert_eq!(
+            values,
+            vec![
+                ("a".to_string(), serialise(&1u8)?.to_vec()),
+                ("adult".to_string(), serialise(&vec!["Node Full"])?.to_vec()),
+            ]
+        );
+        Ok(())
+    }
 }